@@ -0,0 +1,616 @@
+use anyhow::{Context, Result};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use clap::Subcommand;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{error::AppError, AppState};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SESSION_COOKIE: &str = "webmux_session";
+
+const SECRET_FILE: &str = "secret.key";
+const USERS_FILE: &str = "users.json";
+const REVOKED_FILE: &str = "revoked.json";
+const LOGIN_PAGE: &str = include_str!("login.html");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserRecord {
+    password_hash: String,
+    // Bumped on every password change so tokens issued before it stop validating
+    generation: u64,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TokenClaims {
+    sub: String,
+    jti: String,
+    gen: u64,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthSession {
+    pub username: String,
+    pub token_id: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+struct AuthState {
+    secret: Vec<u8>,
+    users: HashMap<String, UserRecord>,
+    // Revoked token ids mapped to their expiry, pruned once expired
+    revoked: HashMap<String, DateTime<Utc>>,
+    secret_mtime: Option<SystemTime>,
+    users_mtime: Option<SystemTime>,
+    revoked_mtime: Option<SystemTime>,
+}
+
+pub struct AuthManager {
+    dir: PathBuf,
+    enabled: bool,
+    session_ttl: Duration,
+    state: RwLock<AuthState>,
+}
+
+impl AuthManager {
+    /// Open (or initialise) the credential store in `dir`
+    pub fn open(dir: PathBuf, enabled: bool, session_ttl: Duration) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create auth directory: {}", dir.display()))?;
+        set_private_permissions(&dir, 0o700);
+
+        let secret_path = dir.join(SECRET_FILE);
+        if !secret_path.exists() {
+            write_private(&secret_path, URL_SAFE_NO_PAD.encode(generate_secret()).as_bytes())?;
+            info!("Generated new session signing key at {}", secret_path.display());
+        }
+
+        let state = load_state(&dir)?;
+        Ok(Self {
+            dir,
+            enabled,
+            session_ttl,
+            state: RwLock::new(state),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub async fn has_users(&self) -> bool {
+        self.refresh().await;
+        !self.state.read().await.users.is_empty()
+    }
+
+    pub async fn list_users(&self) -> Vec<String> {
+        self.refresh().await;
+        let state = self.state.read().await;
+        let mut users: Vec<String> = state.users.keys().cloned().collect();
+        users.sort();
+        users
+    }
+
+    /// Create a user or rotate an existing user's password.
+    /// Returns true when a new user was created.
+    pub async fn set_password(&self, username: &str, password: &str) -> Result<bool> {
+        validate_username(username)?;
+        if password.len() < 8 {
+            anyhow::bail!("Password must be at least 8 characters");
+        }
+
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
+            .to_string();
+
+        self.refresh().await;
+        let mut state = self.state.write().await;
+        let now = Utc::now();
+        let created = match state.users.get_mut(username) {
+            Some(user) => {
+                user.password_hash = password_hash;
+                user.generation += 1;
+                user.updated_at = now;
+                false
+            }
+            None => {
+                state.users.insert(
+                    username.to_string(),
+                    UserRecord {
+                        password_hash,
+                        generation: 0,
+                        created_at: now,
+                        updated_at: now,
+                    },
+                );
+                true
+            }
+        };
+        self.save_users(&mut state)?;
+        Ok(created)
+    }
+
+    pub async fn remove_user(&self, username: &str) -> Result<()> {
+        self.refresh().await;
+        let mut state = self.state.write().await;
+        if state.users.remove(username).is_none() {
+            anyhow::bail!("User not found: {}", username);
+        }
+        self.save_users(&mut state)?;
+        Ok(())
+    }
+
+    /// Replace the signing key, invalidating every token issued so far
+    pub async fn rotate_secret(&self) -> Result<()> {
+        let mut state = self.state.write().await;
+        let secret = generate_secret();
+        let path = self.dir.join(SECRET_FILE);
+        write_private(&path, URL_SAFE_NO_PAD.encode(&secret).as_bytes())?;
+        state.secret = secret;
+        state.secret_mtime = modified_time(&path);
+        // Every outstanding token is now invalid, so the revocation list is moot
+        state.revoked.clear();
+        self.save_revoked(&mut state)?;
+        Ok(())
+    }
+
+    /// Verify credentials and issue a signed session token
+    pub async fn login(&self, username: &str, password: &str) -> Result<(String, DateTime<Utc>)> {
+        self.refresh().await;
+        let state = self.state.read().await;
+
+        let user = state
+            .users
+            .get(username)
+            .ok_or_else(|| anyhow::anyhow!("Invalid username or password"))?;
+        let parsed = PasswordHash::new(&user.password_hash)
+            .map_err(|e| anyhow::anyhow!("Corrupt password hash for {}: {}", username, e))?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .map_err(|_| anyhow::anyhow!("Invalid username or password"))?;
+
+        let now = Utc::now();
+        let expires_at = now + self.session_ttl;
+        let claims = TokenClaims {
+            sub: username.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            gen: user.generation,
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        let token = sign_token(&state.secret, &claims)?;
+        Ok((token, expires_at))
+    }
+
+    /// Validate a session token: signature, expiry, revocation and password generation
+    pub async fn validate(&self, token: &str) -> Result<AuthSession> {
+        self.refresh().await;
+        let state = self.state.read().await;
+        let claims = verify_token(&state.secret, token)?;
+
+        let expires_at = DateTime::from_timestamp(claims.exp, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid token expiry"))?;
+        if expires_at <= Utc::now() {
+            anyhow::bail!("Session expired");
+        }
+        if state.revoked.contains_key(&claims.jti) {
+            anyhow::bail!("Session revoked");
+        }
+        match state.users.get(&claims.sub) {
+            Some(user) if user.generation == claims.gen => {}
+            _ => anyhow::bail!("Session no longer valid"),
+        }
+
+        Ok(AuthSession {
+            username: claims.sub,
+            token_id: claims.jti,
+            expires_at,
+        })
+    }
+
+    /// Revoke a single token until it would have expired anyway
+    pub async fn revoke(&self, token: &str) -> Result<()> {
+        let session = self.validate(token).await?;
+        let mut state = self.state.write().await;
+        let now = Utc::now();
+        state.revoked.retain(|_, exp| *exp > now);
+        state.revoked.insert(session.token_id, session.expires_at);
+        self.save_revoked(&mut state)?;
+        info!("Revoked session for user: {}", session.username);
+        Ok(())
+    }
+
+    /// Check the token carried by a request (cookie, bearer header or query parameter)
+    pub async fn authorize(&self, headers: &HeaderMap, query_token: Option<&str>) -> crate::error::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let token = extract_token(headers, query_token)
+            .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string()))?;
+        self.validate(&token)
+            .await
+            .map(|_| ())
+            .map_err(|e| AppError::Unauthorized(e.to_string()))
+    }
+
+    // Private helpers
+
+    /// Pick up changes made by the CLI while the server is running
+    async fn refresh(&self) {
+        let stale = {
+            let state = self.state.read().await;
+            state.secret_mtime != modified_time(&self.dir.join(SECRET_FILE))
+                || state.users_mtime != modified_time(&self.dir.join(USERS_FILE))
+                || state.revoked_mtime != modified_time(&self.dir.join(REVOKED_FILE))
+        };
+        if stale {
+            match load_state(&self.dir) {
+                Ok(fresh) => *self.state.write().await = fresh,
+                Err(e) => warn!("Failed to reload auth state: {}", e),
+            }
+        }
+    }
+
+    fn save_users(&self, state: &mut AuthState) -> Result<()> {
+        let path = self.dir.join(USERS_FILE);
+        write_private(&path, serde_json::to_string_pretty(&state.users)?.as_bytes())?;
+        state.users_mtime = modified_time(&path);
+        Ok(())
+    }
+
+    fn save_revoked(&self, state: &mut AuthState) -> Result<()> {
+        let path = self.dir.join(REVOKED_FILE);
+        write_private(&path, serde_json::to_string_pretty(&state.revoked)?.as_bytes())?;
+        state.revoked_mtime = modified_time(&path);
+        Ok(())
+    }
+}
+
+/// Find a session token in the cookie, `Authorization: Bearer` header or `token` query parameter.
+/// Browsers cannot set headers on WebSocket upgrades, hence the query fallback.
+pub fn extract_token(headers: &HeaderMap, query_token: Option<&str>) -> Option<String> {
+    if let Some(bearer) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        return Some(bearer.trim().to_string());
+    }
+
+    let from_cookie = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string());
+
+    from_cookie.or_else(|| query_token.map(|t| t.to_string()))
+}
+
+fn load_state(dir: &Path) -> Result<AuthState> {
+    let secret_path = dir.join(SECRET_FILE);
+    let encoded = fs::read_to_string(&secret_path)
+        .with_context(|| format!("Failed to read {}", secret_path.display()))?;
+    let users_path = dir.join(USERS_FILE);
+    let revoked_path = dir.join(REVOKED_FILE);
+
+    Ok(AuthState {
+        secret: URL_SAFE_NO_PAD
+            .decode(encoded.trim())
+            .context("Invalid session signing key")?,
+        users: read_json(&users_path)?.unwrap_or_default(),
+        revoked: read_json(&revoked_path)?.unwrap_or_default(),
+        secret_mtime: modified_time(&secret_path),
+        users_mtime: modified_time(&users_path),
+        revoked_mtime: modified_time(&revoked_path),
+    })
+}
+
+fn sign_token(secret: &[u8], claims: &TokenClaims) -> Result<String> {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+    let mut mac = HmacSha256::new_from_slice(secret)?;
+    mac.update(payload.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+    Ok(format!("{}.{}", payload, signature))
+}
+
+fn verify_token(secret: &[u8], token: &str) -> Result<TokenClaims> {
+    let (payload, signature) = token
+        .split_once('.')
+        .ok_or_else(|| anyhow::anyhow!("Malformed token"))?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| anyhow::anyhow!("Malformed token"))?;
+
+    let mut mac = HmacSha256::new_from_slice(secret)?;
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| anyhow::anyhow!("Invalid token signature"))?;
+
+    let claims = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| anyhow::anyhow!("Malformed token"))?;
+    Ok(serde_json::from_slice(&claims)?)
+}
+
+fn validate_username(username: &str) -> Result<()> {
+    if username.is_empty() || username.len() > 64 {
+        anyhow::bail!("Username must be between 1 and 64 characters");
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@'))
+    {
+        anyhow::bail!("Username may only contain letters, digits, '-', '_', '.' and '@'");
+    }
+    Ok(())
+}
+
+fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(Some(value))
+}
+
+/// Write a file readable only by the current user, replacing it atomically
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        file.write_all(content)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+fn set_private_permissions(path: &Path, mode: u32) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(mode)) {
+            warn!("Failed to restrict permissions on {}: {}", path.display(), e);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Marks requests that came in through the HTTPS listener
+#[derive(Debug, Clone, Copy)]
+pub struct Https;
+
+fn session_cookie(token: &str, max_age: i64, secure: bool) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}{}",
+        SESSION_COOKIE,
+        token,
+        max_age,
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+// HTTP handlers
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginResponse {
+    pub success: bool,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub async fn login_page() -> Html<&'static str> {
+    Html(LOGIN_PAGE)
+}
+
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    https: Option<Extension<Https>>,
+    Json(request): Json<LoginRequest>,
+) -> crate::error::Result<Response> {
+    match state.auth.login(&request.username, &request.password).await {
+        Ok((token, expires_at)) => {
+            info!("User logged in: {}", request.username);
+            let max_age = (expires_at - Utc::now()).num_seconds();
+            let secure = https.is_some() || state.config.auth.secure_cookie;
+            let cookie = session_cookie(&token, max_age, secure);
+            let body = Json(LoginResponse {
+                success: true,
+                token,
+                expires_at,
+            });
+            Ok(([(header::SET_COOKIE, cookie)], body).into_response())
+        }
+        Err(e) => {
+            warn!("Failed login attempt for user {}: {}", request.username, e);
+            // Slow down password guessing
+            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+            Err(AppError::Unauthorized("Invalid username or password".to_string()))
+        }
+    }
+}
+
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    https: Option<Extension<Https>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(token) = extract_token(&headers, None) {
+        if let Err(e) = state.auth.revoke(&token).await {
+            warn!("Failed to revoke session on logout: {}", e);
+        }
+    }
+    let body = Json(serde_json::json!({ "success": true }));
+    let secure = https.is_some() || state.config.auth.secure_cookie;
+    ([(header::SET_COOKIE, session_cookie("", 0, secure))], body).into_response()
+}
+
+/// Middleware guarding the static routes: browsers are sent to the login page,
+/// everything else gets a 401
+pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.auth.authorize(request.headers(), None).await {
+        Ok(()) => next.run(request).await,
+        Err(e) => {
+            let wants_html = request
+                .headers()
+                .get(header::ACCEPT)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.contains("text/html"))
+                .unwrap_or(false);
+            if request.method() == Method::GET && wants_html {
+                let next_path = request
+                    .uri()
+                    .path_and_query()
+                    .map(|p| p.as_str())
+                    .unwrap_or("/");
+                Redirect::to(&format!("/login?next={}", percent_encode(next_path))).into_response()
+            } else {
+                (StatusCode::UNAUTHORIZED, e).into_response()
+            }
+        }
+    }
+}
+
+// Command line credential management
+
+#[derive(Subcommand, Debug)]
+pub enum AuthCommand {
+    /// Create a user, or rotate an existing user's password (revokes their sessions)
+    SetPassword {
+        username: String,
+        /// Read the password from stdin instead of prompting
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Remove a user and revoke their sessions
+    RemoveUser { username: String },
+    /// List configured users
+    ListUsers,
+    /// Rotate the session signing key, revoking every issued session
+    RotateSecret,
+}
+
+pub async fn run_command(auth: &AuthManager, command: AuthCommand) -> Result<()> {
+    match command {
+        AuthCommand::SetPassword { username, password_stdin } => {
+            let password = if password_stdin {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line)?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            } else {
+                let password = rpassword::prompt_password(format!("Password for {}: ", username))?;
+                let confirm = rpassword::prompt_password("Confirm password: ")?;
+                if password != confirm {
+                    anyhow::bail!("Passwords do not match");
+                }
+                password
+            };
+            if auth.set_password(&username, &password).await? {
+                println!("Created user {}", username);
+            } else {
+                println!("Updated password for {} (existing sessions revoked)", username);
+            }
+        }
+        AuthCommand::RemoveUser { username } => {
+            auth.remove_user(&username).await?;
+            println!("Removed user {}", username);
+        }
+        AuthCommand::ListUsers => {
+            let users = auth.list_users().await;
+            if users.is_empty() {
+                println!("No users configured");
+            }
+            for user in users {
+                println!("{}", user);
+            }
+        }
+        AuthCommand::RotateSecret => {
+            auth.rotate_secret().await?;
+            println!("Rotated session signing key; all sessions revoked");
+        }
+    }
+    Ok(())
+}
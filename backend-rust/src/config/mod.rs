@@ -0,0 +1,567 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_LOG_FILTER: &str = "webmux_backend=debug,tower_http=info";
+
+/// Effective server configuration.
+///
+/// Values are merged in this order, later sources winning:
+/// built-in defaults, the TOML config file, `WEBMUX_*` environment variables, CLI flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory for persistent state (credentials, history, ...)
+    pub data_dir: PathBuf,
+    pub server: ServerConfig,
+    pub tls: TlsConfig,
+    pub monitor: MonitorConfig,
+    pub terminal: TerminalConfig,
+    pub logging: LoggingConfig,
+    pub auth: AuthConfig,
+    pub cron: CronConfig,
+    pub dotfiles: DotfilesConfig,
+    pub features: FeatureConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub http_port: u16,
+    /// Directory containing the built Vue app
+    pub static_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// Defaults to `server.bind` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind: Option<IpAddr>,
+    pub port: u16,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonitorConfig {
+    pub mode: MonitorMode,
+    /// Polling interval when control mode is unavailable or disabled
+    pub interval_ms: u64,
+    /// Full rescan period in control mode, for changes tmux sends no notification for
+    pub resync_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorMode {
+    /// Control mode, polling while it is unavailable
+    Auto,
+    /// Control mode only
+    Control,
+    /// Poll every `interval_ms`
+    Poll,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerminalConfig {
+    /// How long a disconnected client's terminal stays alive for resuming
+    pub resume_grace_secs: u64,
+    /// Size of the per-stream output replay window
+    pub replay_buffer_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// tracing `EnvFilter` directive; `RUST_LOG` still takes precedence when set
+    pub filter: String,
+    pub audio_debug: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub enabled: bool,
+    pub session_ttl_hours: i64,
+    /// Mark the session cookie `Secure` on plain HTTP too, for a proxy that terminates TLS.
+    /// Requests to the HTTPS listener always get it.
+    pub secure_cookie: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CronConfig {
+    /// IANA timezone schedules are evaluated in; the server's local time when unset.
+    /// Nothing tells the cron daemon about it: jobs still fire in the server's local time,
+    /// so a zone with a different offset is warned about at startup and previews report both.
+    pub timezone: Option<chrono_tz::Tz>,
+    /// Executions kept per job; older ones are dropped as new ones are recorded
+    pub history_limit: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DotfilesConfig {
+    pub mode: DotfilesMode,
+    /// Versions kept per file (history mode)
+    pub history_max_versions: usize,
+    /// Versions older than this are dropped (the newest one is always kept); unlimited when unset
+    pub history_max_age_days: Option<u32>,
+    /// Local path of the repository to pull from / push to (git mode)
+    pub git_remote: Option<PathBuf>,
+    pub git_branch: String,
+    /// Source a tmux config into the running server after it is written through webmux
+    pub reload_tmux_on_write: bool,
+    /// Replace secret values in file contents sent to clients unless they ask to reveal them
+    pub mask_secrets: bool,
+    /// Watch tracked dotfiles, recording and announcing edits made outside webmux
+    pub watch: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DotfilesMode {
+    /// Versions kept in webmux's own history store
+    History,
+    /// Tracked dotfiles committed to a git repository with `$HOME` as its work tree
+    Git,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    pub audio: bool,
+    pub cron: bool,
+    pub dotfiles: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Feature {
+    Audio,
+    Cron,
+    Dotfiles,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: dirs::home_dir()
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join(".webmux"),
+            server: ServerConfig::default(),
+            tls: TlsConfig::default(),
+            monitor: MonitorConfig::default(),
+            terminal: TerminalConfig::default(),
+            logging: LoggingConfig::default(),
+            auth: AuthConfig::default(),
+            cron: CronConfig::default(),
+            dotfiles: DotfilesConfig::default(),
+            features: FeatureConfig::default(),
+        }
+    }
+}
+
+impl Default for CronConfig {
+    fn default() -> Self {
+        Self {
+            timezone: None,
+            history_limit: 100,
+        }
+    }
+}
+
+impl Default for DotfilesConfig {
+    fn default() -> Self {
+        Self {
+            mode: DotfilesMode::History,
+            history_max_versions: 50,
+            history_max_age_days: None,
+            git_remote: None,
+            git_branch: "main".to_string(),
+            reload_tmux_on_write: false,
+            mask_secrets: false,
+            watch: true,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            http_port: 4000,
+            static_dir: cwd_relative("../dist"),
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bind: None,
+            port: 4443,
+            cert: cwd_relative("../certs/cert.pem"),
+            key: cwd_relative("../certs/key.pem"),
+        }
+    }
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            mode: MonitorMode::Auto,
+            interval_ms: 250,
+            resync_secs: 30,
+        }
+    }
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            resume_grace_secs: 120,
+            replay_buffer_bytes: 1 << 20,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            filter: DEFAULT_LOG_FILTER.to_string(),
+            audio_debug: false,
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            session_ttl_hours: 168,
+            secure_cookie: false,
+        }
+    }
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            audio: true,
+            cron: true,
+            dotfiles: true,
+        }
+    }
+}
+
+impl FeatureConfig {
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::Audio => self.audio,
+            Feature::Cron => self.cron,
+            Feature::Dotfiles => self.dotfiles,
+        }
+    }
+
+    fn set(&mut self, feature: Feature, enabled: bool) {
+        match feature {
+            Feature::Audio => self.audio = enabled,
+            Feature::Cron => self.cron = enabled,
+            Feature::Dotfiles => self.dotfiles = enabled,
+        }
+    }
+}
+
+/// Command line / environment overrides, flattened into the top-level `Args`
+#[derive(clap::Args, Debug)]
+pub struct ConfigOverrides {
+    /// Path to a TOML config file [default: ~/.config/webmux/config.toml if present]
+    #[arg(long, short = 'c', env = "WEBMUX_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Directory for persistent state
+    #[arg(long, env = "WEBMUX_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Address to bind the HTTP server to
+    #[arg(long, env = "WEBMUX_BIND")]
+    pub bind: Option<IpAddr>,
+
+    /// HTTP port
+    #[arg(long, env = "WEBMUX_HTTP_PORT")]
+    pub http_port: Option<u16>,
+
+    /// Directory containing the built frontend
+    #[arg(long, env = "WEBMUX_STATIC_DIR")]
+    pub static_dir: Option<PathBuf>,
+
+    /// Address to bind the HTTPS server to
+    #[arg(long, env = "WEBMUX_TLS_BIND")]
+    pub tls_bind: Option<IpAddr>,
+
+    /// HTTPS port
+    #[arg(long, env = "WEBMUX_HTTPS_PORT")]
+    pub https_port: Option<u16>,
+
+    /// TLS certificate (PEM)
+    #[arg(long, env = "WEBMUX_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// TLS private key (PEM)
+    #[arg(long, env = "WEBMUX_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Do not start the HTTPS server
+    #[arg(long)]
+    pub no_https: bool,
+
+    /// How the tmux monitor watches for changes
+    #[arg(long, env = "WEBMUX_MONITOR_MODE", value_enum)]
+    pub monitor_mode: Option<MonitorMode>,
+
+    /// Where dotfile versions are kept
+    #[arg(long, env = "WEBMUX_DOTFILES_MODE", value_enum)]
+    pub dotfiles_mode: Option<DotfilesMode>,
+
+    /// Tmux monitor polling interval in milliseconds
+    #[arg(long, env = "WEBMUX_MONITOR_INTERVAL_MS")]
+    pub monitor_interval_ms: Option<u64>,
+
+    /// Seconds a disconnected terminal is kept alive for resuming
+    #[arg(long, env = "WEBMUX_RESUME_GRACE_SECS")]
+    pub resume_grace_secs: Option<u64>,
+
+    /// Log filter directive (overridden by RUST_LOG)
+    #[arg(long, env = "WEBMUX_LOG")]
+    pub log_filter: Option<String>,
+
+    /// Enable audio streaming debug logs
+    #[arg(long)]
+    pub audio: bool,
+
+    /// Disable authentication (only for trusted, local-only setups)
+    #[arg(long)]
+    pub no_auth: bool,
+
+    /// Lifetime of issued session tokens in hours
+    #[arg(long, env = "WEBMUX_SESSION_TTL_HOURS")]
+    pub session_ttl_hours: Option<i64>,
+
+    /// IANA timezone for cron schedules (e.g. Europe/Berlin)
+    #[arg(long, env = "WEBMUX_CRON_TIMEZONE")]
+    pub cron_timezone: Option<chrono_tz::Tz>,
+
+    /// Comma-separated features to disable
+    #[arg(long, value_enum, value_delimiter = ',', env = "WEBMUX_DISABLE")]
+    pub disable: Vec<Feature>,
+
+    /// Comma-separated features to enable
+    #[arg(long, value_enum, value_delimiter = ',', env = "WEBMUX_ENABLE")]
+    pub enable: Vec<Feature>,
+}
+
+impl Config {
+    /// Load the config file (if any) and apply overrides on top of it
+    pub fn load(overrides: &ConfigOverrides) -> Result<Self> {
+        let cwd = std::env::current_dir().context("Failed to determine working directory")?;
+
+        let file = match &overrides.config {
+            Some(path) => Some(expand_home(path)),
+            None => default_config_path().filter(|p| p.exists()),
+        };
+
+        let mut config = match &file {
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+                let mut config: Config = toml::from_str(&content)
+                    .with_context(|| format!("Invalid config file: {}", path.display()))?;
+                // Relative paths in the file are relative to the file itself
+                let base = path.parent().map(|p| cwd.join(p)).unwrap_or_else(|| cwd.clone());
+                config.resolve_paths(&base);
+                config
+            }
+            None => Config::default(),
+        };
+
+        config.apply_overrides(overrides);
+        config.resolve_paths(&cwd);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn auth_dir(&self) -> PathBuf {
+        self.data_dir.join("auth")
+    }
+
+    pub fn cron_history_dir(&self) -> PathBuf {
+        self.data_dir.join("cron").join("history")
+    }
+
+    pub fn dotfiles_history_dir(&self) -> PathBuf {
+        self.data_dir.join("dotfiles").join("history")
+    }
+
+    pub fn dotfiles_repo_dir(&self) -> PathBuf {
+        self.data_dir.join("dotfiles").join("repo.git")
+    }
+
+    pub fn dotfiles_tracked_path(&self) -> PathBuf {
+        self.data_dir.join("dotfiles").join("tracked.json")
+    }
+
+    pub fn dotfiles_templates_dir(&self) -> PathBuf {
+        self.data_dir.join("dotfiles").join("templates")
+    }
+
+    pub fn dotfiles_backups_dir(&self) -> PathBuf {
+        self.data_dir.join("dotfiles").join("backups")
+    }
+
+    pub fn tls_bind(&self) -> IpAddr {
+        self.tls.bind.unwrap_or(self.server.bind)
+    }
+
+    fn apply_overrides(&mut self, o: &ConfigOverrides) {
+        if let Some(dir) = &o.data_dir {
+            self.data_dir = dir.clone();
+        }
+        if let Some(bind) = o.bind {
+            self.server.bind = bind;
+        }
+        if let Some(port) = o.http_port {
+            self.server.http_port = port;
+        }
+        if let Some(dir) = &o.static_dir {
+            self.server.static_dir = dir.clone();
+        }
+        if let Some(bind) = o.tls_bind {
+            self.tls.bind = Some(bind);
+        }
+        if let Some(port) = o.https_port {
+            self.tls.port = port;
+        }
+        if let Some(cert) = &o.tls_cert {
+            self.tls.cert = cert.clone();
+        }
+        if let Some(key) = &o.tls_key {
+            self.tls.key = key.clone();
+        }
+        if o.no_https {
+            self.tls.enabled = false;
+        }
+        if let Some(mode) = o.monitor_mode {
+            self.monitor.mode = mode;
+        }
+        if let Some(mode) = o.dotfiles_mode {
+            self.dotfiles.mode = mode;
+        }
+        if let Some(interval) = o.monitor_interval_ms {
+            self.monitor.interval_ms = interval;
+        }
+        if let Some(grace) = o.resume_grace_secs {
+            self.terminal.resume_grace_secs = grace;
+        }
+        if let Some(filter) = &o.log_filter {
+            self.logging.filter = filter.clone();
+        }
+        if o.audio {
+            self.logging.audio_debug = true;
+        }
+        if o.no_auth {
+            self.auth.enabled = false;
+        }
+        if let Some(ttl) = o.session_ttl_hours {
+            self.auth.session_ttl_hours = ttl;
+        }
+        if let Some(timezone) = o.cron_timezone {
+            self.cron.timezone = Some(timezone);
+        }
+        for feature in &o.enable {
+            self.features.set(*feature, true);
+        }
+        for feature in &o.disable {
+            self.features.set(*feature, false);
+        }
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in [
+            &mut self.data_dir,
+            &mut self.server.static_dir,
+            &mut self.tls.cert,
+            &mut self.tls.key,
+        ]
+        .into_iter()
+        .chain(self.dotfiles.git_remote.as_mut())
+        {
+            let expanded = expand_home(path);
+            *path = if expanded.is_absolute() {
+                expanded
+            } else {
+                base.join(expanded)
+            };
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.monitor.interval_ms == 0 {
+            anyhow::bail!("monitor.interval_ms must be greater than 0");
+        }
+        if self.monitor.resync_secs == 0 {
+            anyhow::bail!("monitor.resync_secs must be greater than 0");
+        }
+        if self.auth.session_ttl_hours <= 0 {
+            anyhow::bail!("auth.session_ttl_hours must be greater than 0");
+        }
+        if self.cron.history_limit == 0 {
+            anyhow::bail!("cron.history_limit must be greater than 0");
+        }
+        if self.dotfiles.history_max_versions == 0 {
+            anyhow::bail!("dotfiles.history_max_versions must be greater than 0");
+        }
+        if self.dotfiles.git_branch.is_empty() || self.dotfiles.git_branch.starts_with('-') {
+            anyhow::bail!("dotfiles.git_branch is not a valid branch name");
+        }
+        if self.tls.enabled && self.tls_bind() == self.server.bind && self.tls.port == self.server.http_port {
+            anyhow::bail!("HTTP and HTTPS servers cannot share port {}", self.tls.port);
+        }
+        Ok(())
+    }
+}
+
+/// `~/.config/webmux/config.toml` (or the platform equivalent)
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("webmux").join("config.toml"))
+}
+
+/// Defaults keep the historical layout of being launched from `backend-rust/`
+fn cwd_relative(path: &str) -> PathBuf {
+    std::env::current_dir()
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|_| PathBuf::from(path))
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| path.to_path_buf()),
+        Err(_) => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,398 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use super::{
+    window::{self, TmuxTarget, WindowExit},
+    JobExecution,
+};
+
+// Captured bytes kept per stream; output beyond this is still passed through to cron
+const MAX_CAPTURE_BYTES: usize = 16 * 1024;
+
+/// Append-only execution log per job: `<dir>/<job_id>.jsonl`, oldest first
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    dir: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, job_id: &str) -> Result<PathBuf> {
+        validate_job_id(job_id)?;
+        Ok(self.dir.join(format!("{}.jsonl", job_id)))
+    }
+
+    /// Record an execution, dropping the oldest ones beyond `limit`
+    pub fn append(&self, execution: &JobExecution, limit: usize) -> Result<()> {
+        let path = self.path(&execution.job_id)?;
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let mut line = serde_json::to_string(execution)?;
+        line.push('\n');
+        // Overlapping runs of the same job take turns; the trim below rewrites the file
+        let _lock = lock(&path)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(line.as_bytes())?;
+
+        let executions = read_executions(&path)?;
+        if executions.len() > limit {
+            write_executions(&path, &executions[executions.len() - limit..])?;
+        }
+        Ok(())
+    }
+
+    /// Executions of a job, newest first
+    pub fn list(&self, job_id: &str) -> Result<Vec<JobExecution>> {
+        let mut executions = read_executions(&self.path(job_id)?)?;
+        executions.reverse();
+        Ok(executions)
+    }
+
+    pub fn get(&self, job_id: &str, execution_id: &str) -> Result<Option<JobExecution>> {
+        Ok(read_executions(&self.path(job_id)?)?
+            .into_iter()
+            .find(|execution| execution.id == execution_id))
+    }
+
+    pub fn last_run(&self, job_id: &str) -> Option<DateTime<Utc>> {
+        let path = self.path(job_id).ok()?;
+        read_executions(&path).ok()?.last().map(|execution| execution.started_at)
+    }
+
+    /// Drop executions beyond the newest `keep_last` and/or started before `older_than`.
+    /// Applies to every job when `job_id` is `None`. Returns how many were removed.
+    pub fn prune(
+        &self,
+        job_id: Option<&str>,
+        keep_last: Option<usize>,
+        older_than: Option<DateTime<Utc>>,
+    ) -> Result<usize> {
+        let paths = match job_id {
+            Some(job_id) => vec![self.path(job_id)?],
+            None => match fs::read_dir(&self.dir) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+        };
+
+        let mut removed = 0;
+        for path in paths {
+            if !path.exists() {
+                continue;
+            }
+            let _lock = lock(&path)?;
+            let executions = read_executions(&path)?;
+            let before = executions.len();
+            let mut kept: Vec<JobExecution> = executions
+                .into_iter()
+                .filter(|execution| older_than.is_none_or(|cutoff| execution.started_at >= cutoff))
+                .collect();
+            if let Some(keep_last) = keep_last {
+                if kept.len() > keep_last {
+                    kept.drain(..kept.len() - keep_last);
+                }
+            }
+            if kept.len() != before {
+                removed += before - kept.len();
+                write_executions(&path, &kept)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn remove(&self, job_id: &str) -> Result<()> {
+        let path = self.path(job_id)?;
+        let _ = fs::remove_file(path.with_extension("lock"));
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Job ids end up unquoted in crontab lines and in file names
+pub fn validate_job_id(job_id: &str) -> Result<()> {
+    if job_id.is_empty()
+        || !job_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        anyhow::bail!("Invalid job id '{}': use letters, digits, '-' and '_'", job_id);
+    }
+    Ok(())
+}
+
+// Exclusive lock on a history file, released when the returned file is dropped. It's
+// taken on a separate `.lock` file, since rewrites replace the history file itself.
+fn lock(path: &Path) -> Result<fs::File> {
+    let lock_path = path.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+    file.lock().with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+    Ok(file)
+}
+
+fn read_executions(path: &Path) -> Result<Vec<JobExecution>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    // Skip lines that fail to parse (e.g. a write cut short by a crash)
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+fn write_executions(path: &Path, executions: &[JobExecution]) -> Result<()> {
+    let mut content = String::new();
+    for execution in executions {
+        content.push_str(&serde_json::to_string(execution)?);
+        content.push('\n');
+    }
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Run `command` through `sh -c` like cron would, with the job's `env` added, passing
+/// its output through and recording the execution. Returns the command's exit code.
+pub async fn record(
+    store: &HistoryStore,
+    limit: usize,
+    job_id: &str,
+    env: &[(String, String)],
+    command: &str,
+) -> Result<i32> {
+    let started_at = Utc::now();
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start job command")?;
+
+    let stdout = child.stdout.take().context("No stdout for job command")?;
+    let stderr = child.stderr.take().context("No stderr for job command")?;
+    let (stdout, stderr, status) = tokio::join!(
+        capture(stdout, tokio::io::stdout()),
+        capture(stderr, tokio::io::stderr()),
+        child.wait(),
+    );
+    let (stdout, stdout_truncated) = stdout?;
+    let (stderr, stderr_truncated) = stderr?;
+    let status = status?;
+
+    let execution = JobExecution {
+        id: Uuid::new_v4().to_string(),
+        job_id: job_id.to_string(),
+        started_at,
+        finished_at: Some(Utc::now()),
+        exit_code: status.code(),
+        success: status.success(),
+        output: (!stdout.is_empty()).then_some(stdout),
+        error: (!stderr.is_empty()).then_some(stderr),
+        truncated: stdout_truncated || stderr_truncated,
+    };
+    // The job itself ran; a failure to record it shouldn't turn into a job failure
+    if let Err(e) = store.append(&execution, limit) {
+        eprintln!("webmux: failed to record execution of job {}: {:#}", job_id, e);
+    }
+
+    // Killed by a signal: report it the way a shell would
+    Ok(status.code().unwrap_or(128))
+}
+
+/// Like `record`, but run `command` in a tmux window (see `window::run`). The output is
+/// read back from the pane, so stdout and stderr aren't told apart.
+pub async fn record_in_window(
+    store: &HistoryStore,
+    limit: usize,
+    job_id: &str,
+    target: &TmuxTarget,
+    env: &[(String, String)],
+    command: &str,
+) -> Result<i32> {
+    let started_at = Utc::now();
+    // Exit code as a shell would report it, and the one recorded (`None` for no exit status)
+    let (status, exit_code, output, error) = match window::run(target, job_id, env, command).await {
+        Ok(run) => match run.exit {
+            WindowExit::Code(code) => (code, Some(code), run.output, None),
+            WindowExit::Signal(signal) => {
+                let error = format!("Killed by signal {}", signal);
+                (128 + signal, None, run.output, Some(error))
+            }
+            WindowExit::Closed => {
+                let error = "The window was closed before the job finished".to_string();
+                (128, None, run.output, Some(error))
+            }
+        },
+        Err(e) => (1, Some(1), String::new(), Some(format!("{:#}", e))),
+    };
+    // Pass the output on to cron like a plain run would
+    print!("{}", output);
+    if let Some(error) = &error {
+        eprintln!("webmux: {}", error);
+    }
+
+    let truncated = output.len() > MAX_CAPTURE_BYTES;
+    let mut end = output.len().min(MAX_CAPTURE_BYTES);
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    let execution = JobExecution {
+        id: Uuid::new_v4().to_string(),
+        job_id: job_id.to_string(),
+        started_at,
+        finished_at: Some(Utc::now()),
+        exit_code,
+        success: exit_code == Some(0),
+        output: (!output.is_empty()).then(|| output[..end].to_string()),
+        error,
+        truncated,
+    };
+    if let Err(e) = store.append(&execution, limit) {
+        eprintln!("webmux: failed to record execution of job {}: {:#}", job_id, e);
+    }
+
+    Ok(status)
+}
+
+/// Copy a stream through to `passthrough`, keeping its first `MAX_CAPTURE_BYTES`
+async fn capture(
+    mut reader: impl AsyncRead + Unpin,
+    mut passthrough: impl tokio::io::AsyncWrite + Unpin,
+) -> Result<(String, bool)> {
+    let mut captured = Vec::new();
+    let mut truncated = false;
+    let mut buffer = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        let _ = passthrough.write_all(&buffer[..n]).await;
+        let room = MAX_CAPTURE_BYTES.saturating_sub(captured.len());
+        captured.extend_from_slice(&buffer[..n.min(room)]);
+        truncated |= n > room;
+    }
+    let _ = passthrough.flush().await;
+    Ok((String::from_utf8_lossy(&captured).into_owned(), truncated))
+}
+
+// Separates the recorder invocation from the job's own command in crontab lines
+const RECORD_MARKER: &str = " cron record ";
+
+/// Crontab command that runs `command` through `<exe> cron record` with `env` set, in
+/// a tmux window when `target` is set
+pub fn wrap_command(
+    exe: &Path,
+    data_dir: &Path,
+    job_id: &str,
+    target: Option<&TmuxTarget>,
+    env: &[(String, String)],
+    command: &str,
+) -> String {
+    let mut options = String::new();
+    for (name, value) in env {
+        options.push_str(&format!(" --env {}", crontab_quote(&format!("{}={}", name, value))));
+    }
+    if let Some(target) = target {
+        options.push_str(&format!(" --tmux-session {}", shell_quote(&target.session)));
+        if let Some(window) = &target.window {
+            options.push_str(&format!(" --tmux-window {}", shell_quote(window)));
+        }
+        if target.keep_on_failure {
+            options.push_str(" --keep-window");
+        }
+    }
+    format!(
+        "{} --data-dir {}{}{}{} -- {}",
+        shell_quote(&exe.to_string_lossy()),
+        shell_quote(&data_dir.to_string_lossy()),
+        RECORD_MARKER,
+        job_id,
+        options,
+        shell_quote(command)
+    )
+}
+
+/// The original command of a line written by `wrap_command`, `None` for plain commands
+pub fn unwrap_command(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once(RECORD_MARKER)?;
+    // Option values are quoted and may contain ` -- ` themselves
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if !quoted => escaped = true,
+            '\'' => quoted = !quoted,
+            ' ' if !quoted && rest[i..].starts_with(" -- ") => return shell_unquote(&rest[i + 4..]),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `export` statement setting `env` ahead of a plain (unrecorded) crontab command
+pub fn export_prefix(env: &[(String, String)]) -> String {
+    if env.is_empty() {
+        return String::new();
+    }
+    let vars: Vec<String> = env
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, crontab_quote(value)))
+        .collect();
+    format!("export {}; ", vars.join(" "))
+}
+
+// cron turns an unescaped `%` in the command into a newline
+fn crontab_quote(value: &str) -> String {
+    shell_quote(value).replace('%', "\\%")
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// Inverse of `shell_quote`: single-quoted runs joined by `\'`
+fn shell_unquote(value: &str) -> Option<String> {
+    let mut result = String::new();
+    let mut chars = value.trim_end().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => loop {
+                match chars.next()? {
+                    '\'' => break,
+                    c => result.push(c),
+                }
+            },
+            '\\' => result.push(chars.next()?),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
@@ -1,44 +1,164 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use clap::Subcommand;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process::Command;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::types::CronJob;
+use crate::{config::Config, types::CronJob};
+
+pub mod history;
+pub mod schedule;
+pub mod window;
+
+use history::HistoryStore;
+use schedule::{Schedule, ScheduleError};
+use window::TmuxTarget;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct JobExecution {
+    pub id: String,
     pub job_id: String,
     pub started_at: DateTime<Utc>,
     pub finished_at: Option<DateTime<Utc>>,
+    // `None` when the command was killed by a signal
+    pub exit_code: Option<i32>,
     pub success: bool,
+    // Captured stdout / stderr
     pub output: Option<String>,
     pub error: Option<String>,
+    // Output was cut off at the capture limit
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CronCommand {
+    /// Run a job command and record its execution (used by WebMux crontab entries)
+    Record {
+        job_id: String,
+        /// Run the command in a window of this tmux session (created if missing)
+        #[arg(long)]
+        tmux_session: Option<String>,
+        /// Reuse the window with this name instead of opening a fresh one
+        #[arg(long, requires = "tmux_session")]
+        tmux_window: Option<String>,
+        /// Leave a fresh window open when the command fails
+        #[arg(long, requires = "tmux_session")]
+        keep_window: bool,
+        /// Set an environment variable for the command (repeatable)
+        #[arg(long = "env", value_name = "NAME=VALUE")]
+        env: Vec<String>,
+        /// Shell command to run
+        #[arg(last = true)]
+        command: String,
+    },
+}
+
+pub async fn run_command(config: &Config, command: CronCommand) -> Result<i32> {
+    match command {
+        CronCommand::Record {
+            job_id,
+            tmux_session,
+            tmux_window,
+            keep_window,
+            env,
+            command,
+        } => {
+            let store = HistoryStore::new(config.cron_history_dir());
+            let limit = config.cron.history_limit;
+            let env = env
+                .iter()
+                .map(|var| match var.split_once('=') {
+                    Some((name, value)) => Ok((name.to_string(), value.to_string())),
+                    None => anyhow::bail!("Invalid --env '{}': expected NAME=VALUE", var),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            match tmux_session {
+                Some(session) => {
+                    let target = TmuxTarget {
+                        session,
+                        window: tmux_window,
+                        keep_on_failure: keep_window,
+                    };
+                    history::record_in_window(&store, limit, &job_id, &target, &env, &command).await
+                }
+                None => history::record(&store, limit, &job_id, &env, &command).await,
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct CronSettings {
+    // Timezone schedules are evaluated in; `None` is the server's local time
+    timezone: Option<Tz>,
+    history: Option<HistoryStore>,
+    // Backend binary and data dir that crontab entries record executions with
+    recorder: Option<(PathBuf, PathBuf)>,
 }
 
 pub struct CronManager {
     jobs: RwLock<HashMap<String, CronJob>>,
+    settings: std::sync::RwLock<CronSettings>,
 }
 
 impl CronManager {
     pub fn new() -> Self {
         Self {
             jobs: RwLock::new(HashMap::new()),
+            settings: std::sync::RwLock::new(CronSettings::default()),
         }
     }
 
-    pub async fn initialize(&self) -> Result<()> {
+    pub async fn initialize(&self, config: &Config) -> Result<()> {
+        let recorder = match std::env::current_exe() {
+            Ok(exe) => Some((exe, config.data_dir.clone())),
+            Err(e) => {
+                warn!("Cron executions will not be recorded: {}", e);
+                None
+            }
+        };
+        if let Some(tz) = config.cron.timezone {
+            let now = Utc::now();
+            if now.with_timezone(&tz).naive_local() != now.with_timezone(&Local).naive_local() {
+                warn!(
+                    "cron.timezone {} differs from the server's {}, which cron runs jobs in; next run times will be off",
+                    tz.name(),
+                    daemon_timezone()
+                );
+            }
+        }
+        *self.settings.write().unwrap_or_else(|e| e.into_inner()) = CronSettings {
+            timezone: config.cron.timezone,
+            history: Some(HistoryStore::new(config.cron_history_dir())),
+            recorder,
+        };
+
         // Load existing cron jobs from system crontab
         self.load_from_crontab().await?;
         Ok(())
     }
 
     pub async fn list_jobs(&self) -> Vec<CronJob> {
+        let history = self.history().ok();
         let jobs = self.jobs.read().await;
-        let mut job_list: Vec<CronJob> = jobs.values().cloned().collect();
+        let mut job_list: Vec<CronJob> = jobs
+            .values()
+            .cloned()
+            .map(|mut job| {
+                // Stored next runs go stale as time passes
+                job.next_run = self.next_run_for(&job);
+                job.last_run = history.as_ref().and_then(|history| history.last_run(&job.id));
+                job
+            })
+            .collect();
         job_list.sort_by(|a, b| a.created_at.cmp(&b.created_at));
         job_list
     }
@@ -48,6 +168,7 @@ impl CronManager {
         if job.id.is_empty() {
             job.id = Uuid::new_v4().to_string();
         }
+        history::validate_job_id(&job.id)?;
         
         // Check for duplicate names
         {
@@ -64,9 +185,11 @@ impl CronManager {
         
         // Validate cron expression
         self.validate_cron_expression(&job.schedule)?;
+        validate_tmux_target(&job)?;
+        validate_environment(&job)?;
         
         // Calculate next run time
-        job.next_run = self.calculate_next_run(&job.schedule).unwrap_or(None);
+        job.next_run = self.next_run_for(&job);
         
         // Add to crontab
         self.add_to_crontab(&job).await?;
@@ -80,6 +203,8 @@ impl CronManager {
     }
 
     pub async fn update_job(&self, id: String, mut job: CronJob) -> Result<CronJob> {
+        history::validate_job_id(&id)?;
+
         // Check for duplicate names (excluding self)
         {
             let jobs = self.jobs.read().await;
@@ -90,13 +215,15 @@ impl CronManager {
         
         // Validate cron expression
         self.validate_cron_expression(&job.schedule)?;
+        validate_tmux_target(&job)?;
+        validate_environment(&job)?;
         
         // Update timestamp
         job.updated_at = Utc::now();
         job.id = id.clone();
         
         // Calculate next run time
-        job.next_run = self.calculate_next_run(&job.schedule).unwrap_or(None);
+        job.next_run = self.next_run_for(&job);
         
         // Remove old entry from crontab
         self.remove_from_crontab(&id).await?;
@@ -121,6 +248,10 @@ impl CronManager {
         if let Some(job) = jobs.remove(id) {
             info!("Deleted cron job: {} ({})", job.name, id);
         }
+
+        if let Err(e) = self.history().and_then(|history| history.remove(id)) {
+            warn!("Failed to remove history of cron job {}: {}", id, e);
+        }
         
         Ok(())
     }
@@ -131,6 +262,7 @@ impl CronManager {
         if let Some(job) = jobs.get_mut(id) {
             job.enabled = enabled;
             job.updated_at = Utc::now();
+            job.next_run = self.next_run_for(job);
             
             // Always remove first to avoid duplicates
             self.remove_from_crontab(id).await?;
@@ -180,10 +312,41 @@ impl CronManager {
         }
     }
 
-    pub async fn get_job_history(&self, _id: &str) -> Vec<JobExecution> {
-        // TODO: Implement job execution history
-        // This would require storing execution results in a database or log file
-        Vec::new()
+    /// Upcoming fire times of `schedule`, plus the name of the timezone they were computed in
+    pub fn preview_schedule(&self, schedule: &str, count: usize) -> (String, Result<Vec<DateTime<Utc>>, ScheduleError>) {
+        let timezone = match self.timezone() {
+            Some(tz) => tz.name().to_string(),
+            None => daemon_timezone(),
+        };
+        let runs = self
+            .parse_schedule(schedule)
+            .map(|schedule| self.upcoming(&schedule, count));
+        (timezone, runs)
+    }
+
+    /// Recorded executions of a job, newest first
+    pub fn get_job_history(&self, id: &str, limit: usize) -> Result<Vec<JobExecution>> {
+        let mut executions = self.history()?.list(id)?;
+        executions.truncate(limit);
+        Ok(executions)
+    }
+
+    pub fn get_execution(&self, job_id: &str, execution_id: &str) -> Result<JobExecution> {
+        self.history()?
+            .get(job_id, execution_id)?
+            .ok_or_else(|| anyhow::anyhow!("Execution not found: {}", execution_id))
+    }
+
+    /// Drop old executions of one job (or every job); returns how many were removed
+    pub fn prune_history(
+        &self,
+        job_id: Option<&str>,
+        keep_last: Option<usize>,
+        older_than: Option<DateTime<Utc>>,
+    ) -> Result<usize> {
+        let removed = self.history()?.prune(job_id, keep_last, older_than)?;
+        info!("Pruned {} cron job executions", removed);
+        Ok(removed)
     }
 
     // Private helper methods
@@ -214,6 +377,10 @@ impl CronManager {
                     // Parse job metadata from comments
                     let mut job_name = String::new();
                     let mut enabled = true;
+                    let mut tmux_session = None;
+                    let mut tmux_window = None;
+                    let mut keep_window_on_failure = None;
+                    let mut environment = HashMap::new();
                     
                     i += 1;
                     while i < lines.len() && !lines[i].starts_with("# WebMux-Job-End") {
@@ -221,6 +388,17 @@ impl CronManager {
                             job_name = lines[i].strip_prefix("# Name:").unwrap_or("").trim().to_string();
                         } else if lines[i].starts_with("# Enabled:") {
                             enabled = lines[i].strip_prefix("# Enabled:").unwrap_or("true").trim() == "true";
+                        } else if let Some(session) = lines[i].strip_prefix("# TmuxSession:") {
+                            tmux_session = Some(session.trim().to_string());
+                        } else if let Some(window) = lines[i].strip_prefix("# TmuxWindow:") {
+                            tmux_window = Some(window.trim().to_string());
+                        } else if let Some(keep) = lines[i].strip_prefix("# KeepWindow:") {
+                            keep_window_on_failure = Some(keep.trim() == "true");
+                        } else if let Some(var) = lines[i].strip_prefix("# Env:") {
+                            // Values are kept as written, surrounding spaces included
+                            if let Some((name, value)) = var.split_once('=') {
+                                environment.insert(name.to_string(), value.to_string());
+                            }
                         } else if !lines[i].trim().is_empty() {
                             // This could be the actual cron line (active or commented out)
                             let line = if lines[i].starts_with("# ") && enabled == false {
@@ -233,26 +411,29 @@ impl CronManager {
                                 continue;
                             };
                             
-                            let parts: Vec<&str> = line.splitn(6, ' ').collect();
-                            if parts.len() >= 6 {
-                                let schedule = parts[0..5].join(" ");
-                                let command = parts[5].to_string();
-                                
-                                let job = CronJob {
+                            if let Some((schedule, command)) = schedule::split_crontab_line(line) {
+                                let mut job = CronJob {
                                     id: job_id.to_string(),
                                     name: job_name.clone(),
-                                    schedule,
-                                    command,
+                                    schedule: schedule.to_string(),
+                                    // Recorded jobs run through `cron record`
+                                    command: history::unwrap_command(command).unwrap_or_else(|| {
+                                        let prefix = history::export_prefix(&sorted_environment(&environment));
+                                        command.strip_prefix(prefix.as_str()).unwrap_or(command).to_string()
+                                    }),
                                     enabled,
                                     last_run: None,
-                                    next_run: self.calculate_next_run(&parts[0..5].join(" ")).unwrap_or(None),
+                                    next_run: None,
                                     created_at: Utc::now(),
                                     updated_at: Utc::now(),
-                                    environment: None,
+                                    environment: (!environment.is_empty()).then(|| environment.clone()),
                                     log_output: None,
                                     email_to: None,
-                                    tmux_session: None,
+                                    tmux_session: tmux_session.clone(),
+                                    tmux_window: tmux_window.clone(),
+                                    keep_window_on_failure,
                                 };
+                                job.next_run = self.next_run_for(&job);
                                 
                                 jobs.insert(job_id.to_string(), job);
                             }
@@ -280,27 +461,46 @@ impl CronManager {
             String::new()
         };
         
+        let command = self.crontab_command(job);
+
+        // Where and how the job runs, read back by `load_from_crontab`
+        let mut metadata = String::new();
+        for (name, value) in job_environment(job) {
+            metadata.push_str(&format!("# Env:{}={}\n", name, value));
+        }
+        if let Some(target) = tmux_target(job) {
+            metadata.push_str(&format!("# TmuxSession:{}\n", target.session));
+            if let Some(window) = &target.window {
+                metadata.push_str(&format!("# TmuxWindow:{}\n", window));
+            }
+            if target.keep_on_failure {
+                metadata.push_str("# KeepWindow:true\n");
+            }
+        }
+
         // Add job with WebMux markers
         let job_entry = if job.enabled {
             // Active job - include the cron line
             format!(
-                "\n# WebMux-Job-Start:{}\n# Name:{}\n# Enabled:{}\n{} {}\n# WebMux-Job-End:{}\n",
+                "\n# WebMux-Job-Start:{}\n# Name:{}\n# Enabled:{}\n{}{} {}\n# WebMux-Job-End:{}\n",
                 job.id,
                 job.name,
                 job.enabled,
+                metadata,
                 job.schedule,
-                job.command,
+                command,
                 job.id
             )
         } else {
             // Disabled job - comment out the cron line
             format!(
-                "\n# WebMux-Job-Start:{}\n# Name:{}\n# Enabled:{}\n# {} {}\n# WebMux-Job-End:{}\n",
+                "\n# WebMux-Job-Start:{}\n# Name:{}\n# Enabled:{}\n{}# {} {}\n# WebMux-Job-End:{}\n",
                 job.id,
                 job.name,
                 job.enabled,
+                metadata,
                 job.schedule,
-                job.command,
+                command,
                 job.id
             )
         };
@@ -374,26 +574,133 @@ impl CronManager {
     }
 
     fn validate_cron_expression(&self, expression: &str) -> Result<()> {
-        // Basic validation - check if it has 5 fields
-        let parts: Vec<&str> = expression.split_whitespace().collect();
-        if parts.len() != 5 {
-            return Err(anyhow::anyhow!(
-                "Invalid cron expression: expected 5 fields, got {}",
-                parts.len()
-            ));
-        }
-        
-        // TODO: Add more sophisticated validation
-        // For now, we'll trust the user input and let cron validate it
-        
+        self.parse_schedule(expression)?;
         Ok(())
     }
 
-    fn calculate_next_run(&self, _schedule: &str) -> Result<Option<DateTime<Utc>>> {
-        // TODO: Implement proper cron expression parsing and next run calculation
-        // For now, return None
-        Ok(None)
+    /// Parse a schedule, also rejecting ones that cron accepts but never runs
+    fn parse_schedule(&self, expression: &str) -> Result<Schedule, ScheduleError> {
+        let schedule = Schedule::parse(expression)?;
+        if !schedule.is_reboot() && self.upcoming(&schedule, 1).is_empty() {
+            return Err(ScheduleError::never_fires());
+        }
+        Ok(schedule)
+    }
+
+    fn calculate_next_run(&self, schedule: &str) -> Result<Option<DateTime<Utc>>> {
+        let schedule = Schedule::parse(schedule)?;
+        Ok(self.upcoming(&schedule, 1).into_iter().next())
+    }
+
+    fn next_run_for(&self, job: &CronJob) -> Option<DateTime<Utc>> {
+        if !job.enabled {
+            return None;
+        }
+        self.calculate_next_run(&job.schedule).unwrap_or(None)
+    }
+
+    /// The command as written to the crontab, wrapped to record its executions
+    fn crontab_command(&self, job: &CronJob) -> String {
+        let settings = self.settings.read().unwrap_or_else(|e| e.into_inner());
+        match &settings.recorder {
+            Some((exe, data_dir)) => history::wrap_command(
+                exe,
+                data_dir,
+                &job.id,
+                tmux_target(job).as_ref(),
+                &job_environment(job),
+                &job.command,
+            ),
+            None => {
+                if job.tmux_session.is_some() {
+                    warn!("Cron job {} will not run in tmux: the recorder is unavailable", job.id);
+                }
+                format!("{}{}", history::export_prefix(&job_environment(job)), job.command)
+            }
+        }
+    }
+
+    fn history(&self) -> Result<HistoryStore> {
+        self.settings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .history
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Cron manager is not initialized"))
+    }
+
+    fn timezone(&self) -> Option<Tz> {
+        self.settings.read().unwrap_or_else(|e| e.into_inner()).timezone
+    }
+
+    fn upcoming(&self, schedule: &Schedule, count: usize) -> Vec<DateTime<Utc>> {
+        let now = Utc::now();
+        match self.timezone() {
+            Some(tz) => schedule
+                .upcoming(&now.with_timezone(&tz), count)
+                .into_iter()
+                .map(|time| time.with_timezone(&Utc))
+                .collect(),
+            None => schedule
+                .upcoming(&now.with_timezone(&Local), count)
+                .into_iter()
+                .map(|time| time.with_timezone(&Utc))
+                .collect(),
+        }
+    }
+}
+
+/// The server's local time, which the cron daemon fires jobs in, e.g. `local (UTC+02:00)`
+pub fn daemon_timezone() -> String {
+    format!("local (UTC{})", Local::now().offset())
+}
+
+/// The tmux window a job runs in, if it names a session
+fn tmux_target(job: &CronJob) -> Option<TmuxTarget> {
+    let session = job.tmux_session.as_deref().map(str::trim).filter(|session| !session.is_empty())?;
+    Some(TmuxTarget {
+        session: session.to_string(),
+        window: job.tmux_window.as_deref().map(str::trim).filter(|window| !window.is_empty()).map(String::from),
+        keep_on_failure: job.keep_window_on_failure.unwrap_or(false),
+    })
+}
+
+fn validate_tmux_target(job: &CronJob) -> Result<()> {
+    match tmux_target(job) {
+        Some(target) => target.validate(),
+        None if job.tmux_window.as_deref().is_some_and(|window| !window.trim().is_empty()) => {
+            anyhow::bail!("A tmux window needs a tmux session")
+        }
+        None => Ok(()),
+    }
+}
+
+/// A job's environment variables, in a stable order for the crontab
+fn job_environment(job: &CronJob) -> Vec<(String, String)> {
+    job.environment.as_ref().map(sorted_environment).unwrap_or_default()
+}
+
+fn sorted_environment(environment: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> =
+        environment.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+    vars.sort();
+    vars
+}
+
+// Names as a shell accepts them; values go on a single crontab line
+fn validate_environment(job: &CronJob) -> Result<()> {
+    for (name, value) in job.environment.iter().flatten() {
+        let mut chars = name.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            anyhow::bail!("Invalid environment variable name '{}': use letters, digits and '_', not starting with a digit", name);
+        }
+        if value.contains(['\n', '\r', '\0']) {
+            anyhow::bail!("Environment variable {} can't contain line breaks or NUL characters", name);
+        }
     }
+    Ok(())
 }
 
 lazy_static::lazy_static! {
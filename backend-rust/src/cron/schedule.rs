@@ -0,0 +1,460 @@
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use serde::Serialize;
+use std::fmt;
+use thiserror::Error;
+
+// Give up looking for a fire time after this many years (covers Feb 29 schedules)
+const SEARCH_YEARS: i32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CronField {
+    Minute,
+    Hour,
+    DayOfMonth,
+    Month,
+    DayOfWeek,
+}
+
+impl CronField {
+    const ALL: [CronField; 5] = [
+        CronField::Minute,
+        CronField::Hour,
+        CronField::DayOfMonth,
+        CronField::Month,
+        CronField::DayOfWeek,
+    ];
+
+    fn bounds(self) -> (u32, u32) {
+        match self {
+            CronField::Minute => (0, 59),
+            CronField::Hour => (0, 23),
+            CronField::DayOfMonth => (1, 31),
+            CronField::Month => (1, 12),
+            // 7 is accepted as an alias for Sunday
+            CronField::DayOfWeek => (0, 7),
+        }
+    }
+
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            CronField::Month => &[
+                "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+            ],
+            CronField::DayOfWeek => &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"],
+            _ => &[],
+        }
+    }
+
+    // Value of names()[0]
+    fn first_name_value(self) -> u32 {
+        match self {
+            CronField::Month => 1,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for CronField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CronField::Minute => "minute",
+            CronField::Hour => "hour",
+            CronField::DayOfMonth => "day of month",
+            CronField::Month => "month",
+            CronField::DayOfWeek => "day of week",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Why a schedule was rejected, pointing at the offending field where there is one
+#[derive(Debug, Clone, Error, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[error("{}", self.describe())]
+pub struct ScheduleError {
+    pub field: Option<CronField>,
+    // The part of the field that failed to parse
+    pub value: Option<String>,
+    pub message: String,
+}
+
+impl ScheduleError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            field: None,
+            value: None,
+            message: message.into(),
+        }
+    }
+
+    fn field(field: CronField, value: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: Some(field),
+            value: Some(value.to_string()),
+            message: message.into(),
+        }
+    }
+
+    /// A syntactically valid schedule with no matching date, e.g. `0 0 30 2 *`
+    pub fn never_fires() -> Self {
+        Self::new("Schedule never fires: no date matches its day and month fields")
+    }
+
+    fn describe(&self) -> String {
+        match (&self.field, &self.value) {
+            (Some(field), Some(value)) => format!("Invalid {} '{}': {}", field, value, self.message),
+            _ => self.message.clone(),
+        }
+    }
+}
+
+/// A parsed crontab schedule (5 fields or an `@` macro)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u32,
+    days_of_month: u32,
+    months: u16,
+    // Bit 0 = Sunday; 7 is folded into 0
+    days_of_week: u8,
+    // Field started with `*`; decides the day-of-month/day-of-week OR rule
+    dom_star: bool,
+    dow_star: bool,
+    // `@reboot`: only runs at cron startup, so has no next run time
+    reboot: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self, ScheduleError> {
+        let expression = expression.trim();
+        if let Some(name) = expression.strip_prefix('@') {
+            return Self::parse_macro(name);
+        }
+
+        let parts: Vec<&str> = expression.split_whitespace().collect();
+        if parts.len() != 5 {
+            return Err(ScheduleError::new(format!(
+                "Expected 5 fields (minute hour day-of-month month day-of-week) or an @macro, got {}",
+                parts.len()
+            )));
+        }
+
+        let mut sets = [0u64; 5];
+        for (i, (field, text)) in CronField::ALL.iter().zip(&parts).enumerate() {
+            sets[i] = parse_field(*field, text)?;
+        }
+
+        // Fold Sunday=7 into 0
+        let mut days_of_week = sets[4];
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: sets[0],
+            hours: sets[1] as u32,
+            days_of_month: sets[2] as u32,
+            months: sets[3] as u16,
+            days_of_week: days_of_week as u8,
+            dom_star: parts[2].starts_with('*'),
+            dow_star: parts[4].starts_with('*'),
+            reboot: false,
+        })
+    }
+
+    fn parse_macro(name: &str) -> Result<Self, ScheduleError> {
+        let expression = match name.to_ascii_lowercase().as_str() {
+            "yearly" | "annually" => "0 0 1 1 *",
+            "monthly" => "0 0 1 * *",
+            "weekly" => "0 0 * * 0",
+            "daily" | "midnight" => "0 0 * * *",
+            "hourly" => "0 * * * *",
+            "reboot" => {
+                return Ok(Self {
+                    reboot: true,
+                    ..Self::parse("* * * * *")?
+                })
+            }
+            _ => return Err(ScheduleError::new(format!("Unknown schedule macro '@{}'", name))),
+        };
+        Self::parse(expression)
+    }
+
+    pub fn is_reboot(&self) -> bool {
+        self.reboot
+    }
+
+    /// First fire time strictly after `after`, in the same timezone
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        if self.reboot {
+            return None;
+        }
+
+        let tz = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let last_year = start.year() + SEARCH_YEARS;
+        let mut candidate = start;
+
+        while candidate.year() <= last_year {
+            if !bit(self.months as u64, candidate.month()) {
+                candidate = first_of_next_month(candidate.date())?;
+                continue;
+            }
+            if !self.day_matches(candidate.date()) {
+                candidate = candidate.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !bit(self.hours as u64, candidate.hour()) {
+                candidate = candidate.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if !bit(self.minutes, candidate.minute()) {
+                candidate += Duration::minutes(1);
+                continue;
+            }
+
+            match tz.from_local_datetime(&candidate) {
+                LocalResult::Single(time) => return Some(time),
+                // Repeated by a DST transition: cron fires once, on the first occurrence
+                LocalResult::Ambiguous(earliest, _) => {
+                    if earliest > *after {
+                        return Some(earliest);
+                    }
+                    candidate += Duration::minutes(1);
+                }
+                // Skipped by a DST transition
+                LocalResult::None => candidate += Duration::minutes(1),
+            }
+        }
+        None
+    }
+
+    /// The next `count` fire times after `after`
+    pub fn upcoming<Tz: TimeZone>(&self, after: &DateTime<Tz>, count: usize) -> Vec<DateTime<Tz>> {
+        let mut times = Vec::with_capacity(count);
+        let mut cursor = after.clone();
+        while times.len() < count {
+            match self.next_after(&cursor) {
+                Some(next) => {
+                    cursor = next.clone();
+                    times.push(next);
+                }
+                None => break,
+            }
+        }
+        times
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let dom = bit(self.days_of_month as u64, date.day());
+        let dow = bit(self.days_of_week as u64, date.weekday().num_days_from_sunday());
+        // Vixie cron: when both day fields are restricted, either may match
+        if self.dom_star || self.dow_star {
+            dom && dow
+        } else {
+            dom || dow
+        }
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn first_of_next_month(date: NaiveDate) -> Option<NaiveDateTime> {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+/// Parse one field (`*`, `a`, `a-b`, any of those with `/step`, comma-separated) into a bitset
+fn parse_field(field: CronField, text: &str) -> Result<u64, ScheduleError> {
+    let (min, max) = field.bounds();
+    let mut set = 0u64;
+
+    for element in text.split(',') {
+        if element.is_empty() {
+            return Err(ScheduleError::field(field, text, "empty list element"));
+        }
+
+        let (range, step) = match element.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| ScheduleError::field(field, element, format!("step '{}' is not a number", step)))?;
+                if step == 0 {
+                    return Err(ScheduleError::field(field, element, "step must be greater than 0"));
+                }
+                (range, Some(step))
+            }
+            None => (element, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            let start = parse_value(field, element, start)?;
+            let end = parse_value(field, element, end)?;
+            if start > end {
+                return Err(ScheduleError::field(
+                    field,
+                    element,
+                    format!("range start {} is after range end {}", start, end),
+                ));
+            }
+            (start, end)
+        } else {
+            let value = parse_value(field, element, range)?;
+            // `a/n` means every n-th value from a to the end of the field
+            (value, if step.is_some() { max } else { value })
+        };
+
+        let step = step.unwrap_or(1);
+        if step > max - min + 1 {
+            return Err(ScheduleError::field(
+                field,
+                element,
+                format!("step {} is larger than the {}-{} range", step, min, max),
+            ));
+        }
+
+        let mut value = start;
+        while value <= end {
+            set |= 1 << value;
+            value += step;
+        }
+    }
+
+    Ok(set)
+}
+
+fn parse_value(field: CronField, element: &str, text: &str) -> Result<u32, ScheduleError> {
+    let (min, max) = field.bounds();
+
+    let upper = text.to_ascii_uppercase();
+    if let Some(index) = field.names().iter().position(|name| *name == upper) {
+        return Ok(index as u32 + field.first_name_value());
+    }
+
+    let value: u32 = text.parse().map_err(|_| {
+        let expected = if field.names().is_empty() {
+            format!("'{}' is not a number", text)
+        } else {
+            format!("'{}' is not a number or one of {}", text, field.names().join(", "))
+        };
+        ScheduleError::field(field, element, expected)
+    })?;
+
+    if value < min || value > max {
+        return Err(ScheduleError::field(
+            field,
+            element,
+            format!("{} is out of range {}-{}", value, min, max),
+        ));
+    }
+    Ok(value)
+}
+
+/// Split a crontab entry into its schedule and command
+pub fn split_crontab_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let fields = if line.starts_with('@') { 1 } else { 5 };
+
+    let mut rest = line;
+    for _ in 0..fields {
+        let end = rest.find(char::is_whitespace)?;
+        rest = rest[end..].trim_start();
+    }
+    if rest.is_empty() {
+        return None;
+    }
+
+    let schedule = line[..line.len() - rest.len()].trim_end();
+    Some((schedule, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use chrono_tz::Europe::Berlin;
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> Option<DateTime<Utc>> {
+        Schedule::parse(expression).unwrap().next_after(&utc(after))
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let error = Schedule::parse("99 * * * *").unwrap_err();
+        assert_eq!(error.field, Some(CronField::Minute));
+        assert_eq!(error.value.as_deref(), Some("99"));
+    }
+
+    #[test]
+    fn rejects_reversed_ranges() {
+        let error = Schedule::parse("5-3 * * * *").unwrap_err();
+        assert_eq!(error.field, Some(CronField::Minute));
+        assert!(error.message.contains("after range end"));
+    }
+
+    #[test]
+    fn rejects_zero_steps() {
+        let error = Schedule::parse("*/0 * * * *").unwrap_err();
+        assert_eq!(error.value.as_deref(), Some("*/0"));
+    }
+
+    #[test]
+    fn folds_day_of_week_seven_into_sunday() {
+        assert_eq!(Schedule::parse("0 0 * * 7").unwrap(), Schedule::parse("0 0 * * 0").unwrap());
+        assert_eq!(Schedule::parse("0 0 * * 5-7").unwrap(), Schedule::parse("0 0 * * 0,5,6").unwrap());
+    }
+
+    #[test]
+    fn leap_day_waits_for_the_next_leap_year() {
+        assert_eq!(next("0 0 29 2 *", "2026-03-01T00:00:00Z"), Some(utc("2028-02-29T00:00:00Z")));
+    }
+
+    #[test]
+    fn impossible_dates_never_fire() {
+        assert_eq!(next("0 0 30 2 *", "2026-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // 2026-10-17 is a Saturday
+        let runs: Vec<_> = Schedule::parse("0 0 1-7 * MON")
+            .unwrap()
+            .upcoming(&utc("2026-10-17T00:00:00Z"), 4)
+            .into_iter()
+            .map(|time| time.date_naive().to_string())
+            .collect();
+        assert_eq!(runs, ["2026-10-19", "2026-10-26", "2026-11-01", "2026-11-02"]);
+    }
+
+    #[test]
+    fn skips_times_in_a_dst_gap() {
+        // 02:00-03:00 doesn't exist in Berlin on 2026-03-29
+        let schedule = Schedule::parse("30 2 * * *").unwrap();
+        let after = Berlin.with_ymd_and_hms(2026, 3, 28, 12, 0, 0).unwrap();
+        let next = schedule.next_after(&after).unwrap();
+        assert_eq!(next, Berlin.with_ymd_and_hms(2026, 3, 30, 2, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn fires_once_in_a_dst_overlap() {
+        // 02:00-03:00 happens twice in Berlin on 2026-10-25, first at UTC+2
+        let schedule = Schedule::parse("30 2 * * *").unwrap();
+        let after = Berlin.with_ymd_and_hms(2026, 10, 24, 12, 0, 0).unwrap();
+        let runs = schedule.upcoming(&after, 2);
+        assert_eq!(runs[0].with_timezone(&Utc), utc("2026-10-25T00:30:00Z"));
+        assert_eq!(runs[1].with_timezone(&Utc), utc("2026-10-26T01:30:00Z"));
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::tmux::run_tmux as tmux;
+
+// How often a running job's pane is checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Failed checks in a row before giving up on a job's pane
+const MAX_POLL_FAILURES: u32 = 10;
+
+/// A tmux window for a job to run in, so its output can be watched live
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TmuxTarget {
+    // Created when missing
+    pub session: String,
+    // Reused across runs when set; otherwise each run gets a fresh window
+    pub window: Option<String>,
+    // Leave a fresh window open when the run fails, to inspect its output
+    pub keep_on_failure: bool,
+}
+
+impl TmuxTarget {
+    pub fn validate(&self) -> Result<()> {
+        validate_name("session", &self.session)?;
+        if let Some(window) = &self.window {
+            validate_name("window", window)?;
+        }
+        Ok(())
+    }
+}
+
+/// Names end up in tmux targets (where `:` and `.` separate parts) and in crontab lines
+fn validate_name(kind: &str, name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('-')
+        || name.chars().any(|c| c.is_whitespace() || c.is_control() || c == ':' || c == '.')
+    {
+        anyhow::bail!("Invalid tmux {} name '{}': no spaces, ':' or '.'", kind, name);
+    }
+    Ok(())
+}
+
+/// How a job run in a window ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowExit {
+    Code(i32),
+    Signal(i32),
+    // The window was closed before the job finished
+    Closed,
+}
+
+/// What a job run in a window produced
+pub struct WindowRun {
+    pub exit: WindowExit,
+    // stdout and stderr together, as the pane showed them
+    pub output: String,
+}
+
+/// Run `command` through `sh -c` in a window of `target.session`, with `env` added to
+/// the pane's environment, and wait for it.
+/// The pane is kept after the command exits (`remain-on-exit`) so the exit status and
+/// output can be read back; afterwards a fresh window is closed unless it failed and
+/// `keep_on_failure` is set, and a named window stays, showing the latest run.
+pub async fn run(target: &TmuxTarget, job_id: &str, env: &[(String, String)], command: &str) -> Result<WindowRun> {
+    if tmux(&["has-session", "-t", &format!("={}", target.session)]).await.is_err() {
+        let home = std::env::var("HOME").unwrap_or_else(|_| "/".to_string());
+        tmux(&["new-session", "-d", "-s", &target.session, "-c", &home])
+            .await
+            .with_context(|| format!("Failed to create tmux session {}", target.session))?;
+    }
+
+    // The command waits until remain-on-exit is set, so even an instant exit is seen
+    let gate = format!("webmux-cron-{}", Uuid::new_v4().simple());
+    let script = format!("tmux wait-for {}; {}", gate, command);
+    let pane = start_pane(target, job_id, env, &script).await?;
+    tmux(&["set-option", "-w", "-t", &pane, "remain-on-exit", "on"]).await?;
+    tmux(&["wait-for", "-S", &gate]).await?;
+
+    let mut failures = 0;
+    let exit = loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        // Tab separated: the status is empty when a signal killed the command
+        let format = "#{pane_id}\t#{pane_dead}\t#{pane_dead_status}\t#{pane_dead_signal}";
+        let state = match tmux(&["display-message", "-p", "-t", &pane, format]).await {
+            Ok(state) => state,
+            Err(e) if is_gone(&e) => break WindowExit::Closed,
+            Err(e) => {
+                failures += 1;
+                if failures >= MAX_POLL_FAILURES {
+                    return Err(e.context("Lost track of the job's tmux pane"));
+                }
+                continue;
+            }
+        };
+        failures = 0;
+        match state.trim_end_matches('\n').split('\t').collect::<Vec<_>>()[..] {
+            [id, "1", status, signal, ..] if id == pane => {
+                break match (status.parse(), signal.parse()) {
+                    (Ok(code), _) => WindowExit::Code(code),
+                    (_, Ok(signal)) => WindowExit::Signal(signal),
+                    _ => WindowExit::Code(1),
+                }
+            }
+            [id, ..] if id == pane => continue,
+            // Some tmux versions answer for a missing pane with empty formats
+            _ => break WindowExit::Closed,
+        }
+    };
+    if exit == WindowExit::Closed {
+        return Ok(WindowRun {
+            exit,
+            output: String::new(),
+        });
+    }
+
+    let output = tmux(&["capture-pane", "-p", "-J", "-S", "-", "-t", &pane])
+        .await
+        .unwrap_or_default();
+    // Newer tmux versions note the exit in the pane itself
+    let mut lines: Vec<&str> = output.lines().collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty() || line.starts_with("Pane is dead")) {
+        lines.pop();
+    }
+    let mut output = lines.join("\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+
+    if target.window.is_none() && (exit == WindowExit::Code(0) || !target.keep_on_failure) {
+        let _ = tmux(&["kill-window", "-t", &pane]).await;
+    }
+    Ok(WindowRun { exit, output })
+}
+
+// The pane, or the whole server, no longer exists
+fn is_gone(error: &anyhow::Error) -> bool {
+    let message = error.to_string();
+    message.contains("can't find") || message.contains("no server running")
+}
+
+// Start the script in the target window; returns the id of its pane
+async fn start_pane(target: &TmuxTarget, job_id: &str, env: &[(String, String)], script: &str) -> Result<String> {
+    let session = format!("={}:", target.session);
+    let env: Vec<String> = env.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    let env_args: Vec<&str> = env.iter().flat_map(|var| ["-e", var.as_str()]).collect();
+    if let Some(name) = &target.window {
+        // Reuse the named window once its last run has exited; a window still busy
+        // (a previous run, or a shell the user opened) is left alone
+        let windows = tmux(&["list-windows", "-t", &session, "-F", "#{window_id}\t#{window_name}\t#{pane_dead}"]).await?;
+        let idle = windows.lines().find_map(|line| match line.split('\t').collect::<Vec<_>>()[..] {
+            [id, window_name, "1"] if window_name == name => Some(id.to_string()),
+            _ => None,
+        });
+        if let Some(id) = idle {
+            let mut args = vec!["respawn-window", "-k", "-t", &id];
+            args.extend(&env_args);
+            args.extend(["--", "sh", "-c", script]);
+            tmux(&args).await?;
+            let pane = tmux(&["display-message", "-p", "-t", &id, "#{pane_id}"]).await?;
+            let pane = pane.trim().to_string();
+            // Only this run's output should be read back
+            tmux(&["clear-history", "-t", &pane]).await?;
+            return Ok(pane);
+        }
+    }
+
+    let name = match &target.window {
+        Some(name) => name.clone(),
+        None => format!("cron-{}", job_id.chars().take(8).collect::<String>()),
+    };
+    let mut args = vec!["new-window", "-d", "-P", "-F", "#{pane_id}", "-t", &session, "-n", &name];
+    args.extend(&env_args);
+    args.extend(["--", "sh", "-c", script]);
+    let pane = tmux(&args)
+        .await
+        .with_context(|| format!("Failed to open a window in tmux session {}", target.session))?;
+    Ok(pane.trim().to_string())
+}
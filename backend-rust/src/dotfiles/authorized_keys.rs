@@ -0,0 +1,556 @@
+use anyhow::{Context, Result};
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+    Engine,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const AUTHORIZED_KEYS_PATH: &str = "~/.ssh/authorized_keys";
+// OpenSSH refuses shorter RSA keys
+const MIN_RSA_BITS: u32 = 1024;
+
+// Options sshd accepts, and whether they take a value (`name="value"`)
+const KEY_OPTIONS: &[(&str, bool)] = &[
+    ("agent-forwarding", false),
+    ("cert-authority", false),
+    ("command", true),
+    ("environment", true),
+    ("expiry-time", true),
+    ("from", true),
+    ("no-agent-forwarding", false),
+    ("no-port-forwarding", false),
+    ("no-pty", false),
+    ("no-touch-required", false),
+    ("no-user-rc", false),
+    ("no-x11-forwarding", false),
+    ("permitlisten", true),
+    ("permitopen", true),
+    ("port-forwarding", false),
+    ("principals", true),
+    ("pty", false),
+    ("restrict", false),
+    ("tunnel", true),
+    ("user-rc", false),
+    ("verify-required", false),
+    ("x11-forwarding", false),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyOption {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorizedKey {
+    // 1-based
+    pub line: usize,
+    pub options: Vec<KeyOption>,
+    pub key_type: String,
+    // Base64 key blob
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    // `SHA256:...`, as `ssh-keygen -l` prints it
+    pub fingerprint: String,
+    // Not known for certificates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bits: Option<u32>,
+    // The key this backend's SSH session authenticated with
+    pub current: bool,
+}
+
+/// A line that isn't a comment or a valid key; kept as it is
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidKeyLine {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorizedKeysView {
+    pub path: String,
+    // Addressed by their index here
+    pub keys: Vec<AuthorizedKey>,
+    pub invalid: Vec<InvalidKeyLine>,
+    // Of the content on disk, for edits; `None` when the file doesn't exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+}
+
+/// A change to one key
+#[derive(Debug, Clone)]
+pub enum AuthorizedKeyEdit {
+    // `key` is a public key line, e.g. the content of `id_ed25519.pub`; `options` and
+    // `comment` replace the ones in it when given
+    Add {
+        key: String,
+        options: Option<Vec<KeyOption>>,
+        comment: Option<String>,
+    },
+    Update {
+        index: usize,
+        options: Vec<KeyOption>,
+        comment: Option<String>,
+    },
+    Remove {
+        index: usize,
+    },
+}
+
+impl AuthorizedKeyEdit {
+    /// Commit message for the edit in git mode
+    pub fn describe(&self, keys: &AuthorizedKeys) -> String {
+        let name = |entry: &Entry| entry.comment.clone().unwrap_or_else(|| entry.fingerprint());
+        match self {
+            AuthorizedKeyEdit::Add { key, comment, .. } => match Entry::parse(key) {
+                Ok(entry) => format!("Add SSH key {}", comment.clone().unwrap_or_else(|| name(&entry))),
+                Err(_) => "Add SSH key".to_string(),
+            },
+            AuthorizedKeyEdit::Update { index, .. } => match keys.entry(*index) {
+                Some(entry) => format!("Update SSH key {}", name(entry)),
+                None => "Update SSH key".to_string(),
+            },
+            AuthorizedKeyEdit::Remove { index } => match keys.entry(*index) {
+                Some(entry) => format!("Remove SSH key {}", name(entry)),
+                None => "Remove SSH key".to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    options: Vec<KeyOption>,
+    key_type: String,
+    key: String,
+    comment: Option<String>,
+    bits: Option<u32>,
+}
+
+impl Entry {
+    /// Parse and check `[options] type base64-key [comment]`
+    fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let first = line.split_whitespace().next().unwrap_or_default();
+        let (options, rest) = if is_key_type(first) {
+            (Vec::new(), line)
+        } else {
+            let (options, rest) = split_options(line)?;
+            let options = parse_options(options)?;
+            check_options(&options)?;
+            (options, rest.trim_start())
+        };
+
+        let mut parts = rest.splitn(3, char::is_whitespace);
+        let key_type = parts.next().filter(|part| !part.is_empty()).context("Missing key type")?;
+        if !is_key_type(key_type) {
+            anyhow::bail!("Unknown key type: {}", key_type);
+        }
+        let key = parts.next().filter(|part| !part.is_empty()).context("Missing key")?;
+        let comment = parts.next().map(str::trim).filter(|comment| !comment.is_empty());
+        let bits = check_key(key_type, key)?;
+
+        Ok(Self {
+            options,
+            key_type: key_type.to_string(),
+            key: key.to_string(),
+            comment: comment.map(String::from),
+            bits,
+        })
+    }
+
+    fn render(&self) -> String {
+        let mut line = String::new();
+        if !self.options.is_empty() {
+            let options: Vec<String> = self
+                .options
+                .iter()
+                .map(|option| match &option.value {
+                    Some(value) => format!("{}=\"{}\"", option.name, value.replace('"', "\\\"")),
+                    None => option.name.clone(),
+                })
+                .collect();
+            line.push_str(&options.join(","));
+            line.push(' ');
+        }
+        line.push_str(&self.key_type);
+        line.push(' ');
+        line.push_str(&self.key);
+        if let Some(comment) = &self.comment {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        line
+    }
+
+    fn fingerprint(&self) -> String {
+        fingerprint(&self.key).unwrap_or_default()
+    }
+}
+
+/// An authorized_keys file that can be edited by key; comments, blank lines and lines
+/// that don't parse are written back as they were
+#[derive(Debug, Clone)]
+pub struct AuthorizedKeys {
+    lines: Vec<String>,
+    // Parsed keys and the index of their line
+    entries: Vec<(usize, Entry)>,
+    invalid: Vec<InvalidKeyLine>,
+}
+
+impl AuthorizedKeys {
+    pub fn parse(content: &str) -> Self {
+        let mut keys = Self {
+            lines: content.lines().map(String::from).collect(),
+            entries: Vec::new(),
+            invalid: Vec::new(),
+        };
+        for (i, line) in keys.lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            match Entry::parse(trimmed) {
+                Ok(entry) => keys.entries.push((i, entry)),
+                Err(e) => keys.invalid.push(InvalidKeyLine {
+                    line: i + 1,
+                    message: e.to_string(),
+                }),
+            }
+        }
+        keys
+    }
+
+    pub fn render(&self) -> String {
+        let mut content = self.lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        content
+    }
+
+    /// The keys, with `current` set on the ones in `current_keys` (key blobs)
+    pub fn keys(&self, current_keys: &[String]) -> Vec<AuthorizedKey> {
+        self.entries
+            .iter()
+            .map(|(i, entry)| AuthorizedKey {
+                line: i + 1,
+                options: entry.options.clone(),
+                key_type: entry.key_type.clone(),
+                key: entry.key.clone(),
+                comment: entry.comment.clone(),
+                fingerprint: entry.fingerprint(),
+                bits: entry.bits,
+                current: current_keys.contains(&entry.key),
+            })
+            .collect()
+    }
+
+    pub fn invalid(&self) -> Vec<InvalidKeyLine> {
+        self.invalid.clone()
+    }
+
+    /// Whether the file authorizes the key with this blob
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.iter().any(|(_, entry)| entry.key == key)
+    }
+
+    pub fn apply(&mut self, edit: &AuthorizedKeyEdit) -> Result<()> {
+        match edit {
+            AuthorizedKeyEdit::Add { key, options, comment } => {
+                let mut entry = Entry::parse(key)?;
+                if let Some(options) = options {
+                    check_options(options)?;
+                    entry.options = options.clone();
+                }
+                if let Some(comment) = comment {
+                    entry.comment = Some(comment.trim().to_string()).filter(|comment| !comment.is_empty());
+                }
+                check_comment(entry.comment.as_deref())?;
+                if self.contains(&entry.key) {
+                    anyhow::bail!("Key {} is already authorized", entry.fingerprint());
+                }
+                self.lines.push(entry.render());
+                self.entries.push((self.lines.len() - 1, entry));
+            }
+            AuthorizedKeyEdit::Update { index, options, comment } => {
+                check_options(options)?;
+                check_comment(comment.as_deref())?;
+                let (line, entry) = self
+                    .entries
+                    .get_mut(*index)
+                    .with_context(|| format!("No key at index {}", index))?;
+                entry.options = options.clone();
+                entry.comment = comment.as_deref().map(str::trim).filter(|c| !c.is_empty()).map(String::from);
+                self.lines[*line] = entry.render();
+            }
+            AuthorizedKeyEdit::Remove { index } => {
+                if *index >= self.entries.len() {
+                    anyhow::bail!("No key at index {}", index);
+                }
+                let (line, _) = self.entries.remove(*index);
+                self.lines.remove(line);
+                for (other, _) in &mut self.entries[*index..] {
+                    *other -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn entry(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index).map(|(_, entry)| entry)
+    }
+}
+
+/// `SHA256:` and the unpadded base64 SHA-256 of the key blob
+pub fn fingerprint(key: &str) -> Result<String> {
+    let blob = STANDARD.decode(key).context("Key is not valid base64")?;
+    Ok(format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(&blob))))
+}
+
+/// Blobs of the public keys this process's SSH session authenticated with. Only known
+/// when sshd runs with `ExposeAuthInfo yes`, which points `SSH_USER_AUTH` at a file
+/// of `publickey <type> <key>` lines.
+pub fn current_keys() -> Vec<String> {
+    let Some(path) = std::env::var_os("SSH_USER_AUTH") else {
+        return Vec::new();
+    };
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            (parts.next() == Some("publickey")).then_some(())?;
+            parts.nth(1).map(String::from)
+        })
+        .collect()
+}
+
+fn is_key_type(name: &str) -> bool {
+    let name = name.strip_suffix("-cert-v01@openssh.com").unwrap_or(name);
+    matches!(
+        name,
+        "ssh-rsa"
+            | "ssh-dss"
+            | "ssh-ed25519"
+            | "ecdsa-sha2-nistp256"
+            | "ecdsa-sha2-nistp384"
+            | "ecdsa-sha2-nistp521"
+            | "sk-ssh-ed25519@openssh.com"
+            | "sk-ecdsa-sha2-nistp256@openssh.com"
+    )
+}
+
+// Check that the blob is a well-formed key of `key_type`; returns its size in bits
+fn check_key(key_type: &str, key: &str) -> Result<Option<u32>> {
+    let blob = STANDARD.decode(key).context("Key is not valid base64")?;
+    let mut reader = BlobReader(&blob);
+    let blob_type = String::from_utf8_lossy(reader.string()?).to_string();
+    if blob_type != key_type {
+        anyhow::bail!("Key type {} doesn't match the key, which is {}", key_type, blob_type);
+    }
+
+    let bits = match key_type {
+        "ssh-dss" => anyhow::bail!("DSA keys are no longer accepted by OpenSSH"),
+        "ssh-rsa" => {
+            let _exponent = reader.string()?;
+            let modulus = reader.string()?;
+            let modulus = &modulus[modulus.iter().take_while(|byte| **byte == 0).count()..];
+            let bits = match modulus.first() {
+                Some(first) => (modulus.len() as u32 - 1) * 8 + (8 - first.leading_zeros()),
+                None => 0,
+            };
+            if bits < MIN_RSA_BITS {
+                anyhow::bail!("RSA key is {} bits; at least {} are needed", bits, MIN_RSA_BITS);
+            }
+            Some(bits)
+        }
+        "ssh-ed25519" | "sk-ssh-ed25519@openssh.com" => {
+            if reader.string()?.len() != 32 {
+                anyhow::bail!("Invalid Ed25519 key");
+            }
+            Some(256)
+        }
+        "ecdsa-sha2-nistp256" | "ecdsa-sha2-nistp384" | "ecdsa-sha2-nistp521" | "sk-ecdsa-sha2-nistp256@openssh.com" => {
+            let curve = String::from_utf8_lossy(reader.string()?).to_string();
+            let _point = reader.string()?;
+            match curve.as_str() {
+                "nistp256" => Some(256),
+                "nistp384" => Some(384),
+                "nistp521" => Some(521),
+                _ => anyhow::bail!("Unknown ECDSA curve: {}", curve),
+            }
+        }
+        // Certificates: the type is all that's checked
+        _ => None,
+    };
+    Ok(bits)
+}
+
+// Reads the length-prefixed strings SSH keys are made of
+struct BlobReader<'a>(&'a [u8]);
+
+impl<'a> BlobReader<'a> {
+    fn string(&mut self) -> Result<&'a [u8]> {
+        let truncated = || anyhow::anyhow!("Key is truncated");
+        let len_bytes: [u8; 4] = self.0.get(..4).ok_or_else(truncated)?.try_into()?;
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        let value = self.0.get(4..4 + len).ok_or_else(truncated)?;
+        self.0 = &self.0[4 + len..];
+        Ok(value)
+    }
+}
+
+// The options field ends at the first whitespace outside quotes. Like sshd, only `\"`
+// is an escape; any other backslash stands for itself.
+fn split_options(line: &str) -> Result<(&str, &str)> {
+    let mut quoted = false;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some((_, '"'))) => {
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => return Ok((&line[..i], &line[i..])),
+            _ => {}
+        }
+    }
+    if quoted {
+        anyhow::bail!("Unterminated quote in options");
+    }
+    anyhow::bail!("Missing key type")
+}
+
+// `name,name="value",...`
+fn parse_options(options: &str) -> Result<Vec<KeyOption>> {
+    let mut parsed = Vec::new();
+    let mut rest = options;
+    while !rest.is_empty() {
+        let name_len = rest.find([',', '=']).unwrap_or(rest.len());
+        let name = rest[..name_len].to_string();
+        rest = &rest[name_len..];
+        let value = match rest.strip_prefix('=') {
+            Some(quoted) => {
+                let inner = quoted.strip_prefix('"').context("Option values must be quoted")?;
+                let mut value = String::new();
+                let mut chars = inner.char_indices().peekable();
+                let mut end = None;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' if matches!(chars.peek(), Some((_, '"'))) => {
+                            chars.next();
+                            value.push('"');
+                        }
+                        '"' => {
+                            end = Some(i);
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                let end = end.context("Unterminated quote in options")?;
+                rest = &inner[end + 1..];
+                Some(value)
+            }
+            None => None,
+        };
+        parsed.push(KeyOption { name, value });
+        rest = match rest.strip_prefix(',') {
+            Some(rest) => rest,
+            None if rest.is_empty() => rest,
+            None => anyhow::bail!("Expected ',' between options"),
+        };
+    }
+    Ok(parsed)
+}
+
+fn check_options(options: &[KeyOption]) -> Result<()> {
+    for option in options {
+        let name = option.name.to_ascii_lowercase();
+        let Some((_, takes_value)) = KEY_OPTIONS.iter().find(|(known, _)| *known == name) else {
+            anyhow::bail!("Unknown key option: {}", option.name);
+        };
+        match (&option.value, takes_value) {
+            (Some(_), false) => anyhow::bail!("{} doesn't take a value", option.name),
+            (None, true) => anyhow::bail!("{} needs a value", option.name),
+            (Some(value), true) => {
+                if value.contains(['\n', '\r']) {
+                    anyhow::bail!("Invalid value for {}", option.name);
+                }
+                // `\"` before the closing quote would escape it; sshd has no way to write this
+                if value.ends_with('\\') {
+                    anyhow::bail!("The value of {} can't end with a backslash", option.name);
+                }
+                match name.as_str() {
+                    "from" | "principals" if value.trim().is_empty() => {
+                        anyhow::bail!("{} needs at least one pattern", option.name)
+                    }
+                    "environment" if !value.contains('=') => {
+                        anyhow::bail!("environment must be NAME=value")
+                    }
+                    // YYYYMMDD[HHMM[SS]]
+                    "expiry-time"
+                        if ![8, 12, 14].contains(&value.len()) || !value.chars().all(|c| c.is_ascii_digit()) =>
+                    {
+                        anyhow::bail!("expiry-time must be YYYYMMDD[HHMM[SS]]")
+                    }
+                    _ => {}
+                }
+            }
+            (None, false) => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_comment(comment: Option<&str>) -> Result<()> {
+    if comment.is_some_and(|comment| comment.contains(['\n', '\r'])) {
+        anyhow::bail!("The comment must be a single line");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(name: &str, value: &str) -> KeyOption {
+        KeyOption {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+        }
+    }
+
+    #[test]
+    fn option_values_round_trip() {
+        let options = vec![option("command", r#"echo "hi" \n a\"b"#), option("from", "10.0.0.0/8")];
+        check_options(&options).unwrap();
+        let entry = Entry {
+            options: options.clone(),
+            key_type: "ssh-ed25519".to_string(),
+            key: "AAAA".to_string(),
+            comment: None,
+            bits: None,
+        };
+        let line = entry.render();
+        let (rendered, rest) = split_options(&line).unwrap();
+        assert_eq!(rest, " ssh-ed25519 AAAA");
+        assert_eq!(parse_options(rendered).unwrap(), options);
+    }
+
+    #[test]
+    fn refuses_a_trailing_backslash() {
+        assert!(check_options(&[option("command", r"echo \")]).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
+
+pub const DEFAULT_DEPTH: usize = 1;
+pub const MAX_DEPTH: usize = 5;
+// Entries returned by one listing, across all levels
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirEntry {
+    pub name: String,
+    pub path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    // Octal permission bits, e.g. `0644`
+    pub mode: String,
+    // `ls -l` style, e.g. `-rw-r--r--`
+    pub permissions: String,
+    // Listed by `list-dotfiles`, built in or added by the user
+    pub tracked: bool,
+    // Subdirectory contents, down to the requested depth; symlinked directories aren't followed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<DirEntry>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirListing {
+    pub path: String,
+    pub entries: Vec<DirEntry>,
+    // Some entries were left out to stay under the listing limit
+    pub truncated: bool,
+}
+
+/// List `dir` down to `depth` levels, marking files in `tracked` (absolute paths)
+pub fn list_dir(dir: &Path, depth: usize, tracked: &HashSet<String>) -> Result<DirListing> {
+    let mut budget = MAX_ENTRIES;
+    let mut truncated = false;
+    let entries = list_level(dir, depth.clamp(1, MAX_DEPTH), tracked, &mut budget, &mut truncated)
+        .with_context(|| format!("Failed to list {}", dir.display()))?;
+    Ok(DirListing {
+        path: dir.to_string_lossy().to_string(),
+        entries,
+        truncated,
+    })
+}
+
+fn list_level(
+    dir: &Path,
+    depth: usize,
+    tracked: &HashSet<String>,
+    budget: &mut usize,
+    truncated: &mut bool,
+) -> Result<Vec<DirEntry>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.path(), entry.file_type().ok()?)))
+        .collect();
+    // Directories first, then by name
+    paths.sort_by(|(a, a_type), (b, b_type)| b_type.is_dir().cmp(&a_type.is_dir()).then_with(|| a.cmp(b)));
+
+    let mut entries = Vec::new();
+    for (path, file_type) in paths {
+        if *budget == 0 {
+            *truncated = true;
+            break;
+        }
+        *budget -= 1;
+
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let kind = if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else {
+            EntryKind::Other
+        };
+        let mode = metadata.permissions().mode();
+        let path_str = path.to_string_lossy().to_string();
+
+        // Unreadable subdirectories are listed without their contents
+        let children = (kind == EntryKind::Dir && depth > 1)
+            .then(|| list_level(&path, depth - 1, tracked, budget, truncated).ok())
+            .flatten();
+
+        entries.push(DirEntry {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            tracked: tracked.contains(&path_str),
+            path: path_str,
+            kind,
+            size: metadata.len(),
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            mode: format!("{:04o}", mode & 0o7777),
+            permissions: permission_string(kind, mode),
+            children,
+        });
+    }
+    Ok(entries)
+}
+
+fn permission_string(kind: EntryKind, mode: u32) -> String {
+    let mut result = String::with_capacity(10);
+    result.push(match kind {
+        EntryKind::Dir => 'd',
+        EntryKind::Symlink => 'l',
+        EntryKind::File => '-',
+        EntryKind::Other => '?',
+    });
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        result.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        result.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        result.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    result
+}
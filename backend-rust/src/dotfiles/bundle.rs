@@ -0,0 +1,317 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read, Write},
+    path::{Component, Path},
+};
+
+use super::{
+    diff::FileDiff,
+    history::{content_hash, VersionSource},
+    FileVersion,
+};
+
+pub const MANIFEST_NAME: &str = "manifest.json";
+// Directory inside the archive holding the files, by path relative to $HOME
+const FILES_DIR: &str = "files/";
+const BUNDLE_VERSION: u32 = 1;
+// Limits on archives accepted for import
+pub const MAX_BUNDLE_BYTES: usize = 32 * 1024 * 1024;
+const MAX_BUNDLE_FILES: usize = 1000;
+// Uploaded bundles kept for applying; the oldest is dropped first
+pub const MAX_STAGED_IMPORTS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    #[default]
+    Tar,
+    Zip,
+}
+
+impl BundleFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            BundleFormat::Tar => "application/x-tar",
+            BundleFormat::Zip => "application/zip",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            BundleFormat::Tar => "tar",
+            BundleFormat::Zip => "zip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub hostname: String,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    // Relative to $HOME
+    pub path: String,
+    // Unix permission bits
+    pub mode: u32,
+    pub size: u64,
+    pub hash: String,
+    // The file's recorded history, oldest first, without contents
+    pub versions: Vec<VersionMeta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionMeta {
+    pub timestamp: DateTime<Utc>,
+    pub hash: String,
+    pub size: u64,
+    pub source: VersionSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl From<FileVersion> for VersionMeta {
+    fn from(version: FileVersion) -> Self {
+        Self {
+            timestamp: version.timestamp,
+            hash: version.hash,
+            size: version.size,
+            source: version.source,
+            revision: version.revision,
+            message: version.message,
+        }
+    }
+}
+
+/// How an incoming file relates to the one on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportStatus {
+    /// Not on disk yet
+    New,
+    /// Same content as on disk
+    Identical,
+    /// The bundle's history contains the file as it is on disk: importing only adds newer changes
+    Update,
+    /// The file was here in this content before: importing would revert to it
+    Older,
+    /// Both sides changed since they last had the same content
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportFile {
+    pub path: String,
+    pub status: ImportStatus,
+    pub size: u64,
+    pub hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_hash: Option<String>,
+    // From the file on disk to the incoming one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<FileDiff>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    pub import_id: String,
+    pub exported_at: DateTime<Utc>,
+    pub hostname: String,
+    pub files: Vec<ImportFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub path: String,
+    pub success: bool,
+    // Copy of the content that was overwritten
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A bundle read back from an archive: the manifest and each file's content by path
+pub struct Bundle {
+    pub manifest: Manifest,
+    pub contents: HashMap<String, String>,
+}
+
+impl Bundle {
+    /// The manifest entry and content of every file, in manifest order
+    pub fn files(&self) -> impl Iterator<Item = (&ManifestFile, &str)> {
+        self.manifest
+            .files
+            .iter()
+            .filter_map(|file| Some((file, self.contents.get(&file.path)?.as_str())))
+    }
+}
+
+pub fn new_manifest(files: Vec<ManifestFile>) -> Manifest {
+    Manifest {
+        version: BUNDLE_VERSION,
+        exported_at: Utc::now(),
+        hostname: sysinfo::System::host_name().unwrap_or_default(),
+        files,
+    }
+}
+
+/// Write the manifest and `contents` (same order as `manifest.files`) as an archive
+pub fn write_bundle(format: BundleFormat, manifest: &Manifest, contents: &[String], out: impl Write) -> Result<()> {
+    let manifest_json = serde_json::to_vec_pretty(manifest)?;
+    let mtime = manifest.exported_at.timestamp().max(0) as u64;
+
+    match format {
+        BundleFormat::Tar => {
+            let mut builder = tar::Builder::new(out);
+            let mut append = |name: &str, data: &[u8], mode: u32| -> Result<()> {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(mode);
+                header.set_mtime(mtime);
+                header.set_entry_type(tar::EntryType::Regular);
+                builder.append_data(&mut header, name, data)?;
+                Ok(())
+            };
+            append(MANIFEST_NAME, &manifest_json, 0o644)?;
+            for (file, content) in manifest.files.iter().zip(contents) {
+                append(&format!("{}{}", FILES_DIR, file.path), content.as_bytes(), file.mode)?;
+            }
+            builder.into_inner()?.flush()?;
+        }
+        // The zip writer needs to seek, so the archive is assembled in memory
+        BundleFormat::Zip => {
+            let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            let mut options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+            if let Ok(time) = zip::DateTime::try_from(manifest.exported_at.naive_utc()) {
+                options = options.last_modified_time(time);
+            }
+            zip.start_file(MANIFEST_NAME, options.unix_permissions(0o644))?;
+            zip.write_all(&manifest_json)?;
+            for (file, content) in manifest.files.iter().zip(contents) {
+                zip.start_file(format!("{}{}", FILES_DIR, file.path), options.unix_permissions(file.mode))?;
+                zip.write_all(content.as_bytes())?;
+            }
+            let mut out = out;
+            out.write_all(&zip.finish()?.into_inner())?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a bundle written by `write_bundle`, telling tar from zip by its first bytes
+pub fn read_bundle(data: &[u8]) -> Result<Bundle> {
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+    // Unpacked bytes so far; a small compressed archive can expand enormously
+    let mut total = 0usize;
+    let mut add = |name: String, reader: &mut dyn Read, size: u64| -> Result<()> {
+        if entries.len() >= MAX_BUNDLE_FILES {
+            anyhow::bail!("Bundle has more than {} files", MAX_BUNDLE_FILES);
+        }
+        let remaining = (MAX_BUNDLE_BYTES - total) as u64;
+        let mut data = Vec::with_capacity(size.min(remaining) as usize);
+        // One byte past the limit tells an oversized entry from one that just fits
+        reader.take(remaining + 1).read_to_end(&mut data)?;
+        if data.len() as u64 > remaining {
+            anyhow::bail!("Bundle unpacks to more than {} MiB", MAX_BUNDLE_BYTES / (1024 * 1024));
+        }
+        total += data.len();
+        entries.push((name, data));
+        Ok(())
+    };
+
+    if data.starts_with(b"PK\x03\x04") {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).context("Invalid zip archive")?;
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_file() {
+                let (name, size) = (file.name().to_string(), file.size());
+                add(name, &mut file, size)?;
+            }
+        }
+    } else {
+        let mut archive = tar::Archive::new(Cursor::new(data));
+        for entry in archive.entries().context("Invalid tar archive")? {
+            let mut entry = entry.context("Invalid tar archive")?;
+            if entry.header().entry_type().is_file() {
+                let name = entry.path()?.to_string_lossy().to_string();
+                let size = entry.size();
+                add(name, &mut entry, size)?;
+            }
+        }
+    }
+
+    let mut manifest = None;
+    let mut contents = HashMap::new();
+    for (name, data) in entries {
+        if name == MANIFEST_NAME {
+            manifest = Some(serde_json::from_slice::<Manifest>(&data).context("Invalid bundle manifest")?);
+        } else if let Some(path) = name.strip_prefix(FILES_DIR) {
+            check_path(path)?;
+            let content = String::from_utf8(data).with_context(|| format!("{} is not text", path))?;
+            contents.insert(path.to_string(), content);
+        }
+    }
+
+    let mut manifest =
+        manifest.ok_or_else(|| anyhow::anyhow!("Not a dotfiles bundle: {} is missing", MANIFEST_NAME))?;
+    if manifest.version > BUNDLE_VERSION {
+        anyhow::bail!("Bundle version {} is newer than this server supports", manifest.version);
+    }
+    for file in &mut manifest.files {
+        check_path(&file.path)?;
+        let content = contents
+            .get(&file.path)
+            .ok_or_else(|| anyhow::anyhow!("Bundle lists {} but doesn't contain it", file.path))?;
+        // Go by what the archive holds, in case it was edited after export
+        file.hash = content_hash(content);
+        file.size = content.len() as u64;
+    }
+    Ok(Bundle { manifest, contents })
+}
+
+// Paths in a bundle must stay inside $HOME
+fn check_path(path: &str) -> Result<()> {
+    let relative = Path::new(path);
+    if path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        anyhow::bail!("Invalid path in bundle: {}", path);
+    }
+    Ok(())
+}
+
+/// Classify an incoming file against the disk content and its local history
+pub fn classify(
+    incoming: &ManifestFile,
+    current_hash: Option<&str>,
+    local_versions: &[FileVersion],
+) -> ImportStatus {
+    let Some(current_hash) = current_hash else {
+        return ImportStatus::New;
+    };
+    if current_hash == incoming.hash {
+        ImportStatus::Identical
+    } else if incoming.versions.iter().any(|version| version.hash == current_hash) {
+        ImportStatus::Update
+    } else if local_versions.iter().any(|version| version.hash == incoming.hash) {
+        ImportStatus::Older
+    } else {
+        ImportStatus::Conflict
+    }
+}
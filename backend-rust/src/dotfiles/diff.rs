@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::collections::HashMap;
+
+use super::secrets;
+
+pub const DEFAULT_CONTEXT: usize = 3;
+
+/// One side of a `DiffDotfile` comparison
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum DiffSide {
+    /// The file as it is on disk (empty if it doesn't exist)
+    Current,
+    /// A recorded version, by timestamp or (git mode) revision
+    Version {
+        timestamp: Option<DateTime<Utc>>,
+        revision: Option<String>,
+    },
+    /// A template, by name, rendered with `values` (defaults for the rest)
+    Template {
+        name: String,
+        #[serde(default)]
+        values: HashMap<String, serde_json::Value>,
+    },
+    /// Unsaved content from the editor
+    Content { content: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub content: String,
+    // 1-based; absent on the side the line doesn't exist on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_line: Option<usize>,
+}
+
+/// A run of changes with surrounding context, like a `@@` block of a unified diff
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// Line diff from `old` to `new` with `context` unchanged lines around each change
+pub fn diff_lines(old: &str, new: &str, context: usize) -> FileDiff {
+    let diff = TextDiff::from_lines(old, new);
+    let mut result = FileDiff {
+        added: 0,
+        removed: 0,
+        hunks: Vec::new(),
+    };
+
+    for group in diff.grouped_ops(context) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+
+        let mut lines = Vec::new();
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let kind = match change.tag() {
+                    ChangeTag::Equal => LineKind::Context,
+                    ChangeTag::Insert => {
+                        result.added += 1;
+                        LineKind::Added
+                    }
+                    ChangeTag::Delete => {
+                        result.removed += 1;
+                        LineKind::Removed
+                    }
+                };
+                lines.push(DiffLine {
+                    kind,
+                    content: change.value().trim_end_matches(['\n', '\r']).to_string(),
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                });
+            }
+        }
+
+        result.hunks.push(DiffHunk {
+            // Unified diff convention: an empty range starts at the line before it
+            old_start: if old_range.is_empty() { old_range.start } else { old_range.start + 1 },
+            old_lines: old_range.len(),
+            new_start: if new_range.is_empty() { new_range.start } else { new_range.start + 1 },
+            new_lines: new_range.len(),
+            lines,
+        });
+    }
+
+    result
+}
+
+/// `diff_lines`, with the secret values on both sides replaced by `secrets::MASK` when `mask`
+/// is set. Lines are diffed before masking, so a changed secret still shows as a change.
+pub fn diff_redacted(old: &str, new: &str, context: usize, mask: bool) -> FileDiff {
+    let mut diff = diff_lines(old, new, context);
+    if !mask {
+        return diff;
+    }
+    let old = secrets::redact(old.to_string(), true);
+    let new = secrets::redact(new.to_string(), true);
+    if !old.masked && !new.masked {
+        return diff;
+    }
+
+    let old_lines: Vec<&str> = old.content.lines().collect();
+    let new_lines: Vec<&str> = new.content.lines().collect();
+    for line in diff.hunks.iter_mut().flat_map(|hunk| hunk.lines.iter_mut()) {
+        let masked = match (line.new_line, line.old_line) {
+            (Some(n), _) => new_lines.get(n - 1),
+            (None, Some(n)) => old_lines.get(n - 1),
+            (None, None) => None,
+        };
+        if let Some(masked) = masked {
+            line.content = masked.to_string();
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_both_sides_of_a_changed_secret() {
+        let old = "a\nexport GITHUB_TOKEN=old\n";
+        let new = "a\nexport GITHUB_TOKEN=new\n";
+        let diff = diff_redacted(old, new, DEFAULT_CONTEXT, true);
+        assert_eq!((diff.added, diff.removed), (1, 1));
+        let lines = &diff.hunks[0].lines;
+        assert_eq!(lines[0].content, "a");
+        assert_eq!(lines[1].content, "export GITHUB_TOKEN=<redacted>");
+        assert_eq!(lines[2].content, "export GITHUB_TOKEN=<redacted>");
+    }
+}
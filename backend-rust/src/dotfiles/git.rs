@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use super::{history::content_hash, history::VersionSource, FileVersion};
+use crate::config::DotfilesConfig;
+
+// Commit trailer recording how a webmux commit came about; commits without it came from elsewhere
+const SOURCE_TRAILER: &str = "Webmux-Source";
+const REMOTE: &str = "origin";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatus {
+    pub branch: String,
+    // `None` until the first commit
+    pub head: Option<String>,
+    pub remote: Option<String>,
+    // Relative to the remote branch as of the last pull/push
+    pub ahead: usize,
+    pub behind: usize,
+    pub files: Vec<GitFileStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitFileStatus {
+    // Relative to the home directory
+    pub path: String,
+    // Two-letter `git status --porcelain` code, e.g. ` M`
+    pub status: String,
+}
+
+/// A bare repository whose work tree is the home directory, so tracked dotfiles
+/// stay where programs expect them
+pub struct GitRepo {
+    git_dir: PathBuf,
+    work_tree: PathBuf,
+    remote: Option<PathBuf>,
+    branch: String,
+}
+
+impl GitRepo {
+    pub async fn open(git_dir: PathBuf, work_tree: PathBuf, config: &DotfilesConfig) -> Result<Self> {
+        let repo = Self {
+            git_dir,
+            work_tree,
+            remote: config.git_remote.clone(),
+            branch: config.git_branch.clone(),
+        };
+
+        if !repo.git_dir.join("HEAD").exists() {
+            let output = Command::new("git")
+                .arg("init")
+                .arg("--bare")
+                .arg(format!("--initial-branch={}", repo.branch))
+                .arg(&repo.git_dir)
+                .output()
+                .await
+                .context("Failed to run git")?;
+            if !output.status.success() {
+                anyhow::bail!("git init failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+            }
+            // The work tree is all of $HOME; only explicitly tracked files matter
+            repo.git(&["config", "status.showUntrackedFiles", "no"]).await?;
+        }
+
+        // Commits need an identity even where the user never configured one
+        if repo.git(&["config", "user.email"]).await.is_err() {
+            repo.git(&["config", "user.name", "webmux"]).await?;
+            repo.git(&["config", "user.email", "webmux@localhost"]).await?;
+        }
+
+        if let Some(remote) = &repo.remote {
+            let url = remote.to_string_lossy();
+            let remotes = repo.git(&["remote"]).await?;
+            if remotes.lines().any(|name| name == REMOTE) {
+                repo.git(&["remote", "set-url", REMOTE, &url]).await?;
+            } else {
+                repo.git(&["remote", "add", REMOTE, &url]).await?;
+            }
+        }
+
+        Ok(repo)
+    }
+
+    pub fn work_tree(&self) -> &Path {
+        &self.work_tree
+    }
+
+    /// Commit the file's current state if it differs from HEAD (tracking it if needed).
+    /// Returns the new commit.
+    pub async fn commit_file(&self, path: &str, message: &str, source: VersionSource) -> Result<Option<String>> {
+        self.git(&["add", "--", path]).await?;
+        if self.git(&["diff", "--cached", "--quiet", "--", path]).await.is_ok() {
+            return Ok(None);
+        }
+
+        let trailer = format!("{}: {}", SOURCE_TRAILER, source_name(source));
+        self.git(&["commit", "--quiet", "-m", message, "-m", &trailer, "--", path])
+            .await?;
+        Ok(Some(self.git(&["rev-parse", "HEAD"]).await?.trim().to_string()))
+    }
+
+    /// Commits touching `path`, oldest first
+    pub async fn log(&self, path: &str) -> Result<Vec<FileVersion>> {
+        if self.head().await.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let format = format!("--format=%H%x1f%cI%x1f%s%x1f%(trailers:key={},valueonly)%x1e", SOURCE_TRAILER);
+        let log = self.git(&["log", "--reverse", &format, "--", path]).await?;
+
+        let mut versions = Vec::new();
+        for record in log.split('\x1e') {
+            let fields: Vec<&str> = record.trim().split('\x1f').collect();
+            let [revision, date, subject, source] = fields[..] else {
+                continue;
+            };
+            // Commits deleting the file have no content to restore
+            let Ok(content) = self.show(revision, path).await else {
+                continue;
+            };
+            versions.push(FileVersion {
+                timestamp: DateTime::parse_from_rfc3339(date)?.with_timezone(&Utc),
+                size: content.len() as u64,
+                hash: content_hash(&content),
+                content,
+                source: parse_source(source.trim()),
+                revision: Some(revision.to_string()),
+                message: Some(subject.to_string()),
+            });
+        }
+        Ok(versions)
+    }
+
+    pub async fn show(&self, revision: &str, path: &str) -> Result<String> {
+        self.git(&["show", &format!("{}:{}", revision, path)]).await
+    }
+
+    /// Resolve a user-supplied revision to a commit id
+    pub async fn resolve(&self, revision: &str) -> Result<String> {
+        if revision.starts_with('-') {
+            anyhow::bail!("Invalid revision: {}", revision);
+        }
+        let commit = self
+            .git(&["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", revision)])
+            .await
+            .map_err(|_| anyhow::anyhow!("Unknown revision: {}", revision))?;
+        Ok(commit.trim().to_string())
+    }
+
+    pub async fn status(&self) -> Result<GitStatus> {
+        let head = self.head().await;
+        let porcelain = self.git(&["status", "--porcelain", "-z"]).await?;
+        let files = porcelain
+            .split('\0')
+            .filter(|entry| entry.len() > 3)
+            .map(|entry| GitFileStatus {
+                path: entry[3..].to_string(),
+                status: entry[..2].to_string(),
+            })
+            .collect();
+
+        let (mut ahead, mut behind) = (0, 0);
+        let upstream = format!("refs/remotes/{}/{}", REMOTE, self.branch);
+        if head.is_some() && self.git(&["rev-parse", "--verify", "--quiet", &upstream]).await.is_ok() {
+            let counts = self
+                .git(&["rev-list", "--left-right", "--count", &format!("HEAD...{}", upstream)])
+                .await?;
+            let mut counts = counts.split_whitespace().map(|n| n.parse().unwrap_or(0));
+            ahead = counts.next().unwrap_or(0);
+            behind = counts.next().unwrap_or(0);
+        }
+
+        Ok(GitStatus {
+            branch: self.branch.clone(),
+            head,
+            remote: self.remote.as_ref().map(|remote| remote.to_string_lossy().to_string()),
+            ahead,
+            behind,
+            files,
+        })
+    }
+
+    /// Unified diff of the work tree against HEAD, for one file or every tracked one
+    pub async fn diff(&self, path: Option<&str>) -> Result<String> {
+        if self.head().await.is_none() {
+            return Ok(String::new());
+        }
+        let mut args = vec!["diff", "HEAD", "--"];
+        args.extend(path);
+        self.git(&args).await
+    }
+
+    pub async fn pull(&self) -> Result<String> {
+        self.require_remote()?;
+        self.git_verbose(&["pull", "--ff-only", REMOTE, &self.branch]).await
+    }
+
+    pub async fn push(&self) -> Result<String> {
+        self.require_remote()?;
+        self.git_verbose(&["push", REMOTE, &format!("HEAD:refs/heads/{}", self.branch)])
+            .await
+    }
+
+    fn require_remote(&self) -> Result<()> {
+        if self.remote.is_none() {
+            anyhow::bail!("No remote configured (set dotfiles.git_remote)");
+        }
+        Ok(())
+    }
+
+    async fn head(&self) -> Option<String> {
+        self.git(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .await
+            .ok()
+            .map(|head| head.trim().to_string())
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.run(args).await?.stdout).to_string())
+    }
+
+    /// Like `git`, but also returns what git reported on stderr (pull/push progress)
+    async fn git_verbose(&self, args: &[&str]) -> Result<String> {
+        let output = self.run(args).await?;
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(text)
+    }
+
+    async fn run(&self, args: &[&str]) -> Result<std::process::Output> {
+        let output = Command::new("git")
+            .arg("--git-dir")
+            .arg(&self.git_dir)
+            .arg("--work-tree")
+            .arg(&self.work_tree)
+            .args(args)
+            .current_dir(&self.work_tree)
+            .output()
+            .await
+            .context("Failed to run git")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            // Some commands (e.g. pull conflicts) explain themselves on stdout
+            let message = if stderr.trim().is_empty() { stdout } else { stderr };
+            anyhow::bail!("git {} failed: {}", args[0], message.trim());
+        }
+        Ok(output)
+    }
+}
+
+fn source_name(source: VersionSource) -> &'static str {
+    match source {
+        VersionSource::Webmux => "webmux",
+        VersionSource::Restore => "restore",
+        VersionSource::External => "external",
+    }
+}
+
+fn parse_source(trailer: &str) -> VersionSource {
+    match trailer {
+        "webmux" => VersionSource::Webmux,
+        "restore" => VersionSource::Restore,
+        // No trailer: committed outside webmux (e.g. pulled from the remote)
+        _ => VersionSource::External,
+    }
+}
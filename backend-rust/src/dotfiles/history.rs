@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::FileVersion;
+use crate::config::DotfilesConfig;
+
+/// How a version came to be recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionSource {
+    /// Saved through webmux
+    Webmux,
+    /// Restored from an earlier version through webmux
+    Restore,
+    /// Found on disk without webmux having written it (edited elsewhere, or first seen)
+    External,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VersionEntry {
+    timestamp: DateTime<Utc>,
+    hash: String,
+    size: u64,
+    source: VersionSource,
+}
+
+/// Dotfile versions on disk: content-addressed blobs under `blobs/<sha256>` and
+/// an `index.json` mapping each file path to its versions, oldest first
+pub struct VersionStore {
+    dir: PathBuf,
+    max_versions: usize,
+    max_age: Option<Duration>,
+    index: HashMap<String, Vec<VersionEntry>>,
+}
+
+impl VersionStore {
+    pub fn open(dir: PathBuf, config: &DotfilesConfig) -> Result<Self> {
+        fs::create_dir_all(dir.join("blobs"))
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let index_path = dir.join("index.json");
+        let index = match fs::read_to_string(&index_path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid history index: {}", index_path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut store = Self {
+            dir,
+            max_versions: config.history_max_versions,
+            max_age: config.history_max_age_days.map(|days| Duration::days(days as i64)),
+            index,
+        };
+
+        // Retention may have been tightened since the versions were recorded
+        let paths: Vec<String> = store.index.keys().cloned().collect();
+        let removed: Vec<String> = paths.iter().flat_map(|path| store.prune(path)).collect();
+        if !removed.is_empty() {
+            store.save_index()?;
+            store.collect_blobs(removed);
+        }
+        Ok(store)
+    }
+
+    /// Record `content` as the newest version of `path` unless it already is.
+    /// Returns whether a version was added.
+    pub fn record(&mut self, path: &str, content: &str, source: VersionSource) -> Result<bool> {
+        let hash = content_hash(content);
+        if self.latest_hash(path) == Some(hash.as_str()) {
+            return Ok(false);
+        }
+
+        let blob = self.blob_path(&hash);
+        if !blob.exists() {
+            write_atomic(&blob, content.as_bytes())?;
+        }
+
+        let versions = self.index.entry(path.to_string()).or_default();
+        versions.push(VersionEntry {
+            timestamp: Utc::now(),
+            hash,
+            size: content.len() as u64,
+            source,
+        });
+
+        let removed = self.prune(path);
+        self.save_index()?;
+        self.collect_blobs(removed);
+        Ok(true)
+    }
+
+    pub fn latest_hash(&self, path: &str) -> Option<&str> {
+        self.index
+            .get(path)
+            .and_then(|versions| versions.last())
+            .map(|version| version.hash.as_str())
+    }
+
+    /// Versions of `path`, oldest first
+    pub fn versions(&self, path: &str) -> Result<Vec<FileVersion>> {
+        self.index
+            .get(path)
+            .map(|versions| versions.iter().map(|entry| self.load(entry)).collect())
+            .unwrap_or_else(|| Ok(Vec::new()))
+    }
+
+    pub fn content_at(&self, path: &str, timestamp: DateTime<Utc>) -> Result<Option<String>> {
+        let entry = self
+            .index
+            .get(path)
+            .and_then(|versions| versions.iter().find(|v| v.timestamp == timestamp));
+        match entry {
+            Some(entry) => Ok(Some(self.read_blob(&entry.hash)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn load(&self, entry: &VersionEntry) -> Result<FileVersion> {
+        Ok(FileVersion {
+            timestamp: entry.timestamp,
+            content: self.read_blob(&entry.hash)?,
+            size: entry.size,
+            hash: entry.hash.clone(),
+            source: entry.source,
+            revision: None,
+            message: None,
+        })
+    }
+
+    fn read_blob(&self, hash: &str) -> Result<String> {
+        let path = self.blob_path(hash);
+        fs::read_to_string(&path).with_context(|| format!("Missing history blob: {}", path.display()))
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("blobs").join(hash)
+    }
+
+    /// Apply retention to one file's versions, returning the dropped ones' hashes
+    fn prune(&mut self, path: &str) -> Vec<String> {
+        let Some(versions) = self.index.get_mut(path) else {
+            return Vec::new();
+        };
+
+        let mut keep_from = versions.len().saturating_sub(self.max_versions);
+        if let Some(max_age) = self.max_age {
+            let cutoff = Utc::now() - max_age;
+            let expired = versions.iter().take_while(|v| v.timestamp < cutoff).count();
+            // Never drop the newest version: it is the baseline for detecting external edits
+            keep_from = keep_from.max(expired.min(versions.len() - 1));
+        }
+
+        versions.drain(..keep_from).map(|v| v.hash).collect()
+    }
+
+    /// Delete blobs no version refers to any more
+    fn collect_blobs(&self, candidates: Vec<String>) {
+        if candidates.is_empty() {
+            return;
+        }
+        let referenced: HashSet<&str> = self
+            .index
+            .values()
+            .flatten()
+            .map(|version| version.hash.as_str())
+            .collect();
+        for hash in candidates {
+            if !referenced.contains(hash.as_str()) {
+                let _ = fs::remove_file(self.blob_path(&hash));
+            }
+        }
+    }
+
+    fn save_index(&self) -> Result<()> {
+        let content = serde_json::to_vec_pretty(&self.index)?;
+        write_atomic(&self.dir.join("index.json"), &content)
+    }
+}
+
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Path, Query},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::{
+    io::{self, BufWriter, Write},
+    sync::Arc,
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::{bundle, bundle::BundleFormat, DOTFILES_MANAGER};
+use crate::{error::AppError, AppState};
+
+// Bytes gathered before a chunk of an export is sent
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Bundle export and import. Uploads and downloads are plain HTTP rather than
+/// WebSocket messages, so they're routed here and guarded like the static files.
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/dotfiles/export", get(export))
+        .route(
+            "/api/dotfiles/import",
+            post(import).layer(DefaultBodyLimit::max(bundle::MAX_BUNDLE_BYTES)),
+        )
+        .route("/api/dotfiles/import/:id", delete(discard_import))
+        .route("/api/dotfiles/import/:id/apply", post(apply_import))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: BundleFormat,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyImportRequest {
+    paths: Vec<String>,
+    #[serde(default)]
+    force: bool,
+}
+
+/// Stream every tracked dotfile and its history metadata as a tar or zip archive
+async fn export(Query(query): Query<ExportQuery>) -> crate::error::Result<Response> {
+    let (manifest, contents) = DOTFILES_MANAGER.export_bundle().await?;
+    let file_name = format!(
+        "dotfiles-{}.{}",
+        manifest.exported_at.format("%Y%m%d-%H%M%S"),
+        query.format.extension()
+    );
+
+    let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(8);
+    tokio::task::spawn_blocking(move || {
+        let writer = BufWriter::with_capacity(EXPORT_CHUNK_SIZE, ChannelWriter(tx.clone()));
+        if let Err(e) = bundle::write_bundle(query.format, &manifest, &contents, writer) {
+            warn!("Dotfiles export failed: {:#}", e);
+            let _ = tx.blocking_send(Err(io::Error::other(e.to_string())));
+        }
+    });
+    let body = Body::from_stream(futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Takes the archive as the request body and answers with a preview of each file
+async fn import(body: Bytes) -> crate::error::Result<Response> {
+    let preview = DOTFILES_MANAGER
+        .stage_import(&body)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("{:#}", e)))?;
+    Ok(Json(preview).into_response())
+}
+
+async fn apply_import(
+    Path(id): Path<String>,
+    Json(request): Json<ApplyImportRequest>,
+) -> crate::error::Result<Response> {
+    let results = DOTFILES_MANAGER
+        .apply_import(&id, &request.paths, request.force)
+        .await
+        .map_err(|e| AppError::NotFound(e.to_string()))?;
+    let success = results.iter().all(|result| result.success);
+    Ok(Json(serde_json::json!({ "success": success, "results": results })).into_response())
+}
+
+async fn discard_import(Path(id): Path<String>) -> crate::error::Result<Response> {
+    if !DOTFILES_MANAGER.discard_import(&id).await {
+        return Err(AppError::NotFound(format!("Import not found: {}", id)));
+    }
+    Ok(Json(serde_json::json!({ "success": true })).into_response())
+}
+
+// Hands what the archive writer produces to the response body
+struct ChannelWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client went away"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
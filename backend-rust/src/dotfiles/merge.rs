@@ -0,0 +1,160 @@
+use serde::Serialize;
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::ops::Range;
+use thiserror::Error;
+
+/// Outcome of a three-way merge
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Merge {
+    // Regions both sides changed differently are wrapped in conflict markers
+    pub content: String,
+    pub conflicts: usize,
+}
+
+/// A write refused because the file changed on disk since the client read it
+#[derive(Debug, Clone, Serialize, Error)]
+#[error("{message}")]
+#[serde(rename_all = "camelCase")]
+pub struct WriteConflict {
+    #[serde(skip)]
+    pub message: String,
+    // The file as it is on disk; empty when it was deleted
+    pub content: String,
+    // Of the content on disk; `None` when the file doesn't exist
+    pub etag: Option<String>,
+    // The edit and the disk content merged against the version the client read, when
+    // that version is still in the history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged: Option<Merge>,
+}
+
+// Base lines `start..end` that one side replaced with its lines `new`
+struct Change {
+    start: usize,
+    end: usize,
+    side: usize,
+    new: Range<usize>,
+}
+
+/// Merge the changes `ours` and `theirs` each made to `base`, line by line
+pub fn merge3(base: &str, ours: &str, theirs: &str, labels: [&str; 2]) -> Merge {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let sides: [Vec<&str>; 2] = [
+        ours.split_inclusive('\n').collect(),
+        theirs.split_inclusive('\n').collect(),
+    ];
+    let mut changes: Vec<Change> = (0..2)
+        .flat_map(|side| {
+            capture_diff_slices(Algorithm::Myers, &base, &sides[side])
+                .into_iter()
+                .filter_map(move |op| {
+                    let (tag, old, new) = op.as_tag_tuple();
+                    (tag != DiffTag::Equal).then_some(Change {
+                        start: old.start,
+                        end: old.end,
+                        side,
+                        new,
+                    })
+                })
+        })
+        .collect();
+    changes.sort_by_key(|change| (change.start, change.end));
+
+    let mut merge = Merge {
+        content: String::new(),
+        conflicts: 0,
+    };
+    let mut pos = 0;
+    let mut i = 0;
+    while i < changes.len() {
+        // Changes touching the same or adjacent base lines are resolved together
+        let start = changes[i].start;
+        let mut end = changes[i].end;
+        let mut j = i + 1;
+        while j < changes.len() && changes[j].start <= end {
+            end = end.max(changes[j].end);
+            j += 1;
+        }
+        let group = &changes[i..j];
+
+        // Each side's version of base[start..end]; `None` if it left it alone
+        let side_text = |side: usize| {
+            let mut text = String::new();
+            let mut at = start;
+            let mut changed = false;
+            for change in group.iter().filter(|change| change.side == side) {
+                text.extend(base[at..change.start].iter().copied());
+                text.extend(sides[side][change.new.clone()].iter().copied());
+                at = change.end;
+                changed = true;
+            }
+            text.extend(base[at..end].iter().copied());
+            changed.then_some(text)
+        };
+
+        merge.content.extend(base[pos..start].iter().copied());
+        match (side_text(0), side_text(1)) {
+            (Some(ours), Some(theirs)) if ours != theirs => {
+                merge.conflicts += 1;
+                push_marker(&mut merge.content, &format!("<<<<<<< {}", labels[0]));
+                merge.content.push_str(&ours);
+                push_marker(&mut merge.content, "=======");
+                merge.content.push_str(&theirs);
+                push_marker(&mut merge.content, &format!(">>>>>>> {}", labels[1]));
+            }
+            (Some(text), _) | (None, Some(text)) => merge.content.push_str(&text),
+            (None, None) => {}
+        }
+        pos = end;
+        i = j;
+    }
+    merge.content.extend(base[pos..].iter().copied());
+    merge
+}
+
+// Markers go on their own line, even after a last line without a newline
+fn push_marker(content: &mut String, marker: &str) {
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(marker);
+    content.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> Merge {
+        merge3(base, ours, theirs, ["ours", "theirs"])
+    }
+
+    #[test]
+    fn adjacent_edits_conflict() {
+        let merged = merge("a\nb\nc\n", "A\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(merged.content, "<<<<<<< ours\nA\nb\n=======\na\nB\n>>>>>>> theirs\nc\n");
+    }
+
+    #[test]
+    fn identical_edits_merge_cleanly() {
+        let merged = merge("a\nb\nc\n", "a\nB\nc\n", "a\nB\nc\n");
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.content, "a\nB\nc\n");
+    }
+
+    #[test]
+    fn insert_at_start_and_append_merge_cleanly() {
+        let merged = merge("a\nb\n", "x\na\nb\n", "a\nb\ny\n");
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.content, "x\na\nb\ny\n");
+    }
+
+    #[test]
+    fn markers_go_on_their_own_line_without_a_final_newline() {
+        let merged = merge("a\nb", "a\nc", "a\nd");
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(merged.content, "a\n<<<<<<< ours\nc\n=======\nd\n>>>>>>> theirs\n");
+    }
+}
@@ -1,12 +1,44 @@
 use anyhow::{Result, Context};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
+use crate::config::{Config, DotfilesMode};
+
+pub mod authorized_keys;
+pub mod browse;
+pub mod bundle;
+pub mod diff;
+pub mod git;
+pub mod history;
+pub mod http;
+pub mod merge;
+pub mod secrets;
+pub mod ssh_config;
+pub mod template;
+pub mod tracked;
+pub mod validate;
+pub mod watch;
+
+use authorized_keys::{AuthorizedKeyEdit, AuthorizedKeys, AuthorizedKeysView, AUTHORIZED_KEYS_PATH};
+use browse::DirListing;
+use bundle::{Bundle, ImportFile, ImportPreview, ImportResult, Manifest, ManifestFile};
+use diff::{DiffSide, FileDiff};
+use git::{GitRepo, GitStatus};
+use history::{VersionSource, VersionStore};
+use merge::WriteConflict;
+use secrets::SecretFinding;
+use ssh_config::{SshConfig, SshConfigView, SshHostEdit, SSH_CONFIG_PATH};
+use template::{TemplateApplication, TemplateVariable, VariableType};
+use tracked::{TrackedPattern, TrackedRegistry};
+use validate::{Validation, ValidationError};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DotFile {
     pub name: String,
@@ -17,6 +49,9 @@ pub struct DotFile {
     pub readable: bool,
     pub writable: bool,
     pub file_type: DotFileType,
+    // Secrets found in the file, without their values
+    #[serde(default)]
+    pub secrets: Vec<SecretFinding>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -34,12 +69,41 @@ pub struct FileVersion {
     pub timestamp: DateTime<Utc>,
     pub content: String,
     pub size: u64,
+    // SHA-256 of the content
     pub hash: String,
+    pub source: VersionSource,
+    // Commit and its subject (git mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Default)]
+struct DotfilesSettings {
+    reload_tmux_on_write: bool,
+    mask_secrets: bool,
+    // User templates, and copies of files overwritten by templates; `None` until initialized
+    templates_dir: Option<PathBuf>,
+    backups_dir: Option<PathBuf>,
+}
+
+/// Where versions are kept, per `dotfiles.mode`
+enum Backend {
+    History(VersionStore),
+    Git(GitRepo),
 }
 
 pub struct DotFilesManager {
-    // Store file history in memory for now (could be moved to DB later)
-    history: RwLock<HashMap<String, Vec<FileVersion>>>,
+    // `None` until initialized
+    backend: RwLock<Option<Backend>>,
+    settings: std::sync::RwLock<DotfilesSettings>,
+    // Paths and globs the user added; `None` until initialized
+    tracked: RwLock<Option<TrackedRegistry>>,
+    // Signalled when patterns are tracked or untracked
+    tracked_changed: tokio::sync::Notify,
+    // Uploaded bundles waiting to be applied, oldest first
+    imports: RwLock<Vec<(String, Bundle)>>,
     // Common dotfile paths relative to home directory
     common_dotfiles: Vec<(&'static str, DotFileType)>,
 }
@@ -47,7 +111,11 @@ pub struct DotFilesManager {
 impl DotFilesManager {
     pub fn new() -> Self {
         Self {
-            history: RwLock::new(HashMap::new()),
+            backend: RwLock::new(None),
+            settings: std::sync::RwLock::new(DotfilesSettings::default()),
+            tracked: RwLock::new(None),
+            tracked_changed: tokio::sync::Notify::new(),
+            imports: RwLock::new(Vec::new()),
             common_dotfiles: vec![
                 (".bashrc", DotFileType::Shell),
                 (".zshrc", DotFileType::Shell),
@@ -66,6 +134,37 @@ impl DotFilesManager {
         }
     }
 
+    pub async fn initialize(&self, config: &Config) -> Result<()> {
+        *self.settings.write().unwrap_or_else(|e| e.into_inner()) = DotfilesSettings {
+            reload_tmux_on_write: config.dotfiles.reload_tmux_on_write,
+            mask_secrets: config.dotfiles.mask_secrets,
+            templates_dir: Some(config.dotfiles_templates_dir()),
+            backups_dir: Some(config.dotfiles_backups_dir()),
+        };
+        *self.tracked.write().await = Some(TrackedRegistry::open(config.dotfiles_tracked_path())?);
+
+        let backend = match config.dotfiles.mode {
+            DotfilesMode::History => {
+                Backend::History(VersionStore::open(config.dotfiles_history_dir(), &config.dotfiles)?)
+            }
+            DotfilesMode::Git => {
+                let home_dir = dirs::home_dir()
+                    .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?
+                    .canonicalize()?;
+                let repo = GitRepo::open(config.dotfiles_repo_dir(), home_dir.clone(), &config.dotfiles).await?;
+
+                // Track the known dotfiles that exist, picking up changes made while we were down
+                for (file_name, _) in self.dotfile_names(&home_dir).await {
+                    track_in_git(&repo, &home_dir, &file_name).await;
+                }
+                info!("Dotfiles tracked in git repository {}", config.dotfiles_repo_dir().display());
+                Backend::Git(repo)
+            }
+        };
+        *self.backend.write().await = Some(backend);
+        Ok(())
+    }
+
     /// List common dotfiles with their metadata
     pub async fn list_dotfiles(&self) -> Result<Vec<DotFile>> {
         let home_dir = dirs::home_dir()
@@ -75,8 +174,8 @@ impl DotFilesManager {
         
         let mut dotfiles = Vec::new();
         
-        for (file_name, file_type) in &self.common_dotfiles {
-            let file_path = home_dir.join(file_name);
+        for (file_name, file_type) in self.dotfile_names(&home_dir).await {
+            let file_path = home_dir.join(&file_name);
             let path_str = file_path.to_string_lossy().to_string();
             
             let dotfile = if file_path.exists() {
@@ -94,7 +193,8 @@ impl DotFilesManager {
                     exists: true,
                     readable: self.is_readable(&file_path),
                     writable: self.is_writable(&file_path),
-                    file_type: file_type.clone(),
+                    file_type,
+                    secrets: scan_file(&file_path, metadata.len()),
                 }
             } else {
                 DotFile {
@@ -105,7 +205,8 @@ impl DotFilesManager {
                     exists: false,
                     readable: false,
                     writable: false,
-                    file_type: file_type.clone(),
+                    file_type,
+                    secrets: Vec::new(),
                 }
             };
             
@@ -116,13 +217,103 @@ impl DotFilesManager {
         Ok(dotfiles)
     }
 
+    /// The built-in dotfiles followed by the ones matched by user-tracked patterns,
+    /// relative to `home_dir`
+    async fn dotfile_names(&self, home_dir: &Path) -> Vec<(String, DotFileType)> {
+        let mut names: Vec<(String, DotFileType)> = self
+            .common_dotfiles
+            .iter()
+            .map(|(name, file_type)| (name.to_string(), file_type.clone()))
+            .collect();
+
+        let tracked = self.tracked.read().await;
+        let patterns = tracked.as_ref().map(|registry| registry.resolve(home_dir)).unwrap_or_default();
+        for name in patterns.into_iter().flat_map(|pattern| pattern.matches) {
+            if !names.iter().any(|(known, _)| *known == name) {
+                let file_type = self.file_type_of(&home_dir.join(&name));
+                names.push((name, file_type));
+            }
+        }
+        names
+    }
+
+    /// User-tracked paths and globs with the files they match
+    pub async fn tracked_patterns(&self) -> Result<Vec<TrackedPattern>> {
+        let home_dir = home_dir()?;
+        let tracked = self.tracked.read().await;
+        Ok(tracked.as_ref().ok_or_else(not_initialized)?.resolve(&home_dir))
+    }
+
+    /// Track a path or a glob under ~/.config; in git mode the files it matches are committed
+    pub async fn track(&self, pattern: &str) -> Result<Vec<TrackedPattern>> {
+        let home_dir = home_dir()?;
+        let added = {
+            let mut tracked = self.tracked.write().await;
+            let registry = tracked.as_mut().ok_or_else(not_initialized)?;
+            registry.add(pattern, &home_dir)?
+        };
+
+        if let Some(pattern) = &added {
+            info!("Tracking dotfiles {}", pattern);
+            self.tracked_changed.notify_one();
+            if let Some(Backend::Git(repo)) = self.backend.read().await.as_ref() {
+                let matches = self
+                    .tracked_patterns()
+                    .await?
+                    .into_iter()
+                    .find(|tracked| tracked.pattern == *pattern)
+                    .map(|tracked| tracked.matches)
+                    .unwrap_or_default();
+                for file_name in matches {
+                    track_in_git(repo, repo.work_tree(), &file_name).await;
+                }
+            }
+        }
+        self.tracked_patterns().await
+    }
+
+    /// Stop listing a tracked path or glob. Files keep their recorded history.
+    pub async fn untrack(&self, pattern: &str) -> Result<Vec<TrackedPattern>> {
+        let home_dir = home_dir()?;
+        {
+            let mut tracked = self.tracked.write().await;
+            let registry = tracked.as_mut().ok_or_else(not_initialized)?;
+            if !registry.remove(pattern, &home_dir)? {
+                anyhow::bail!("Not tracked: {}", pattern);
+            }
+        }
+        info!("Stopped tracking dotfiles {}", pattern);
+        self.tracked_changed.notify_one();
+        self.tracked_patterns().await
+    }
+
+    /// Directory tree under $HOME (the home directory itself by default), for finding files to track
+    pub async fn browse_dir(&self, path: Option<&str>, depth: usize) -> Result<DirListing> {
+        let home_dir = home_dir()?.canonicalize()?;
+        let dir = match path {
+            Some(path) => self.validate_and_resolve_path(path)?,
+            None => home_dir.clone(),
+        };
+        if !dir.starts_with(&home_dir) {
+            anyhow::bail!("{} is outside the home directory", dir.display());
+        }
+        if !dir.is_dir() {
+            anyhow::bail!("Not a directory: {}", dir.display());
+        }
+
+        let tracked: HashSet<String> = self
+            .dotfile_names(&home_dir)
+            .await
+            .into_iter()
+            .map(|(name, _)| home_dir.join(name).to_string_lossy().to_string())
+            .collect();
+        browse::list_dir(&dir, depth, &tracked)
+    }
+
     /// Read a dotfile's content with security validation
     pub async fn read_dotfile(&self, path: &str) -> Result<String> {
         let file_path = self.validate_and_resolve_path(path)?;
         
-        // Debug logging
-        info!("Reading dotfile - requested path: {}, resolved path: {}", path, file_path.display());
-        
         // Check if file is readable
         if !self.is_readable(&file_path) {
             return Err(anyhow::anyhow!("File is not readable: {}", path));
@@ -132,115 +323,502 @@ impl DotFilesManager {
         let content = fs::read_to_string(&file_path)
             .with_context(|| format!("Failed to read file: {}", path))?;
         
-        info!("Read dotfile: {} ({} bytes) from {}", path, content.len(), file_path.display());
-        
-        // Check if this is .zshrc and log more details
-        if path.contains("zshrc") {
-            info!(".zshrc detected - checking content characteristics");
-            let lines: Vec<&str> = content.lines().collect();
-            info!("Total lines: {}", lines.len());
-            info!("First non-comment line: {:?}", lines.iter().find(|l| !l.trim().starts_with('#') && !l.trim().is_empty()));
-            info!("Contains 'Zim' setup: {}", content.contains("Zim"));
-            info!("Contains 'autoload': {}", content.contains("autoload"));
+        // Only the size: dotfiles may hold secrets
+        info!("Read dotfile: {} ({} bytes)", file_path.display(), content.len());
+
+        if let Err(e) = self.record_version(&file_path, &content, VersionSource::External, None).await {
+            warn!("Failed to record version of {}: {}", file_path.display(), e);
         }
         
-        // Log first 200 chars for debugging
-        let preview = if content.len() > 200 { 
-            format!("{}...", &content[..200])
-        } else { 
-            content.clone() 
-        };
-        info!("Content preview: {}", preview.replace('\n', "\\n"));
-        
         Ok(content)
     }
 
-    /// Write content to a dotfile with backup
-    pub async fn write_dotfile(&self, path: &str, content: &str) -> Result<()> {
+    /// Write content to a dotfile, recording it in the version history.
+    /// `message` becomes the commit message in git mode. Content that fails its
+    /// syntax check is refused with a `ValidationError` unless `force` is set.
+    /// `etag` is the hash of the content the edit started from (`None` for a new file);
+    /// if the file no longer matches it the write is refused with a `WriteConflict`.
+    pub async fn write_dotfile(
+        &self,
+        path: &str,
+        content: &str,
+        message: Option<&str>,
+        force: bool,
+        etag: Option<&str>,
+    ) -> Result<()> {
+        self.check_unmasked(path, content)?;
+        self.check_etag(path, content, etag).await?;
+        self.check_syntax(path, content, force).await?;
+        self.write_version(path, content, VersionSource::Webmux, message, None).await
+    }
+
+    // Refuse to overwrite changes made since the client read the file
+    async fn check_etag(&self, path: &str, content: &str, etag: Option<&str>) -> Result<()> {
+        let file_path = self.validate_and_resolve_path(path)?;
+        let current = match fs::read_to_string(&file_path) {
+            Ok(current) => Some(current),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read file: {}", path)),
+        };
+        let current_etag = current.as_deref().map(history::content_hash);
+        if current_etag.as_deref() == etag || current.as_deref() == Some(content) {
+            return Ok(());
+        }
+
+        let message = match (etag, &current) {
+            (None, Some(_)) => format!("{} exists; send the etag it was read with to overwrite it", path),
+            (Some(_), None) => format!("{} was deleted since it was read", path),
+            _ => format!("{} changed on disk since it was read", path),
+        };
+        // The version the client read, to merge both edits against
+        let base = match (etag, &current) {
+            (Some(etag), Some(_)) => self
+                .get_file_history(path)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .rev()
+                .find(|version| version.hash == etag)
+                .map(|version| version.content),
+            _ => None,
+        };
+        let current = current.unwrap_or_default();
+        let merged = base.map(|base| merge::merge3(&base, content, &current, ["yours", "on disk"]));
+        Err(WriteConflict {
+            message,
+            content: current,
+            etag: current_etag,
+            merged,
+        }
+        .into())
+    }
+
+    // Refuse content that was read masked, so secrets aren't overwritten with the mask
+    fn check_unmasked(&self, path: &str, content: &str) -> Result<()> {
+        if !content.contains(secrets::MASK) {
+            return Ok(());
+        }
+        let file_path = self.validate_and_resolve_path(path)?;
+        let current = fs::read_to_string(&file_path).unwrap_or_default();
+        if !secrets::scan(&current).is_empty() && !current.contains(secrets::MASK) {
+            anyhow::bail!(
+                "{} still contains masked secrets ({}); read it with reveal before saving",
+                path,
+                secrets::MASK
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether contents sent to clients have their secrets masked unless revealed
+    pub fn masks_secrets(&self) -> bool {
+        self.settings.read().unwrap_or_else(|e| e.into_inner()).mask_secrets
+    }
+
+    // Refuse content failing its syntax check, unless forced
+    async fn check_syntax(&self, path: &str, content: &str, force: bool) -> Result<()> {
+        let validation = self.validate_dotfile(path, content).await?;
+        if !validation.valid {
+            if !force {
+                return Err(ValidationError { validation }.into());
+            }
+            warn!("Writing {} despite failed syntax check: {}", path, ValidationError { validation });
+        }
+        Ok(())
+    }
+
+    /// Syntax-check content proposed for a dotfile without writing it
+    pub async fn validate_dotfile(&self, path: &str, content: &str) -> Result<Validation> {
+        let file_path = self.validate_and_resolve_path(path)?;
+        let file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        validate::validate(&self.file_type_of(&file_path), &file_name, content).await
+    }
+
+    /// Where to reload tmux's config from after writing `path`: set when the file is a
+    /// tmux config and `dotfiles.reload_tmux_on_write` is on
+    pub fn tmux_reload_path(&self, path: &str) -> Option<String> {
+        if !self.settings.read().unwrap_or_else(|e| e.into_inner()).reload_tmux_on_write {
+            return None;
+        }
+        let file_path = self.validate_and_resolve_path(path).ok()?;
+        (self.file_type_of(&file_path) == DotFileType::Tmux).then(|| file_path.to_string_lossy().to_string())
+    }
+
+    /// `~/.ssh/config` parsed into its `Host` blocks; empty when the file doesn't exist
+    pub async fn ssh_config(&self) -> Result<SshConfigView> {
+        let (config, etag) = self.read_ssh_config().await?;
+        self.ssh_config_view(&config, etag)
+    }
+
+    /// Change one `Host` block of `~/.ssh/config`. The file is written like any other
+    /// edit: checked with `ssh -G` unless `force` is set, and refused with a
+    /// `WriteConflict` if it changed since `etag` was read.
+    pub async fn edit_ssh_config(&self, edit: &SshHostEdit, etag: Option<&str>, force: bool) -> Result<SshConfigView> {
+        let (mut config, _) = self.read_ssh_config().await?;
+        let message = edit.describe(&config);
+        config.apply(edit)?;
+        let content = config.render();
+        self.write_dotfile(SSH_CONFIG_PATH, &content, Some(&message), force, etag).await?;
+        self.ssh_config_view(&config, Some(history::content_hash(&content)))
+    }
+
+    async fn read_ssh_config(&self) -> Result<(SshConfig, Option<String>)> {
+        if !self.validate_and_resolve_path(SSH_CONFIG_PATH)?.exists() {
+            return Ok((SshConfig::parse(""), None));
+        }
+        let content = self.read_dotfile(SSH_CONFIG_PATH).await?;
+        Ok((SshConfig::parse(&content), Some(history::content_hash(&content))))
+    }
+
+    fn ssh_config_view(&self, config: &SshConfig, etag: Option<String>) -> Result<SshConfigView> {
+        Ok(SshConfigView {
+            path: self.validate_and_resolve_path(SSH_CONFIG_PATH)?.to_string_lossy().to_string(),
+            global: config.global(),
+            hosts: config.hosts(),
+            etag,
+        })
+    }
+
+    /// The keys in `~/.ssh/authorized_keys` with their fingerprints
+    pub async fn authorized_keys(&self) -> Result<AuthorizedKeysView> {
+        let (content, etag) = match self.validate_and_resolve_path(AUTHORIZED_KEYS_PATH)?.exists() {
+            true => {
+                let content = self.read_dotfile(AUTHORIZED_KEYS_PATH).await?;
+                let etag = history::content_hash(&content);
+                (content, Some(etag))
+            }
+            false => (String::new(), None),
+        };
+        self.authorized_keys_view(&content, etag)
+    }
+
+    /// Add, change or remove one key of `~/.ssh/authorized_keys`, refused with a
+    /// `WriteConflict` if the file changed since `etag` was read
+    pub async fn edit_authorized_keys(&self, edit: &AuthorizedKeyEdit, etag: Option<&str>) -> Result<AuthorizedKeysView> {
+        let file_path = self.validate_and_resolve_path(AUTHORIZED_KEYS_PATH)?;
+        let current = match fs::read_to_string(&file_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).context("Failed to read authorized_keys"),
+        };
+        let mut keys = AuthorizedKeys::parse(&current);
+        let message = edit.describe(&keys);
+        keys.apply(edit)?;
+        let content = keys.render();
+        self.write_dotfile(AUTHORIZED_KEYS_PATH, &content, Some(&message), false, etag).await?;
+        // sshd ignores the file if others can write to it
+        if let Err(e) = fs::set_permissions(&file_path, fs::Permissions::from_mode(0o600)) {
+            warn!("Failed to set permissions of {}: {}", file_path.display(), e);
+        }
+        self.authorized_keys_view(&content, Some(history::content_hash(&content)))
+    }
+
+    fn authorized_keys_view(&self, content: &str, etag: Option<String>) -> Result<AuthorizedKeysView> {
+        let keys = AuthorizedKeys::parse(content);
+        Ok(AuthorizedKeysView {
+            path: self.validate_and_resolve_path(AUTHORIZED_KEYS_PATH)?.to_string_lossy().to_string(),
+            keys: keys.keys(&authorized_keys::current_keys()),
+            invalid: keys.invalid(),
+            etag,
+        })
+    }
+
+    // Refuse writes to authorized_keys that drop the key this session logged in with,
+    // when sshd tells us which one that is
+    fn check_current_key(&self, file_path: &Path, content: &str) -> Result<()> {
+        let authorized_keys = self.validate_and_resolve_path(AUTHORIZED_KEYS_PATH)?;
+        if file_path != authorized_keys {
+            return Ok(());
+        }
+        let current = fs::read_to_string(file_path).unwrap_or_default();
+        let (before, after) = (AuthorizedKeys::parse(&current), AuthorizedKeys::parse(content));
+        for key in authorized_keys::current_keys() {
+            if before.contains(&key) && !after.contains(&key) {
+                anyhow::bail!(
+                    "Refusing to remove the key this session authenticated with ({})",
+                    authorized_keys::fingerprint(&key).unwrap_or(key)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Type of a file, from the known dotfiles or else from its name
+    fn file_type_of(&self, file_path: &Path) -> DotFileType {
+        let relative = dirs::home_dir()
+            .and_then(|home| home.canonicalize().ok())
+            .and_then(|home| file_path.strip_prefix(home).ok().map(Path::to_path_buf))
+            .map(|relative| relative.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some((_, file_type)) = self.common_dotfiles.iter().find(|(name, _)| *name == relative) {
+            return file_type.clone();
+        }
+
+        let name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match name.as_str() {
+            _ if name.ends_with("tmux.conf") => DotFileType::Tmux,
+            _ if relative.starts_with(".config/nvim/") || relative.starts_with(".vim/") => DotFileType::Vim,
+            ".gitconfig" => DotFileType::Git,
+            "config" if relative == ".config/git/config" => DotFileType::Git,
+            _ if relative.starts_with(".ssh/") => DotFileType::SSH,
+            ".bashrc" | ".bash_profile" | ".bash_login" | ".bash_logout" | ".bash_aliases" | ".profile"
+            | ".zshrc" | ".zshenv" | ".zprofile" | ".zlogin" | ".zlogout" => DotFileType::Shell,
+            _ if name.ends_with(".sh") || name.ends_with(".bash") || name.ends_with(".zsh") => DotFileType::Shell,
+            _ => DotFileType::Other,
+        }
+    }
+
+    async fn write_version(
+        &self,
+        path: &str,
+        content: &str,
+        source: VersionSource,
+        message: Option<&str>,
+        // Permissions for the file if it doesn't exist yet; the umask decides otherwise
+        new_file_mode: Option<u32>,
+    ) -> Result<()> {
         let file_path = self.validate_and_resolve_path(path)?;
         
         // Check if file/directory is writable
         if file_path.exists() && !self.is_writable(&file_path) {
             return Err(anyhow::anyhow!("File is not writable: {}", path));
         }
-        
-        // Create backup if file exists
-        if file_path.exists() {
-            self.create_backup(&file_path).await?;
+
+        self.check_current_key(&file_path, content)?;
+
+        // Every write has to become a commit in git mode
+        if let Some(Backend::Git(repo)) = self.backend.read().await.as_ref() {
+            repo_path(repo, &file_path)?;
         }
         
+        // Keep edits made outside webmux since the last recorded version
+        self.snapshot(&file_path).await;
+        
         // Ensure parent directory exists
         if let Some(parent) = file_path.parent() {
             fs::create_dir_all(parent)?;
         }
         
-        // Write new content
-        fs::write(&file_path, content)
-            .with_context(|| format!("Failed to write file: {}", path))?;
+        // Write new content. A new file with a mode never exists with looser permissions,
+        // even briefly, so nothing else can read credentials from it.
+        match new_file_mode.filter(|_| !file_path.exists()) {
+            Some(mode) => fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(mode)
+                .open(&file_path)
+                .and_then(|mut file| {
+                    // `mode` was narrowed by the umask
+                    file.set_permissions(fs::Permissions::from_mode(mode))?;
+                    file.write_all(content.as_bytes())
+                }),
+            None => fs::write(&file_path, content),
+        }
+        .with_context(|| format!("Failed to write file: {}", path))?;
         
         info!("Wrote dotfile: {} ({} bytes)", path, content.len());
+
+        if let Err(e) = self.record_version(&file_path, content, source, message).await {
+            warn!("Failed to record version of {}: {}", file_path.display(), e);
+        }
         Ok(())
     }
 
-    /// Create a backup of a file
-    async fn create_backup(&self, file_path: &Path) -> Result<()> {
-        let content = fs::read_to_string(file_path)?;
-        let metadata = fs::metadata(file_path)?;
-        
-        let version = FileVersion {
-            timestamp: Utc::now(),
-            content: content.clone(),
-            size: metadata.len(),
-            hash: self.calculate_hash(&content),
+    /// Record the file's current content if it changed since the last recorded version
+    async fn snapshot(&self, file_path: &Path) {
+        let Ok(content) = fs::read_to_string(file_path) else {
+            return;
         };
-        
-        let path_str = file_path.to_string_lossy().to_string();
-        let mut history = self.history.write().await;
-        let versions = history.entry(path_str.clone()).or_insert_with(Vec::new);
-        
-        // Keep only last 10 versions
-        if versions.len() >= 10 {
-            versions.remove(0);
+        if let Err(e) = self.record_version(file_path, &content, VersionSource::External, None).await {
+            warn!("Failed to record version of {}: {}", file_path.display(), e);
         }
-        
-        versions.push(version);
-        info!("Created backup for: {}", path_str);
-        Ok(())
     }
 
-    /// Get version history for a file
+    /// Returns whether a new version was recorded, i.e. the content changed since the last one
+    async fn record_version(
+        &self,
+        file_path: &Path,
+        content: &str,
+        source: VersionSource,
+        message: Option<&str>,
+    ) -> Result<bool> {
+        let mut backend = self.backend.write().await;
+        match backend.as_mut().ok_or_else(not_initialized)? {
+            Backend::History(store) => {
+                let path_str = file_path.to_string_lossy();
+                let recorded = store.record(&path_str, content, source)?;
+                if recorded {
+                    info!("Recorded {:?} version of {}", source, path_str);
+                }
+                Ok(recorded)
+            }
+            Backend::Git(repo) => {
+                let path = repo_path(repo, file_path)?;
+                let message = match message {
+                    Some(message) if !message.trim().is_empty() => message.to_string(),
+                    _ => default_commit_message(source, &path),
+                };
+                let commit = repo.commit_file(&path, &message, source).await?;
+                if let Some(commit) = &commit {
+                    info!("Committed {} as {}", path, commit);
+                }
+                Ok(commit.is_some())
+            }
+        }
+    }
+
+    /// Record a watched file's content as an external version. Returns its etag if it
+    /// changed since the last recorded version, which webmux's own writes never do.
+    pub async fn record_external_edit(&self, file_path: &Path) -> Result<Option<String>> {
+        let content = fs::read_to_string(file_path)
+            .with_context(|| format!("Failed to read file: {}", file_path.display()))?;
+        let recorded = self
+            .record_version(file_path, &content, VersionSource::External, None)
+            .await?;
+        Ok(recorded.then(|| history::content_hash(&content)))
+    }
+
+    /// Every tracked dotfile (existing or not) by name and resolved path, for the watcher
+    pub async fn watched_files(&self) -> Result<Vec<(String, PathBuf)>> {
+        let home_dir = home_dir()?;
+        Ok(self
+            .dotfile_names(&home_dir)
+            .await
+            .into_iter()
+            .filter_map(|(name, _)| {
+                let path = self.validate_and_resolve_path(&name).ok()?;
+                Some((name, path))
+            })
+            .collect())
+    }
+
+    /// Resolves when the tracked files changed, so the watcher can update what it watches
+    pub async fn tracked_changed(&self) {
+        self.tracked_changed.notified().await
+    }
+
+    /// Get version history for a file, oldest first
     pub async fn get_file_history(&self, path: &str) -> Result<Vec<FileVersion>> {
         let file_path = self.validate_and_resolve_path(path)?;
-        let path_str = file_path.to_string_lossy().to_string();
-        
-        let history = self.history.read().await;
-        Ok(history.get(&path_str).cloned().unwrap_or_default())
+        self.snapshot(&file_path).await;
+
+        let backend = self.backend.read().await;
+        match backend.as_ref().ok_or_else(not_initialized)? {
+            Backend::History(store) => store.versions(&file_path.to_string_lossy()),
+            Backend::Git(repo) => repo.log(&repo_path(repo, &file_path)?).await,
+        }
     }
 
-    /// Restore a file from a specific version
-    pub async fn restore_version(&self, path: &str, timestamp: DateTime<Utc>) -> Result<()> {
+    /// Restore a file to a version, identified by its timestamp or (git mode) its revision.
+    /// The old content is written like any other edit: checked for syntax unless `force`
+    /// is set, and refused with a `WriteConflict` if the file changed since `etag` was read.
+    pub async fn restore_version(
+        &self,
+        path: &str,
+        timestamp: Option<DateTime<Utc>>,
+        revision: Option<&str>,
+        force: bool,
+        etag: Option<&str>,
+    ) -> Result<()> {
         let file_path = self.validate_and_resolve_path(path)?;
-        let path_str = file_path.to_string_lossy().to_string();
         
         // Get the content to restore
-        let content_to_restore = {
-            let history = self.history.read().await;
-            let versions = history.get(&path_str)
-                .ok_or_else(|| anyhow::anyhow!("No history found for file"))?;
-            
-            let version = versions.iter()
-                .find(|v| v.timestamp == timestamp)
-                .ok_or_else(|| anyhow::anyhow!("Version not found"))?;
-            
-            version.content.clone()
+        let (content_to_restore, message) = {
+            let backend = self.backend.read().await;
+            match backend.as_ref().ok_or_else(not_initialized)? {
+                Backend::History(store) => (history_content(store, &file_path, timestamp, revision)?, None),
+                Backend::Git(repo) => {
+                    let path = repo_path(repo, &file_path)?;
+                    let revision = find_revision(repo, &path, timestamp, revision).await?;
+                    let message = format!("Restore {} to {}", path, &revision[..revision.len().min(12)]);
+                    (repo.show(&revision, &path).await?, Some(message))
+                }
+            }
         }; // Read lock is released here
         
+        if etag.is_some() {
+            self.check_etag(path, &content_to_restore, etag).await?;
+        }
+        self.check_syntax(path, &content_to_restore, force).await?;
         // Write the old version content
-        self.write_dotfile(path, &content_to_restore).await?;
+        self.write_version(path, &content_to_restore, VersionSource::Restore, message.as_deref(), None).await?;
         
         Ok(())
     }
 
+    /// Line diff between two of: the current file, a recorded version, a template, unsaved content.
+    /// Secret values in the lines are masked when `mask` is set.
+    pub async fn diff_dotfile(
+        &self,
+        path: &str,
+        from: &DiffSide,
+        to: &DiffSide,
+        context: usize,
+        mask: bool,
+    ) -> Result<FileDiff> {
+        let file_path = self.validate_and_resolve_path(path)?;
+        let old = self.side_content(&file_path, from).await?;
+        let new = self.side_content(&file_path, to).await?;
+        Ok(diff::diff_redacted(&old, &new, context, mask))
+    }
+
+    async fn side_content(&self, file_path: &Path, side: &DiffSide) -> Result<String> {
+        match side {
+            DiffSide::Current => match fs::read_to_string(file_path) {
+                Ok(content) => Ok(content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+                Err(e) => Err(e).with_context(|| format!("Failed to read file: {}", file_path.display())),
+            },
+            DiffSide::Version { timestamp, revision } => {
+                let backend = self.backend.read().await;
+                match backend.as_ref().ok_or_else(not_initialized)? {
+                    Backend::History(store) => history_content(store, file_path, *timestamp, revision.as_deref()),
+                    Backend::Git(repo) => {
+                        let path = repo_path(repo, file_path)?;
+                        let revision = find_revision(repo, &path, *timestamp, revision.as_deref()).await?;
+                        repo.show(&revision, &path).await
+                    }
+                }
+            }
+            DiffSide::Template { name, values } => template::render(&self.find_template(name)?, values),
+            DiffSide::Content { content } => Ok(content.clone()),
+        }
+    }
+
+    pub async fn git_status(&self) -> Result<GitStatus> {
+        let backend = self.backend.read().await;
+        git_repo(backend.as_ref())?.status().await
+    }
+
+    /// Uncommitted changes to one tracked file, or all of them
+    pub async fn git_diff(&self, path: Option<&str>) -> Result<String> {
+        let file_path = path.map(|path| self.validate_and_resolve_path(path)).transpose()?;
+        let backend = self.backend.read().await;
+        let repo = git_repo(backend.as_ref())?;
+        let path = file_path.map(|file_path| repo_path(repo, &file_path)).transpose()?;
+        repo.diff(path.as_deref()).await
+    }
+
+    pub async fn pull(&self) -> Result<String> {
+        let backend = self.backend.write().await;
+        let output = git_repo(backend.as_ref())?.pull().await?;
+        info!("Pulled dotfiles");
+        Ok(output)
+    }
+
+    pub async fn push(&self) -> Result<String> {
+        let backend = self.backend.read().await;
+        let output = git_repo(backend.as_ref())?.push().await?;
+        info!("Pushed dotfiles");
+        Ok(output)
+    }
+
     /// Validate and resolve file path - simplified for personal use
     fn validate_and_resolve_path(&self, path: &str) -> Result<PathBuf> {
         let home_dir = dirs::home_dir()
@@ -302,44 +880,317 @@ impl DotFilesManager {
         }
     }
 
-    /// Calculate a simple hash for version tracking
-    fn calculate_hash(&self, content: &str) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        let mut hasher = DefaultHasher::new();
-        content.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
-    }
-
-    /// Get default templates for common config files
+    /// Built-in templates for common config files, followed by the user's own
     pub fn get_templates(&self) -> Vec<DotFileTemplate> {
-        vec![
+        let mut templates = vec![
             DotFileTemplate {
                 name: "Basic .bashrc".to_string(),
                 file_type: DotFileType::Shell,
                 description: "Basic bash configuration with common aliases".to_string(),
                 content: BASHRC_TEMPLATE.to_string(),
+                target: Some(".bashrc".to_string()),
+                variables: vec![TemplateVariable::new(
+                    "history_size",
+                    VariableType::Integer { min: Some(0), max: None },
+                    "Commands kept in the shell history",
+                    "1000",
+                )],
+                builtin: true,
             },
             DotFileTemplate {
                 name: "Basic .vimrc".to_string(),
                 file_type: DotFileType::Vim,
                 description: "Basic vim configuration with sensible defaults".to_string(),
                 content: VIMRC_TEMPLATE.to_string(),
+                target: Some(".vimrc".to_string()),
+                variables: vec![TemplateVariable::new(
+                    "indent_width",
+                    VariableType::Integer { min: Some(1), max: Some(16) },
+                    "Spaces per indentation level",
+                    "4",
+                )],
+                builtin: true,
             },
             DotFileTemplate {
                 name: "Basic .gitconfig".to_string(),
                 file_type: DotFileType::Git,
                 description: "Git configuration with useful aliases".to_string(),
                 content: GITCONFIG_TEMPLATE.to_string(),
+                target: Some(".gitconfig".to_string()),
+                variables: vec![
+                    TemplateVariable {
+                        name: "user_name".to_string(),
+                        kind: VariableType::String,
+                        description: Some("Name recorded in your commits".to_string()),
+                        default: None,
+                    },
+                    TemplateVariable {
+                        name: "user_email".to_string(),
+                        kind: VariableType::Email,
+                        description: Some("Email recorded in your commits".to_string()),
+                        default: None,
+                    },
+                    TemplateVariable::new("editor", VariableType::String, "Editor for commit messages", "vim"),
+                ],
+                builtin: true,
             },
             DotFileTemplate {
                 name: "Basic .tmux.conf".to_string(),
                 file_type: DotFileType::Tmux,
                 description: "Tmux configuration with improved defaults".to_string(),
                 content: TMUX_CONF_TEMPLATE.to_string(),
+                target: Some(".tmux.conf".to_string()),
+                variables: vec![
+                    TemplateVariable::new("prefix", VariableType::Key, "Prefix key", "C-a"),
+                    TemplateVariable::new("mouse", on_off(), "Mouse support", "on"),
+                    TemplateVariable::new(
+                        "history_limit",
+                        VariableType::Integer { min: Some(0), max: None },
+                        "Scrollback lines kept per pane",
+                        "10000",
+                    ),
+                    TemplateVariable::new(
+                        "mode_keys",
+                        VariableType::Choice { options: vec!["vi".to_string(), "emacs".to_string()] },
+                        "Key bindings in copy mode",
+                        "vi",
+                    ),
+                ],
+                builtin: true,
             },
-        ]
+        ];
+
+        // Read on every call so new user templates show up without a restart
+        let templates_dir = self.settings.read().unwrap_or_else(|e| e.into_inner()).templates_dir.clone();
+        for user_template in templates_dir.map(|dir| template::load_dir(&dir)).unwrap_or_default() {
+            if templates.iter().any(|template| template.name == user_template.name) {
+                warn!("Skipping user template {}: the name is already taken", user_template.name);
+                continue;
+            }
+            templates.push(user_template);
+        }
+        templates
+    }
+
+    fn find_template(&self, name: &str) -> Result<DotFileTemplate> {
+        self.get_templates()
+            .into_iter()
+            .find(|template| template.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Template not found: {}", name))
+    }
+
+    /// Render a template and write it to `path` (the template's target by default), after
+    /// copying the file's previous content to the backups directory. With `dry_run` only
+    /// the rendered content and its diff against the file are returned.
+    pub async fn apply_template(
+        &self,
+        name: &str,
+        path: Option<&str>,
+        values: &HashMap<String, serde_json::Value>,
+        dry_run: bool,
+        force: bool,
+    ) -> Result<TemplateApplication> {
+        let template = self.find_template(name)?;
+        let path = match path.or(template.target.as_deref()) {
+            Some(path) => path.to_string(),
+            None => anyhow::bail!("Template {} has no default path; choose one", name),
+        };
+        let content = template::render(&template, values)?;
+
+        let file_path = self.validate_and_resolve_path(&path)?;
+        let current = self.side_content(&file_path, &DiffSide::Current).await?;
+        let mut application = TemplateApplication {
+            diff: diff::diff_redacted(&current, &content, diff::DEFAULT_CONTEXT, self.masks_secrets()),
+            path,
+            content,
+            backup: None,
+            written: false,
+        };
+        if dry_run || application.content == current {
+            return Ok(application);
+        }
+
+        self.check_syntax(&application.path, &application.content, force).await?;
+        if file_path.exists() {
+            application.backup = Some(self.back_up(&file_path, &current)?.to_string_lossy().to_string());
+        }
+        let message = format!("Apply template {}", name);
+        self.write_version(&application.path, &application.content, VersionSource::Webmux, Some(&message), None)
+            .await?;
+        application.written = true;
+        Ok(application)
+    }
+
+    // Copy to `<backups>/<timestamp>/<path>`, keeping the path relative to $HOME when it is inside
+    fn back_up(&self, file_path: &Path, content: &str) -> Result<PathBuf> {
+        let backups_dir = self
+            .settings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .backups_dir
+            .clone()
+            .ok_or_else(not_initialized)?;
+        let home_dir = home_dir()?.canonicalize()?;
+        let relative = file_path
+            .strip_prefix(&home_dir)
+            .unwrap_or_else(|_| file_path.strip_prefix("/").unwrap_or(file_path));
+
+        let backup = backups_dir
+            .join(Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string())
+            .join(relative);
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&backup, content).with_context(|| format!("Failed to write backup {}", backup.display()))?;
+        info!("Backed up {} to {}", file_path.display(), backup.display());
+        Ok(backup)
+    }
+
+    /// Current content and history of every tracked dotfile that exists, for a bundle.
+    /// Contents are in the same order as the manifest's files.
+    pub async fn export_bundle(&self) -> Result<(Manifest, Vec<String>)> {
+        let home_dir = home_dir()?;
+        let mut files = Vec::new();
+        let mut contents = Vec::new();
+        for (file_name, _) in self.dotfile_names(&home_dir).await {
+            let file_path = home_dir.join(&file_name);
+            if !file_path.is_file() {
+                continue;
+            }
+            let content = match fs::read_to_string(&file_path) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Leaving {} out of the bundle: {}", file_name, e);
+                    continue;
+                }
+            };
+            let versions = match self.get_file_history(&file_name).await {
+                Ok(versions) => versions.into_iter().map(Into::into).collect(),
+                Err(e) => {
+                    warn!("Exporting {} without its history: {}", file_name, e);
+                    Vec::new()
+                }
+            };
+            files.push(ManifestFile {
+                mode: fs::metadata(&file_path)?.permissions().mode() & 0o777,
+                size: content.len() as u64,
+                hash: history::content_hash(&content),
+                path: file_name,
+                versions,
+            });
+            contents.push(content);
+        }
+        info!("Exporting {} dotfiles", files.len());
+        Ok((bundle::new_manifest(files), contents))
+    }
+
+    /// Read an uploaded bundle and keep it for `apply_import`, returning how each of its
+    /// files compares with the one on disk
+    pub async fn stage_import(&self, data: &[u8]) -> Result<ImportPreview> {
+        let bundle = bundle::read_bundle(data)?;
+        let mut files = Vec::new();
+        for (incoming, content) in bundle.files() {
+            let file_path = self.validate_and_resolve_path(&incoming.path)?;
+            let current = match fs::read_to_string(&file_path) {
+                Ok(current) => Some(current),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e).with_context(|| format!("Failed to read file: {}", incoming.path)),
+            };
+            let current_hash = current.as_deref().map(history::content_hash);
+            let local_versions = match current {
+                Some(_) => self.get_file_history(&incoming.path).await.unwrap_or_else(|e| {
+                    warn!("Comparing {} without its history: {}", incoming.path, e);
+                    Vec::new()
+                }),
+                None => Vec::new(),
+            };
+
+            let status = bundle::classify(incoming, current_hash.as_deref(), &local_versions);
+            files.push(ImportFile {
+                path: incoming.path.clone(),
+                status,
+                size: incoming.size,
+                hash: incoming.hash.clone(),
+                diff: (status != bundle::ImportStatus::Identical).then(|| {
+                    let current = current.as_deref().unwrap_or("");
+                    diff::diff_redacted(current, content, diff::DEFAULT_CONTEXT, self.masks_secrets())
+                }),
+                current_hash,
+            });
+        }
+
+        let import_id = uuid::Uuid::new_v4().to_string();
+        let preview = ImportPreview {
+            import_id: import_id.clone(),
+            exported_at: bundle.manifest.exported_at,
+            hostname: bundle.manifest.hostname.clone(),
+            files,
+        };
+        let mut imports = self.imports.write().await;
+        if imports.len() >= bundle::MAX_STAGED_IMPORTS {
+            imports.remove(0);
+        }
+        imports.push((import_id, bundle));
+        info!("Staged import {} ({} files)", preview.import_id, preview.files.len());
+        Ok(preview)
+    }
+
+    /// Write the chosen files of a staged import, backing up what they replace
+    pub async fn apply_import(&self, import_id: &str, paths: &[String], force: bool) -> Result<Vec<ImportResult>> {
+        let imports = self.imports.read().await;
+        let (_, bundle) = imports
+            .iter()
+            .find(|(id, _)| id == import_id)
+            .ok_or_else(|| anyhow::anyhow!("Import not found: {}", import_id))?;
+
+        let mut results = Vec::new();
+        for path in paths {
+            let result = match bundle.files().find(|(file, _)| file.path == *path) {
+                Some((file, content)) => self.import_file(file, content, force).await,
+                None => Err(anyhow::anyhow!("{} is not in this import", path)),
+            };
+            results.push(match result {
+                Ok(backup) => ImportResult {
+                    path: path.clone(),
+                    success: true,
+                    backup: backup.map(|backup| backup.to_string_lossy().to_string()),
+                    error: None,
+                },
+                Err(e) => ImportResult {
+                    path: path.clone(),
+                    success: false,
+                    backup: None,
+                    error: Some(format!("{:#}", e)),
+                },
+            });
+        }
+        Ok(results)
+    }
+
+    /// Drop a staged import; returns whether it existed
+    pub async fn discard_import(&self, import_id: &str) -> bool {
+        let mut imports = self.imports.write().await;
+        let before = imports.len();
+        imports.retain(|(id, _)| id != import_id);
+        imports.len() != before
+    }
+
+    // Returns the backup of the replaced content, if there was any
+    async fn import_file(&self, file: &ManifestFile, content: &str, force: bool) -> Result<Option<PathBuf>> {
+        let file_path = self.validate_and_resolve_path(&file.path)?;
+        let current = self.side_content(&file_path, &DiffSide::Current).await?;
+        let exists = file_path.exists();
+        if exists && current == content {
+            return Ok(None);
+        }
+
+        self.check_syntax(&file.path, content, force).await?;
+        let backup = exists.then(|| self.back_up(&file_path, &current)).transpose()?;
+        let message = format!("Import {}", file.path);
+        // A new file gets the bundle's mode; an existing one keeps its own
+        self.write_version(&file.path, content, VersionSource::Webmux, Some(&message), Some(file.mode & 0o777))
+            .await?;
+        Ok(backup)
     }
 }
 
@@ -348,7 +1199,110 @@ pub struct DotFileTemplate {
     pub name: String,
     pub file_type: DotFileType,
     pub description: String,
+    // With `{{variable}}` placeholders
     pub content: String,
+    // Path the template is meant for, relative to $HOME
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    // Shipped with webmux rather than loaded from the templates directory
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+fn on_off() -> VariableType {
+    VariableType::Choice {
+        options: vec!["on".to_string(), "off".to_string()],
+    }
+}
+
+fn home_dir() -> Result<PathBuf> {
+    dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))
+}
+
+/// Commit a file's current state in git mode, if it exists
+async fn track_in_git(repo: &GitRepo, home_dir: &Path, file_name: &str) {
+    if !home_dir.join(file_name).is_file() {
+        return;
+    }
+    let message = format!("Track {}", file_name);
+    if let Err(e) = repo.commit_file(file_name, &message, VersionSource::External).await {
+        warn!("Failed to track {}: {}", file_name, e);
+    }
+}
+
+// Secrets in a file for the listing; large or unreadable files aren't scanned
+fn scan_file(file_path: &Path, size: u64) -> Vec<SecretFinding> {
+    if size > secrets::MAX_SCAN_BYTES {
+        return Vec::new();
+    }
+    fs::read_to_string(file_path)
+        .map(|content| secrets::scan(&content))
+        .unwrap_or_default()
+}
+
+fn not_initialized() -> anyhow::Error {
+    anyhow::anyhow!("Dotfiles manager is not initialized")
+}
+
+fn git_repo(backend: Option<&Backend>) -> Result<&GitRepo> {
+    match backend.ok_or_else(not_initialized)? {
+        Backend::Git(repo) => Ok(repo),
+        Backend::History(_) => anyhow::bail!("Dotfiles are not in git mode (set dotfiles.mode = \"git\")"),
+    }
+}
+
+fn history_content(
+    store: &VersionStore,
+    file_path: &Path,
+    timestamp: Option<DateTime<Utc>>,
+    revision: Option<&str>,
+) -> Result<String> {
+    if revision.is_some() {
+        anyhow::bail!("Revisions are only available in git mode");
+    }
+    let timestamp = timestamp.ok_or_else(|| anyhow::anyhow!("A version timestamp is required"))?;
+    store
+        .content_at(&file_path.to_string_lossy(), timestamp)?
+        .ok_or_else(|| anyhow::anyhow!("Version not found"))
+}
+
+/// Commit of a version given by revision, or by the commit time `get_file_history` reported
+async fn find_revision(
+    repo: &GitRepo,
+    path: &str,
+    timestamp: Option<DateTime<Utc>>,
+    revision: Option<&str>,
+) -> Result<String> {
+    match (revision, timestamp) {
+        (Some(revision), _) => repo.resolve(revision).await,
+        (None, Some(timestamp)) => repo
+            .log(path)
+            .await?
+            .into_iter()
+            .rev()
+            .find(|version| version.timestamp == timestamp)
+            .and_then(|version| version.revision)
+            .ok_or_else(|| anyhow::anyhow!("Version not found")),
+        (None, None) => anyhow::bail!("A version timestamp or revision is required"),
+    }
+}
+
+/// Path of a file relative to the repository's work tree (the home directory)
+fn repo_path(repo: &GitRepo, file_path: &Path) -> Result<String> {
+    let relative = file_path.strip_prefix(repo.work_tree()).map_err(|_| {
+        anyhow::anyhow!("{} is outside the home directory and cannot be tracked in git", file_path.display())
+    })?;
+    Ok(relative.to_string_lossy().to_string())
+}
+
+fn default_commit_message(source: VersionSource, path: &str) -> String {
+    match source {
+        VersionSource::Webmux => format!("Update {}", path),
+        VersionSource::Restore => format!("Restore {}", path),
+        VersionSource::External => format!("Record external changes to {}", path),
+    }
 }
 
 lazy_static::lazy_static! {
@@ -0,0 +1,597 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Where the structured editor reads and writes
+pub const SSH_CONFIG_PATH: &str = "~/.ssh/config";
+// Indentation for options added to a block that has none to copy
+const DEFAULT_INDENT: &str = "    ";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshOption {
+    pub keyword: String,
+    pub value: String,
+}
+
+/// A `Host` block. The common options have their own fields; everything else is in
+/// `options`, in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshHost {
+    pub patterns: Vec<String>,
+    // The comment lines directly above the `Host` line, without `#`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub identity_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<String>,
+    #[serde(default)]
+    pub options: Vec<SshOption>,
+}
+
+impl SshHost {
+    // Every option as it goes in the file: the common ones first
+    fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        let mut push = |keyword: &str, value: Option<String>| {
+            if let Some(value) = value {
+                entries.push((keyword.to_string(), value));
+            }
+        };
+        push("HostName", self.host_name.clone());
+        push("User", self.user.clone());
+        push("Port", self.port.map(|port| port.to_string()));
+        for file in &self.identity_files {
+            push("IdentityFile", Some(file.clone()));
+        }
+        push("ProxyJump", self.proxy_jump.clone());
+        for option in &self.options {
+            push(&option.keyword, Some(option.value.clone()));
+        }
+        entries
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.patterns.is_empty() {
+            anyhow::bail!("A host needs at least one pattern");
+        }
+        for pattern in &self.patterns {
+            if pattern.is_empty() || pattern.contains(|c: char| c.is_whitespace() || c == '#') {
+                anyhow::bail!("Invalid host pattern: {:?}", pattern);
+            }
+        }
+        if self.comment.as_deref().is_some_and(|comment| comment.contains('\r')) {
+            anyhow::bail!("Invalid comment");
+        }
+        for (keyword, value) in self.entries() {
+            if keyword.is_empty() || !keyword.chars().all(|c| c.is_ascii_alphanumeric()) {
+                anyhow::bail!("Invalid option keyword: {:?}", keyword);
+            }
+            // A second block header would split this one
+            if keyword.eq_ignore_ascii_case("host") || keyword.eq_ignore_ascii_case("match") {
+                anyhow::bail!("{} can't be used as an option", keyword);
+            }
+            if value.trim().is_empty() || value.contains(['\n', '\r']) {
+                anyhow::bail!("Invalid value for {}: {:?}", keyword, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The parsed file as sent to clients; hosts are addressed by their index in `hosts`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshConfigView {
+    pub path: String,
+    // Options before the first block, which apply to every host
+    pub global: Vec<SshOption>,
+    pub hosts: Vec<SshHost>,
+    // Of the content on disk, for edits; `None` when the file doesn't exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+}
+
+/// A change to one `Host` block
+#[derive(Debug, Clone)]
+pub enum SshHostEdit {
+    // Before the host at `before`; by default at the end, but ahead of a final `Host *`
+    Add { host: SshHost, before: Option<usize> },
+    Update { index: usize, host: SshHost },
+    Remove { index: usize },
+}
+
+impl SshHostEdit {
+    /// Commit message for the edit in git mode
+    pub fn describe(&self, config: &SshConfig) -> String {
+        let patterns = |host: &SshHost| host.patterns.join(" ");
+        match self {
+            SshHostEdit::Add { host, .. } => format!("Add SSH host {}", patterns(host)),
+            SshHostEdit::Update { index, host } => {
+                let old = config.hosts().into_iter().nth(*index).map(|old| patterns(&old));
+                match old {
+                    Some(old) if old != patterns(host) => {
+                        format!("Update SSH host {} (was {})", patterns(host), old)
+                    }
+                    _ => format!("Update SSH host {}", patterns(host)),
+                }
+            }
+            SshHostEdit::Remove { index } => match config.hosts().into_iter().nth(*index) {
+                Some(host) => format!("Remove SSH host {}", patterns(&host)),
+                None => "Remove SSH host".to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    Host,
+    Match,
+}
+
+#[derive(Debug, Clone)]
+enum LineKind {
+    Blank,
+    Comment,
+    Header(BlockKind),
+    Option(String),
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    // As in the file, without the newline
+    raw: String,
+    kind: LineKind,
+    // Byte offset of the header's arguments or the option's value
+    value_start: usize,
+}
+
+impl Line {
+    fn new(raw: String) -> Self {
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            let kind = if trimmed.is_empty() { LineKind::Blank } else { LineKind::Comment };
+            return Self {
+                value_start: raw.len(),
+                raw,
+                kind,
+            };
+        }
+
+        // `Keyword value`, `Keyword=value`, `Keyword = value`
+        let start = raw.len() - raw.trim_start().len();
+        let keyword_len = raw[start..]
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(raw.len() - start);
+        let keyword = &raw[start..start + keyword_len];
+        let rest = raw[start + keyword_len..].trim_start();
+        let value_start = raw.len() - rest.strip_prefix('=').unwrap_or(rest).trim_start().len();
+        let kind = if keyword.eq_ignore_ascii_case("host") {
+            LineKind::Header(BlockKind::Host)
+        } else if keyword.eq_ignore_ascii_case("match") {
+            LineKind::Header(BlockKind::Match)
+        } else {
+            LineKind::Option(keyword.to_string())
+        };
+        Self { raw, kind, value_start }
+    }
+
+    fn blank() -> Self {
+        Self::new(String::new())
+    }
+
+    fn indent(&self) -> &str {
+        &self.raw[..self.raw.len() - self.raw.trim_start().len()]
+    }
+
+    fn value(&self) -> &str {
+        self.raw[self.value_start..].trim_end()
+    }
+
+    // Same line with another value, keeping the keyword as it was written
+    fn with_value(&self, value: &str) -> Self {
+        let prefix = &self.raw[..self.value_start];
+        let separator = if prefix.ends_with(|c: char| c.is_whitespace() || c == '=') { "" } else { " " };
+        Self::new(format!("{}{}{}", prefix, separator, value))
+    }
+}
+
+// A `Host` or `Match` line with the comments directly above it and every line up to
+// the next block
+#[derive(Debug, Clone)]
+struct Block {
+    kind: BlockKind,
+    lines: Vec<Line>,
+    // Index of the header in `lines`
+    header: usize,
+}
+
+impl Block {
+    fn host(&self) -> SshHost {
+        let header = &self.lines[self.header];
+        let mut host = SshHost {
+            patterns: header.value().split_whitespace().map(String::from).collect(),
+            comment: self.comment(),
+            ..SshHost::default()
+        };
+        let mut seen_port = false;
+        for line in &self.lines[self.header + 1..] {
+            let LineKind::Option(keyword) = &line.kind else {
+                continue;
+            };
+            let value = line.value().to_string();
+            // ssh uses the first value of single-valued options; later ones stay generic
+            match keyword.to_ascii_lowercase().as_str() {
+                "hostname" if host.host_name.is_none() => host.host_name = Some(value),
+                "user" if host.user.is_none() => host.user = Some(value),
+                "port" if !seen_port && value.parse::<u16>().is_ok() => {
+                    seen_port = true;
+                    host.port = value.parse().ok();
+                }
+                "identityfile" => host.identity_files.push(value),
+                "proxyjump" if host.proxy_jump.is_none() => host.proxy_jump = Some(value),
+                other => {
+                    seen_port |= other == "port";
+                    host.options.push(SshOption {
+                        keyword: keyword.clone(),
+                        value,
+                    });
+                }
+            }
+        }
+        host
+    }
+
+    fn comment(&self) -> Option<String> {
+        if self.header == 0 {
+            return None;
+        }
+        let lines: Vec<&str> = self.lines[..self.header]
+            .iter()
+            .map(|line| {
+                let text = line.raw.trim().trim_start_matches('#');
+                text.strip_prefix(' ').unwrap_or(text)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    fn new_host(host: &SshHost, indent: &str) -> Self {
+        let mut lines = comment_lines(host.comment.as_deref(), "");
+        let header = lines.len();
+        lines.push(Line::new(format!("Host {}", host.patterns.join(" "))));
+        for (keyword, value) in host.entries() {
+            lines.push(Line::new(format!("{}{} {}", indent, keyword, value)));
+        }
+        Self {
+            kind: BlockKind::Host,
+            lines,
+            header,
+        }
+    }
+
+    /// Make the block match `host`, leaving lines that already do untouched
+    fn update(&mut self, host: &SshHost) {
+        let header = &self.lines[self.header];
+        if header.value().split_whitespace().ne(host.patterns.iter().map(String::as_str)) {
+            self.lines[self.header] = header.with_value(&host.patterns.join(" "));
+        }
+
+        if self.comment() != host.comment {
+            let indent = self.lines[self.header].indent().to_string();
+            let comment = comment_lines(host.comment.as_deref(), &indent);
+            let header = comment.len();
+            self.lines.splice(..self.header, comment);
+            self.header = header;
+        }
+
+        // Existing options take the new values in order; ones no longer wanted go
+        let mut wanted: Vec<Option<(String, String)>> = host.entries().into_iter().map(Some).collect();
+        let mut body = Vec::new();
+        for line in self.lines.drain(self.header + 1..) {
+            let LineKind::Option(keyword) = &line.kind else {
+                body.push(line);
+                continue;
+            };
+            let slot = wanted
+                .iter_mut()
+                .find(|entry| entry.as_ref().is_some_and(|(k, _)| k.eq_ignore_ascii_case(keyword)));
+            if let Some((_, value)) = slot.and_then(Option::take) {
+                body.push(if line.value() == value { line } else { line.with_value(&value) });
+            }
+        }
+
+        // New options go after the last existing one, indented like it
+        let last_option = body.iter().rposition(|line| matches!(line.kind, LineKind::Option(_)));
+        let indent = last_option
+            .map(|i| body[i].indent().to_string())
+            .unwrap_or_else(|| DEFAULT_INDENT.to_string());
+        let added = wanted
+            .into_iter()
+            .flatten()
+            .map(|(keyword, value)| Line::new(format!("{}{} {}", indent, keyword, value)));
+        let at = last_option.map_or(0, |i| i + 1);
+        body.splice(at..at, added);
+        self.lines.extend(body);
+    }
+
+    fn is_catch_all(&self) -> bool {
+        self.kind == BlockKind::Host && self.lines[self.header].value() == "*"
+    }
+
+    // Indent of the block's options, if it has any
+    fn option_indent(&self) -> Option<&str> {
+        self.lines[self.header + 1..]
+            .iter()
+            .find(|line| matches!(line.kind, LineKind::Option(_)))
+            .map(Line::indent)
+    }
+}
+
+fn comment_lines(comment: Option<&str>, indent: &str) -> Vec<Line> {
+    comment
+        .map(|comment| {
+            comment
+                .split('\n')
+                .map(|line| match line {
+                    "" => Line::new(format!("{}#", indent)),
+                    line => Line::new(format!("{}# {}", indent, line)),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// An ssh_config file that can be edited by block and written back with every line
+/// it didn't touch (comments, blank lines, `Match` blocks) exactly as it was
+#[derive(Debug, Clone)]
+pub struct SshConfig {
+    // Lines before the first block
+    preamble: Vec<Line>,
+    blocks: Vec<Block>,
+    trailing_newline: bool,
+}
+
+impl SshConfig {
+    pub fn parse(content: &str) -> Self {
+        let mut config = Self {
+            preamble: Vec::new(),
+            blocks: Vec::new(),
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+        };
+        let body = content.strip_suffix('\n').unwrap_or(content);
+        if content.is_empty() {
+            return config;
+        }
+
+        for raw in body.split('\n') {
+            let line = Line::new(raw.to_string());
+            let LineKind::Header(kind) = line.kind else {
+                match config.blocks.last_mut() {
+                    Some(block) => block.lines.push(line),
+                    None => config.preamble.push(line),
+                }
+                continue;
+            };
+            // Comments directly above a header describe its block
+            let (previous, first_body_line) = match config.blocks.last_mut() {
+                Some(block) => (&mut block.lines, block.header + 1),
+                None => (&mut config.preamble, 0),
+            };
+            let comments = previous[first_body_line..]
+                .iter()
+                .rev()
+                .take_while(|line| matches!(line.kind, LineKind::Comment))
+                .count();
+            let mut lines = previous.split_off(previous.len() - comments);
+            let header = lines.len();
+            lines.push(line);
+            config.blocks.push(Block { kind, lines, header });
+        }
+        config
+    }
+
+    pub fn render(&self) -> String {
+        let lines: Vec<&str> = self
+            .preamble
+            .iter()
+            .chain(self.blocks.iter().flat_map(|block| &block.lines))
+            .map(|line| line.raw.as_str())
+            .collect();
+        let mut content = lines.join("\n");
+        if self.trailing_newline && !lines.is_empty() {
+            content.push('\n');
+        }
+        content
+    }
+
+    pub fn global(&self) -> Vec<SshOption> {
+        self.preamble
+            .iter()
+            .filter_map(|line| match &line.kind {
+                LineKind::Option(keyword) => Some(SshOption {
+                    keyword: keyword.clone(),
+                    value: line.value().to_string(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn hosts(&self) -> Vec<SshHost> {
+        self.host_blocks().map(|i| self.blocks[i].host()).collect()
+    }
+
+    pub fn apply(&mut self, edit: &SshHostEdit) -> Result<()> {
+        match edit {
+            SshHostEdit::Add { host, before } => {
+                host.check()?;
+                let at = match before {
+                    Some(before) => self.block_index(*before)?,
+                    None => match self.blocks.last() {
+                        Some(last) if last.is_catch_all() => self.blocks.len() - 1,
+                        _ => self.blocks.len(),
+                    },
+                };
+                // Indented like the nearest block with options
+                let indent = self.blocks[..at]
+                    .iter()
+                    .rev()
+                    .chain(&self.blocks[at..])
+                    .find_map(Block::option_indent)
+                    .unwrap_or(DEFAULT_INDENT)
+                    .to_string();
+                let mut block = Block::new_host(host, &indent);
+                if at < self.blocks.len() {
+                    block.lines.push(Line::blank());
+                } else {
+                    // Keep a blank line between the previous block and this one
+                    let previous = match self.blocks.last_mut() {
+                        Some(last) => &mut last.lines,
+                        None => &mut self.preamble,
+                    };
+                    if previous.last().is_some_and(|line| !matches!(line.kind, LineKind::Blank)) {
+                        previous.push(Line::blank());
+                    }
+                }
+                self.blocks.insert(at, block);
+            }
+            SshHostEdit::Update { index, host } => {
+                host.check()?;
+                let at = self.block_index(*index)?;
+                self.blocks[at].update(host);
+            }
+            SshHostEdit::Remove { index } => {
+                let at = self.block_index(*index)?;
+                self.blocks.remove(at);
+            }
+        }
+        Ok(())
+    }
+
+    fn host_blocks(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.blocks.len()).filter(|&i| self.blocks[i].kind == BlockKind::Host)
+    }
+
+    fn block_index(&self, index: usize) -> Result<usize> {
+        self.host_blocks()
+            .nth(index)
+            .ok_or_else(|| anyhow::anyhow!("No SSH host at index {}", index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# Global settings
+AddKeysToAgent yes
+
+# Work server
+Host work
+  HostName work.example.com
+  User alice
+
+Match host *.internal exec \"true\"
+  ForwardAgent no
+
+Host *
+  ServerAliveInterval 30
+";
+
+    fn apply(content: &str, edit: SshHostEdit) -> String {
+        let mut config = SshConfig::parse(content);
+        config.apply(&edit).unwrap();
+        config.render()
+    }
+
+    fn round_trips(content: &str) {
+        assert_eq!(SshConfig::parse(content).render(), content);
+    }
+
+    #[test]
+    fn round_trips_comments_and_match_blocks() {
+        round_trips(CONFIG);
+        round_trips("Host a\n\tUser x");
+        round_trips("");
+    }
+
+    #[test]
+    fn round_trips_crlf() {
+        let content = CONFIG.replace('\n', "\r\n");
+        round_trips(&content);
+        let hosts = SshConfig::parse(&content).hosts();
+        assert_eq!(hosts[0].host_name.as_deref(), Some("work.example.com"));
+    }
+
+    #[test]
+    fn reads_equals_forms() {
+        let content = "Host=a b\n  User=bob\n  Port = 2222\n  HostName  =  a.example.com\n";
+        round_trips(content);
+        let host = &SshConfig::parse(content).hosts()[0];
+        assert_eq!(host.patterns, ["a", "b"]);
+        assert_eq!(host.user.as_deref(), Some("bob"));
+        assert_eq!(host.port, Some(2222));
+        assert_eq!(host.host_name.as_deref(), Some("a.example.com"));
+    }
+
+    #[test]
+    fn reads_preamble_options_and_block_comments() {
+        let config = SshConfig::parse(CONFIG);
+        assert_eq!(
+            config.global(),
+            [SshOption {
+                keyword: "AddKeysToAgent".to_string(),
+                value: "yes".to_string(),
+            }]
+        );
+        let hosts = config.hosts();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].comment.as_deref(), Some("Work server"));
+        assert_eq!(hosts[1].patterns, ["*"]);
+    }
+
+    #[test]
+    fn update_changes_only_the_edited_lines() {
+        let mut host = SshConfig::parse(CONFIG).hosts()[0].clone();
+        host.user = Some("bob".to_string());
+        host.port = Some(2222);
+        let updated = apply(CONFIG, SshHostEdit::Update { index: 0, host });
+        assert_eq!(
+            updated,
+            CONFIG.replace("  User alice\n", "  User bob\n  Port 2222\n")
+        );
+    }
+
+    #[test]
+    fn add_goes_before_the_catch_all_with_the_file_indent() {
+        let host = SshHost {
+            patterns: vec!["new".to_string()],
+            host_name: Some("new.example.com".to_string()),
+            ..SshHost::default()
+        };
+        let added = apply(CONFIG, SshHostEdit::Add { host, before: None });
+        assert_eq!(
+            added,
+            CONFIG.replace("Host *\n", "Host new\n  HostName new.example.com\n\nHost *\n")
+        );
+    }
+
+    #[test]
+    fn remove_drops_the_block_and_its_comment() {
+        let removed = apply(CONFIG, SshHostEdit::Remove { index: 0 });
+        assert_eq!(
+            removed,
+            CONFIG.replace("# Work server\nHost work\n  HostName work.example.com\n  User alice\n\n", "")
+        );
+    }
+}
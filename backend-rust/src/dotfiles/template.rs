@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+use tracing::warn;
+
+use super::{diff::FileDiff, DotFileTemplate};
+
+/// What a template variable accepts
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum VariableType {
+    String,
+    Email,
+    Integer {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<i64>,
+    },
+    // Rendered as `true` / `false`
+    Boolean,
+    Choice { options: Vec<String> },
+    // A tmux key such as `C-a` or `M-Space`
+    Key,
+}
+
+/// A `{{name}}` placeholder in a template's content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: VariableType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Used when no value is given; a value is required without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+impl TemplateVariable {
+    pub fn new(name: &str, kind: VariableType, description: &str, default: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            description: Some(description.to_string()),
+            default: Some(default.to_string()),
+        }
+    }
+
+    fn check(&self, value: &str) -> Result<()> {
+        if value.contains(['\n', '\r']) {
+            anyhow::bail!("{} must be a single line", self.name);
+        }
+        match &self.kind {
+            VariableType::String => {}
+            VariableType::Email => {
+                let valid = value
+                    .split_once('@')
+                    .is_some_and(|(user, domain)| !user.is_empty() && !domain.is_empty() && !domain.contains('@'))
+                    && !value.contains(char::is_whitespace);
+                if !valid {
+                    anyhow::bail!("{} must be an email address", self.name);
+                }
+            }
+            VariableType::Integer { min, max } => {
+                let number: i64 = value
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("{} must be a whole number", self.name))?;
+                if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                    anyhow::bail!(
+                        "{} must be between {} and {}",
+                        self.name,
+                        min.map_or("-∞".to_string(), |min| min.to_string()),
+                        max.map_or("∞".to_string(), |max| max.to_string())
+                    );
+                }
+            }
+            VariableType::Boolean => {
+                if value != "true" && value != "false" {
+                    anyhow::bail!("{} must be true or false", self.name);
+                }
+            }
+            VariableType::Choice { options } => {
+                if !options.iter().any(|option| option == value) {
+                    anyhow::bail!("{} must be one of: {}", self.name, options.join(", "));
+                }
+            }
+            VariableType::Key => {
+                let mut key = value;
+                while let Some(rest) = ["C-", "M-", "S-"].iter().find_map(|m| key.strip_prefix(m)) {
+                    key = rest;
+                }
+                if key.is_empty() || value.contains(char::is_whitespace) {
+                    anyhow::bail!("{} must be a tmux key such as C-a", self.name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of `ApplyDotfileTemplate`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateApplication {
+    pub path: String,
+    // The rendered template
+    pub content: String,
+    // From the file as it was to the rendered template
+    pub diff: FileDiff,
+    // Copy of the previous content, when it was overwritten
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    pub written: bool,
+}
+
+/// Fill in a template's placeholders from `values`, falling back to each variable's default
+pub fn render(template: &DotFileTemplate, values: &HashMap<String, serde_json::Value>) -> Result<String> {
+    if let Some(unknown) = values
+        .keys()
+        .find(|name| !template.variables.iter().any(|variable| variable.name == **name))
+    {
+        anyhow::bail!("Template {} has no variable {}", template.name, unknown);
+    }
+
+    let mut resolved = HashMap::new();
+    for variable in &template.variables {
+        let value = match values.get(&variable.name) {
+            Some(serde_json::Value::String(value)) => value.clone(),
+            Some(serde_json::Value::Bool(value)) => value.to_string(),
+            Some(serde_json::Value::Number(value)) => value.to_string(),
+            Some(_) => anyhow::bail!("{} must be a string, number or boolean", variable.name),
+            None => variable
+                .default
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("A value for {} is required", variable.name))?,
+        };
+        variable.check(&value)?;
+        resolved.insert(variable.name.as_str(), value);
+    }
+
+    Ok(substitute(&template.content, |name| resolved.get(name).cloned()))
+}
+
+/// Replace each `{{name}}` that `lookup` knows; anything else is left as written
+fn substitute(content: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}").and_then(|end| Some((lookup(after[..end].trim())?, end))) {
+            Some((value, end)) => {
+                result.push_str(&value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                result.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Reject templates whose variables or placeholders don't add up
+pub fn check(template: &DotFileTemplate) -> Result<()> {
+    for (i, variable) in template.variables.iter().enumerate() {
+        if variable.name.is_empty()
+            || !variable.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            anyhow::bail!("Invalid variable name: {:?}", variable.name);
+        }
+        if template.variables[..i].iter().any(|other| other.name == variable.name) {
+            anyhow::bail!("Variable {} is declared twice", variable.name);
+        }
+        if let VariableType::Choice { options } = &variable.kind {
+            if options.is_empty() {
+                anyhow::bail!("Variable {} has no options", variable.name);
+            }
+        }
+        if let Some(default) = &variable.default {
+            variable.check(default).context("Invalid default")?;
+        }
+    }
+
+    // Placeholders that look like variables must be declared
+    let mut undeclared = None;
+    substitute(&template.content, |name| {
+        let looks_like_variable = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if looks_like_variable && !template.variables.iter().any(|variable| variable.name == name) {
+            undeclared.get_or_insert_with(|| name.to_string());
+        }
+        None
+    });
+    if let Some(name) = undeclared {
+        anyhow::bail!("Placeholder {{{{{}}}}} has no variable", name);
+    }
+    Ok(())
+}
+
+/// User templates: one TOML file per template in `dir`. Invalid ones are skipped with a warning.
+pub fn load_dir(dir: &Path) -> Vec<DotFileTemplate> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    let mut templates = Vec::new();
+    for path in paths {
+        let loaded = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(toml::from_str::<DotFileTemplate>(&content)?))
+            .and_then(|template| check(&template).map(|_| template));
+        match loaded {
+            Ok(mut template) => {
+                template.builtin = false;
+                templates.push(template);
+            }
+            Err(e) => warn!("Skipping template {}: {:#}", path.display(), e),
+        }
+    }
+    templates
+}
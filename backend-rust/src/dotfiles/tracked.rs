@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use super::history::write_atomic;
+
+// Globs may only reach into this directory (and not all of it recursively), so a stray
+// pattern can't sweep up all of $HOME
+const GLOB_ROOT: &str = ".config/";
+// Files a single glob may match
+const MAX_GLOB_MATCHES: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedPattern {
+    // Relative to the home directory
+    pub pattern: String,
+    // Files it currently matches, relative to the home directory
+    pub matches: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    patterns: Vec<String>,
+}
+
+/// Dotfiles the user asked to track on top of the built-in list: paths and glob
+/// patterns relative to $HOME, kept in `tracked.json`
+pub struct TrackedRegistry {
+    path: PathBuf,
+    patterns: Vec<String>,
+}
+
+impl TrackedRegistry {
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let file: RegistryFile = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid tracked dotfiles list: {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RegistryFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            patterns: file.patterns,
+        })
+    }
+
+    /// Add a path or glob; returns the normalized pattern, or `None` if it was already tracked
+    pub fn add(&mut self, pattern: &str, home_dir: &Path) -> Result<Option<String>> {
+        let pattern = normalize_pattern(pattern, home_dir)?;
+        if self.patterns.contains(&pattern) {
+            return Ok(None);
+        }
+        self.patterns.push(pattern.clone());
+        self.save()?;
+        Ok(Some(pattern))
+    }
+
+    /// Returns whether the pattern was tracked
+    pub fn remove(&mut self, pattern: &str, home_dir: &Path) -> Result<bool> {
+        let pattern = normalize_pattern(pattern, home_dir)?;
+        let before = self.patterns.len();
+        self.patterns.retain(|p| *p != pattern);
+        if self.patterns.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Every pattern with the files it matches right now
+    pub fn resolve(&self, home_dir: &Path) -> Vec<TrackedPattern> {
+        self.patterns
+            .iter()
+            .map(|pattern| TrackedPattern {
+                pattern: pattern.clone(),
+                matches: expand(pattern, home_dir),
+            })
+            .collect()
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = RegistryFile {
+            patterns: self.patterns.clone(),
+        };
+        write_atomic(&self.path, &serde_json::to_vec_pretty(&file)?)
+    }
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Files a pattern stands for, relative to the home directory. A plain path stands for
+/// itself even while it doesn't exist, so it can be created from the editor.
+fn expand(pattern: &str, home_dir: &Path) -> Vec<String> {
+    if !is_glob(pattern) {
+        return vec![pattern.to_string()];
+    }
+    let full = home_dir.join(pattern);
+    let Ok(paths) = glob::glob(&full.to_string_lossy()) else {
+        return Vec::new();
+    };
+    let mut matches: Vec<String> = paths
+        .filter_map(|path| path.ok())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            path.strip_prefix(home_dir)
+                .ok()
+                .map(|relative| relative.to_string_lossy().to_string())
+        })
+        .take(MAX_GLOB_MATCHES)
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// `~/x`, `/home/me/x` and `x` all become `x`; anything leaving $HOME is refused
+fn normalize_pattern(pattern: &str, home_dir: &Path) -> Result<String> {
+    let pattern = pattern.trim();
+    let relative = if let Some(rest) = pattern.strip_prefix("~/") {
+        PathBuf::from(rest)
+    } else if pattern.starts_with('/') {
+        Path::new(pattern)
+            .strip_prefix(home_dir)
+            .map(Path::to_path_buf)
+            .map_err(|_| anyhow::anyhow!("{} is outside the home directory", pattern))?
+    } else {
+        PathBuf::from(pattern)
+    };
+
+    if relative.as_os_str().is_empty()
+        || !relative.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        anyhow::bail!("Invalid dotfile path: {}", pattern);
+    }
+    let relative = relative.to_string_lossy().to_string();
+
+    if is_glob(&relative) {
+        glob::Pattern::new(&relative).with_context(|| format!("Invalid glob: {}", pattern))?;
+        let below_root = relative.strip_prefix(GLOB_ROOT);
+        if below_root.is_none_or(|rest| rest.starts_with("**")) {
+            anyhow::bail!("Glob patterns must start with a directory under ~/{}: {}", GLOB_ROOT, pattern);
+        }
+    }
+    Ok(relative)
+}
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    fs::{DirBuilder, OpenOptions},
+    io::Write,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::Path,
+    process::Stdio,
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::process::Command;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::DotFileType;
+
+const VALIDATOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    // 1-based, when the checker reported one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Validation {
+    // Command that checked the content; `None` when the file type has no checker
+    // or it isn't installed
+    pub validator: Option<String>,
+    pub valid: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Validation {
+    fn unchecked() -> Self {
+        Self {
+            validator: None,
+            valid: true,
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+/// A write refused because the content failed its syntax check
+#[derive(Debug, Error)]
+#[error("{}", self.describe())]
+pub struct ValidationError {
+    pub validation: Validation,
+}
+
+impl ValidationError {
+    fn describe(&self) -> String {
+        let validator = self.validation.validator.as_deref().unwrap_or("validator");
+        let first = self.validation.diagnostics.first().map(|d| match d.line {
+            Some(line) => format!("line {}: {}", line, d.message),
+            None => d.message.clone(),
+        });
+        format!(
+            "Syntax check failed ({}): {}",
+            validator,
+            first.unwrap_or_else(|| "invalid content".to_string())
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Validator {
+    Bash,
+    Zsh,
+    Tmux,
+    GitConfig,
+    SshConfig,
+}
+
+impl Validator {
+    fn for_file(file_type: &DotFileType, file_name: &str) -> Option<Self> {
+        match file_type {
+            DotFileType::Shell if file_name.contains("zsh") => Some(Validator::Zsh),
+            DotFileType::Shell => Some(Validator::Bash),
+            DotFileType::Tmux => Some(Validator::Tmux),
+            DotFileType::Git if !file_name.contains("ignore") => Some(Validator::GitConfig),
+            // authorized_keys and known_hosts aren't ssh_config files
+            DotFileType::SSH if file_name == "config" => Some(Validator::SshConfig),
+            _ => None,
+        }
+    }
+
+    fn command(self, file: &Path) -> Command {
+        let mut command = match self {
+            Validator::Bash => Command::new("bash"),
+            Validator::Zsh => Command::new("zsh"),
+            Validator::Tmux => Command::new("tmux"),
+            Validator::GitConfig => Command::new("git"),
+            Validator::SshConfig => Command::new("ssh"),
+        };
+        match self {
+            Validator::Bash | Validator::Zsh => {
+                command.arg("-n").arg(file);
+            }
+            // A private server that exits right away, so the user's server is untouched
+            Validator::Tmux => {
+                let socket = format!("webmux-validate-{}", Uuid::new_v4().simple());
+                command
+                    .args(["-L", &socket, "-f", "/dev/null", "start-server", ";", "source-file", "-n"])
+                    .arg(file);
+            }
+            Validator::GitConfig => {
+                command.arg("config").arg("--file").arg(file).arg("--list");
+            }
+            Validator::SshConfig => {
+                command.arg("-G").arg("-F").arg(file).arg("localhost");
+            }
+        }
+        command
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Validator::Bash => "bash -n",
+            Validator::Zsh => "zsh -n",
+            Validator::Tmux => "tmux source-file -n",
+            Validator::GitConfig => "git config --list",
+            Validator::SshConfig => "ssh -G",
+        }
+    }
+}
+
+/// Syntax-check `content` as a file of `file_type` named `file_name`
+pub async fn validate(file_type: &DotFileType, file_name: &str, content: &str) -> Result<Validation> {
+    let Some(validator) = Validator::for_file(file_type, file_name) else {
+        return Ok(Validation::unchecked());
+    };
+
+    // Check the proposed content, not what is on disk; keep the file name for messages.
+    // Dotfiles often hold tokens, so only we may read the copy.
+    let dir = std::env::temp_dir().join(format!("webmux-validate-{}", Uuid::new_v4().simple()));
+    DirBuilder::new().mode(0o700).create(&dir)?;
+    let file = dir.join(file_name);
+    let result = async {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&file)?
+            .write_all(content.as_bytes())?;
+        run(validator, &file).await
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+async fn run(validator: Validator, file: &Path) -> Result<Validation> {
+    // tmux reports errors on stdout; the others print what they parsed there
+    let stdout = if validator == Validator::Tmux { Stdio::piped() } else { Stdio::null() };
+    let child = validator
+        .command(file)
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!("{} is not installed; skipping syntax check", validator.describe());
+            return Ok(Validation::unchecked());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let output = tokio::time::timeout(VALIDATOR_TIMEOUT, child.wait_with_output())
+        .await
+        .with_context(|| format!("{} timed out", validator.describe()))??;
+
+    let file = file.to_string_lossy();
+    let mut report = String::from_utf8_lossy(&output.stdout).to_string();
+    report.push_str(&String::from_utf8_lossy(&output.stderr));
+    let diagnostics: Vec<Diagnostic> = report
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| parse_diagnostic(validator, line, &file))
+        .collect();
+
+    // Some tmux versions exit 0 even when the file has errors
+    let valid = output.status.success() && diagnostics.is_empty();
+    Ok(Validation {
+        validator: Some(validator.describe().to_string()),
+        valid,
+        diagnostics: if valid || !diagnostics.is_empty() {
+            diagnostics
+        } else {
+            vec![Diagnostic {
+                line: None,
+                message: format!("{} failed", validator.describe()),
+            }]
+        },
+    })
+}
+
+/// Turn one line of checker output into a diagnostic, dropping the temp file path
+fn parse_diagnostic(validator: Validator, line: &str, file: &str) -> Option<Diagnostic> {
+    let diagnostic = |line: Option<usize>, message: &str| Diagnostic {
+        line,
+        message: message.trim().to_string(),
+    };
+
+    match validator {
+        // `<file>: line 3: syntax error ...`, `<file>:3: parse error ...`
+        Validator::Bash | Validator::Zsh | Validator::SshConfig => {
+            let rest = line.strip_prefix(file).map(|rest| rest.trim_start_matches(':').trim_start());
+            let Some(rest) = rest else {
+                // ssh warns about things unrelated to the file, e.g. having no terminal
+                return (validator != Validator::SshConfig).then(|| diagnostic(None, line));
+            };
+            if validator == Validator::SshConfig && rest.starts_with("terminating") {
+                return None;
+            }
+            let (number, message) = rest
+                .strip_prefix("line ")
+                .unwrap_or(rest)
+                .split_once(':')
+                .unwrap_or(("", rest));
+            // bash follows a syntax error with the offending source line: `<file>: line 3: `fi'`
+            let message = message.trim();
+            if message.starts_with('`') && message.ends_with('\'') {
+                return None;
+            }
+            Some(diagnostic(number.trim().parse().ok(), message))
+        }
+        // `<file>:3: unknown command: foo`
+        Validator::Tmux => {
+            let rest = line.strip_prefix(file).and_then(|rest| rest.strip_prefix(':'));
+            match rest.and_then(|rest| rest.split_once(':')) {
+                Some((number, message)) => Some(diagnostic(number.parse().ok(), message)),
+                None => Some(diagnostic(None, line)),
+            }
+        }
+        // `fatal: bad config line 3 in file <file>`
+        Validator::GitConfig => {
+            let message = line.strip_prefix("fatal: ").unwrap_or(line);
+            let number = message
+                .split_once("line ")
+                .and_then(|(_, rest)| rest.split_whitespace().next())
+                .and_then(|number| number.parse().ok());
+            let message = message.split(" in file ").next().unwrap_or(message);
+            Some(diagnostic(number, message))
+        }
+    }
+}
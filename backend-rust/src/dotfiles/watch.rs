@@ -0,0 +1,187 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc,
+    time::{interval, Instant, MissedTickBehavior},
+};
+use tracing::{debug, error, info, warn};
+
+use super::DOTFILES_MANAGER;
+use crate::types::ServerMessage;
+
+// Editors save in several steps (write a temp file, rename, chmod); wait for the file
+// to settle before reading it
+const DEBOUNCE: Duration = Duration::from_millis(300);
+// Glob patterns can match new files at any time; look for them this often
+const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DotfileChange {
+    Created,
+    Modified,
+    Deleted,
+}
+
+struct WatchedFile {
+    // Relative to $HOME, as in the dotfiles list
+    name: String,
+    exists: bool,
+}
+
+/// Watches the directories holding tracked dotfiles (files are often replaced rather
+/// than written in place, which a watch on the file itself wouldn't survive). Edits made
+/// outside webmux are recorded as `external` versions and broadcast as `dotfile-changed`.
+pub struct DotfilesWatcher {
+    broadcast_tx: mpsc::UnboundedSender<ServerMessage>,
+    files: HashMap<PathBuf, WatchedFile>,
+    dirs: HashSet<PathBuf>,
+}
+
+impl DotfilesWatcher {
+    pub fn new(broadcast_tx: mpsc::UnboundedSender<ServerMessage>) -> Self {
+        Self {
+            broadcast_tx,
+            files: HashMap::new(),
+            dirs: HashSet::new(),
+        }
+    }
+
+    pub async fn start(mut self) {
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let _ = event_tx.send(event);
+        });
+        let mut watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                error!("Failed to start dotfiles watcher: {}", e);
+                return;
+            }
+        };
+        self.refresh(&mut watcher).await;
+        info!("Watching {} dotfiles in {} directories", self.files.len(), self.dirs.len());
+
+        // Paths with events, and when the latest one came in
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+        let mut flush = interval(DEBOUNCE / 2);
+        flush.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut rescan = interval(RESCAN_INTERVAL);
+        rescan.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        rescan.tick().await;
+
+        loop {
+            tokio::select! {
+                Some(event) = event_rx.recv() => match event {
+                    Ok(event) => {
+                        for path in event.paths {
+                            if self.files.contains_key(&path) {
+                                pending.insert(path, Instant::now());
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Dotfiles watcher error: {}", e),
+                },
+                _ = flush.tick(), if !pending.is_empty() => {
+                    let settled: Vec<PathBuf> = pending
+                        .iter()
+                        .filter(|(_, at)| at.elapsed() >= DEBOUNCE)
+                        .map(|(path, _)| path.clone())
+                        .collect();
+                    for path in settled {
+                        pending.remove(&path);
+                        self.check(path).await;
+                    }
+                }
+                _ = DOTFILES_MANAGER.tracked_changed() => self.refresh(&mut watcher).await,
+                _ = rescan.tick() => self.refresh(&mut watcher).await,
+            }
+        }
+    }
+
+    /// Pick up the current set of tracked files and watch the directories they're in
+    async fn refresh(&mut self, watcher: &mut RecommendedWatcher) {
+        let tracked = match DOTFILES_MANAGER.watched_files().await {
+            Ok(tracked) => tracked,
+            Err(e) => {
+                warn!("Failed to list dotfiles to watch: {}", e);
+                return;
+            }
+        };
+
+        let mut files = HashMap::new();
+        for (name, path) in tracked {
+            let exists = self.files.get(&path).map_or_else(|| path.is_file(), |file| file.exists);
+            files.insert(path, WatchedFile { name, exists });
+        }
+        let dirs: HashSet<PathBuf> = files
+            .keys()
+            .filter_map(|path| path.parent())
+            .filter(|dir| dir.is_dir())
+            .map(PathBuf::from)
+            .collect();
+
+        for dir in self.dirs.difference(&dirs) {
+            let _ = watcher.unwatch(dir);
+        }
+        let mut watched = HashSet::new();
+        for dir in dirs {
+            if self.dirs.contains(&dir) {
+                watched.insert(dir);
+            } else {
+                match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => {
+                        debug!("Watching {}", dir.display());
+                        watched.insert(dir);
+                    }
+                    Err(e) => warn!("Failed to watch {}: {}", dir.display(), e),
+                }
+            }
+        }
+        self.files = files;
+        self.dirs = watched;
+    }
+
+    /// Record and announce what happened to a file once its events settled
+    async fn check(&mut self, path: PathBuf) {
+        let Some(file) = self.files.get_mut(&path) else {
+            return;
+        };
+
+        let (change, etag) = if path.is_file() {
+            let created = !file.exists;
+            file.exists = true;
+            match DOTFILES_MANAGER.record_external_edit(&path).await {
+                Ok(Some(etag)) if created => (DotfileChange::Created, Some(etag)),
+                Ok(Some(etag)) => (DotfileChange::Modified, Some(etag)),
+                // Unchanged since the last recorded version, e.g. written by webmux
+                Ok(None) => return,
+                Err(e) => {
+                    warn!("Failed to record change to {}: {}", path.display(), e);
+                    return;
+                }
+            }
+        } else if file.exists {
+            file.exists = false;
+            (DotfileChange::Deleted, None)
+        } else {
+            return;
+        };
+
+        info!("Dotfile {} {:?} outside webmux", file.name, change);
+        let message = ServerMessage::DotfileChanged {
+            name: file.name.clone(),
+            path: path.to_string_lossy().to_string(),
+            change,
+            etag,
+        };
+        if let Err(e) = self.broadcast_tx.send(message) {
+            error!("Failed to broadcast dotfile change: {}", e);
+        }
+    }
+}
@@ -14,6 +14,9 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
     
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    
     #[error("Not found: {0}")]
     NotFound(String),
     
@@ -31,6 +34,7 @@ impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             AppError::SessionError(msg) => (StatusCode::CONFLICT, msg),
             AppError::WebSocketError(msg) => (StatusCode::BAD_REQUEST, msg),
@@ -1,23 +1,26 @@
 use anyhow::Result;
 use axum::{
-    routing::get,
+    middleware,
+    routing::{get, post},
     Router,
 };
 use axum_server::tls_rustls::RustlsConfig;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::{
     net::SocketAddr,
-    path::PathBuf,
     sync::Arc,
 };
 use tokio::signal;
+use tower::ServiceBuilder;
 use tower_http::{
     cors::{Any, CorsLayer},
     services::{ServeDir, ServeFile},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod auth;
+mod config;
 mod error;
 mod tmux;
 mod types;
@@ -36,9 +39,29 @@ pub static ENABLE_AUDIO_LOGS: std::sync::atomic::AtomicBool = std::sync::atomic:
 #[command(name = "webmux-backend")]
 #[command(about = "WebMux backend server", long_about = None)]
 struct Args {
-    /// Enable audio streaming debug logs
+    #[command(flatten)]
+    overrides: config::ConfigOverrides,
+
+    /// Print the effective merged configuration as TOML and exit
     #[arg(long)]
-    audio: bool,
+    print_config: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage login credentials
+    Auth {
+        #[command(subcommand)]
+        action: auth::AuthCommand,
+    },
+    /// Cron job helpers
+    Cron {
+        #[command(subcommand)]
+        action: cron::CronCommand,
+    },
 }
 
 use tokio::sync::mpsc;
@@ -46,29 +69,63 @@ use crate::types::ServerMessage;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub enable_audio_logs: bool,
+    pub config: Arc<config::Config>,
     pub broadcast_tx: mpsc::UnboundedSender<ServerMessage>,
     pub client_manager: Arc<websocket::ClientManager>,
+    pub auth: Arc<auth::AuthManager>,
+    pub streams: Arc<websocket::stream::StreamRegistry>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    let config = config::Config::load(&args.overrides)?;
+
+    if args.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+
+    let command = match args.command {
+        Some(Command::Cron { action }) => {
+            // Exit with the job's own status so cron sees it unchanged
+            let code = cron::run_command(&config, action).await?;
+            std::process::exit(code);
+        }
+        command => command,
+    };
+
+    let auth = Arc::new(auth::AuthManager::open(
+        config.auth_dir(),
+        config.auth.enabled,
+        chrono::Duration::hours(config.auth.session_ttl_hours),
+    )?);
+
+    if let Some(Command::Auth { action }) = command {
+        return auth::run_command(&auth, action).await;
+    }
+
     // Initialize tracing
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "webmux_backend=debug,tower_http=info".into()),
+                .unwrap_or_else(|_| config.logging.filter.as_str().into()),
         )
         .with(tracing_subscriber::fmt::layer())
         .init();
 
     // Set the global audio logging flag
-    ENABLE_AUDIO_LOGS.store(args.audio, std::sync::atomic::Ordering::Relaxed);
+    ENABLE_AUDIO_LOGS.store(config.logging.audio_debug, std::sync::atomic::Ordering::Relaxed);
     
-    if args.audio {
+    if config.logging.audio_debug {
         info!("Audio debug logging enabled");
     }
+
+    if !auth.is_enabled() {
+        warn!("Authentication is DISABLED - anyone who can reach this server gets a shell");
+    } else if !auth.has_users().await {
+        warn!("No users configured - create one with: webmux-backend auth set-password <username>");
+    }
     
     // Create broadcast channel for tmux updates
     let (broadcast_tx, mut broadcast_rx) = mpsc::unbounded_channel::<ServerMessage>();
@@ -85,32 +142,76 @@ async fn main() -> Result<()> {
     });
     
     let state = AppState {
-        enable_audio_logs: args.audio,
+        config: Arc::new(config.clone()),
         broadcast_tx: broadcast_tx.clone(),
         client_manager,
+        auth,
+        streams: Arc::new(websocket::stream::StreamRegistry::new(
+            std::time::Duration::from_secs(config.terminal.resume_grace_secs),
+            config.terminal.replay_buffer_bytes,
+        )),
     };
+    let state = Arc::new(state);
     
     // Initialize CRON manager
-    if let Err(e) = crate::cron::CRON_MANAGER.initialize().await {
-        error!("Failed to initialize CRON manager: {}", e);
+    if config.features.cron {
+        if let Err(e) = crate::cron::CRON_MANAGER.initialize(&config).await {
+            error!("Failed to initialize CRON manager: {}", e);
+        }
     }
     
+    // Initialize dotfiles manager
+    if config.features.dotfiles {
+        match crate::dotfiles::DOTFILES_MANAGER.initialize(&config).await {
+            Ok(()) if config.dotfiles.watch => {
+                let watcher = dotfiles::watch::DotfilesWatcher::new(broadcast_tx.clone());
+                tokio::spawn(watcher.start());
+            }
+            Ok(()) => {}
+            Err(e) => error!("Failed to initialize dotfiles manager: {}", e),
+        }
+    }
+
     // Start tmux monitor
-    let monitor = monitor::TmuxMonitor::new(broadcast_tx);
+    let monitor = monitor::TmuxMonitor::new(
+        broadcast_tx,
+        config.monitor.clone(),
+    );
     tokio::spawn(async move {
         monitor.start().await;
     });
 
     // Serve static files from dist directory
-    let serve_dir = ServeDir::new("../dist")
-        .not_found_service(ServeFile::new("../dist/index.html"));
+    let static_dir = &config.server.static_dir;
+    if !static_dir.exists() {
+        warn!("Static directory {} does not exist", static_dir.display());
+    }
+    let serve_dir = ServeDir::new(static_dir)
+        .not_found_service(ServeFile::new(static_dir.join("index.html")));
+
+    // Dotfile bundle downloads and uploads
+    let dotfiles_routes = if config.features.dotfiles {
+        dotfiles::http::routes()
+            .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_auth))
+    } else {
+        Router::new()
+    };
 
     // Build the router
     let app = Router::new()
-        // WebSocket endpoint
+        // Authentication
+        .route("/login", get(auth::login_page))
+        .route("/api/auth/login", post(auth::login))
+        .route("/api/auth/logout", post(auth::logout))
+        // WebSocket endpoint (validates its own token before upgrading)
         .route("/ws", get(websocket::ws_handler))
-        // Serve static files (Vue app)
-        .fallback_service(serve_dir)
+        .merge(dotfiles_routes)
+        // Serve static files (Vue app) to authenticated users
+        .fallback_service(
+            ServiceBuilder::new()
+                .layer(middleware::from_fn_with_state(state.clone(), auth::require_auth))
+                .service(serve_dir),
+        )
         // Add CORS
         .layer(
             CorsLayer::new()
@@ -118,27 +219,28 @@ async fn main() -> Result<()> {
                 .allow_methods(Any)
                 .allow_headers(Any),
         )
-        .with_state(Arc::new(state));
+        .with_state(state);
 
-    // Dev branch uses different ports
-    let http_port = 4000;
-    let https_port = 4443;
+    let http_port = config.server.http_port;
+    let https_port = config.tls.port;
 
     // Start HTTP server
-    let http_addr = SocketAddr::from(([0, 0, 0, 0], http_port));
+    let http_addr = SocketAddr::new(config.server.bind, http_port);
     info!("WebMux HTTP server running on {}", http_addr);
     info!("  Local:    http://localhost:{}", http_port);
-    info!("  Network:  http://0.0.0.0:{}", http_port);
+    info!("  Network:  http://{}", http_addr);
 
     // Check if HTTPS certificates exist
-    let cert_path = PathBuf::from("../certs/cert.pem");
-    let key_path = PathBuf::from("../certs/key.pem");
+    let cert_path = config.tls.cert.clone();
+    let key_path = config.tls.key.clone();
 
-    if cert_path.exists() && key_path.exists() {
+    if !config.tls.enabled {
+        info!("HTTPS disabled by configuration");
+    } else if cert_path.exists() && key_path.exists() {
         // Start HTTPS server in a separate task
-        let https_app = app.clone();
+        let https_app = app.clone().layer(axum::Extension(auth::Https));
+        let https_addr = SocketAddr::new(config.tls_bind(), https_port);
         tokio::spawn(async move {
-            let https_addr = SocketAddr::from(([0, 0, 0, 0], https_port));
             let config = match RustlsConfig::from_pem_file(&cert_path, &key_path).await {
                 Ok(config) => config,
                 Err(e) => {
@@ -149,7 +251,7 @@ async fn main() -> Result<()> {
 
             info!("WebMux HTTPS server running on {}", https_addr);
             info!("  Local:    https://localhost:{}", https_port);
-            info!("  Network:  https://0.0.0.0:{}", https_port);
+            info!("  Network:  https://{}", https_addr);
             info!("  Tailscale: Use your Tailscale IP with port {}", https_port);
             info!("  Note: You may need to accept the self-signed certificate");
 
@@ -161,7 +263,11 @@ async fn main() -> Result<()> {
             }
         });
     } else {
-        info!("Warning: Could not load SSL certificates from certs/");
+        info!(
+            "Warning: Could not load SSL certificates from {} / {}",
+            cert_path.display(),
+            key_path.display()
+        );
         info!("HTTPS server will not be available");
     }
 
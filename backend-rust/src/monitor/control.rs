@@ -0,0 +1,146 @@
+use anyhow::Result;
+use std::{process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+
+use crate::tmux::MONITOR_SESSION;
+
+// How long to wait for the control client to attach before giving up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Notification lines emitted by a `tmux -C` client
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    SessionsChanged,
+    SessionChanged,
+    SessionRenamed,
+    SessionWindowChanged,
+    ClientSessionChanged,
+    ClientDetached,
+    WindowAdd,
+    WindowClose,
+    WindowRenamed,
+    WindowPaneChanged,
+    LayoutChange,
+    Output,
+    Exit(Option<String>),
+    Other(String),
+}
+
+impl Notification {
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix('%')?;
+        let (name, args) = rest.split_once(' ').unwrap_or((rest, ""));
+        let notification = match name {
+            "sessions-changed" => Notification::SessionsChanged,
+            "session-changed" => Notification::SessionChanged,
+            "session-renamed" => Notification::SessionRenamed,
+            "session-window-changed" => Notification::SessionWindowChanged,
+            "client-session-changed" => Notification::ClientSessionChanged,
+            "client-detached" => Notification::ClientDetached,
+            "window-add" | "unlinked-window-add" => Notification::WindowAdd,
+            "window-close" | "unlinked-window-close" => Notification::WindowClose,
+            "window-renamed" | "unlinked-window-renamed" => Notification::WindowRenamed,
+            "window-pane-changed" => Notification::WindowPaneChanged,
+            "layout-change" => Notification::LayoutChange,
+            "output" | "extended-output" => Notification::Output,
+            "exit" => Notification::Exit((!args.is_empty()).then(|| args.to_string())),
+            other => Notification::Other(other.to_string()),
+        };
+        Some(notification)
+    }
+
+    /// Whether the notification can change what `list-sessions`/`list-windows` report
+    pub fn affects_sessions(&self) -> bool {
+        !matches!(
+            self,
+            Notification::Output | Notification::Exit(_) | Notification::Other(_)
+        )
+    }
+}
+
+/// A persistent `tmux -C` client attached to a private session, used only to
+/// receive server-wide notifications
+pub struct ControlClient {
+    // Killed on drop so a restarted monitor never leaks clients
+    _child: Child,
+    stdin: ChildStdin,
+    lines: Lines<BufReader<ChildStdout>>,
+    // Inside a %begin/%end reply to one of our own commands
+    in_reply: bool,
+}
+
+impl ControlClient {
+    pub async fn connect() -> Result<Self> {
+        // The session goes away with the client, so it never outlives webmux or keeps
+        // an otherwise empty tmux server running
+        let mut child = Command::new("tmux")
+            .args(["-C", "new-session", "-A", "-s", MONITOR_SESSION, "cat", ";"])
+            .args(["set-option", "-t", MONITOR_SESSION, "destroy-unattached", "on"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("No stdin for tmux control client"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("No stdout for tmux control client"))?;
+
+        let mut client = Self {
+            _child: child,
+            stdin,
+            lines: BufReader::new(stdout).lines(),
+            in_reply: false,
+        };
+
+        // Commands only apply once the client is attached to its session
+        tokio::time::timeout(CONNECT_TIMEOUT, async {
+            loop {
+                match client.next_notification().await? {
+                    Some(Notification::SessionChanged) => return Ok(()),
+                    Some(Notification::Exit(reason)) => {
+                        anyhow::bail!("tmux control client exited: {}", reason.unwrap_or_default())
+                    }
+                    Some(_) => {}
+                    None => anyhow::bail!("tmux control client closed its output"),
+                }
+            }
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out waiting for tmux control client"))??;
+
+        // Pane output is already streamed through PTYs; don't pay for it twice
+        client.command("refresh-client -f no-output").await?;
+        Ok(client)
+    }
+
+    async fn command(&mut self, command: &str) -> Result<()> {
+        self.stdin.write_all(command.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Next notification, or `None` once the client has gone away. Cancel safe.
+    pub async fn next_notification(&mut self) -> Result<Option<Notification>> {
+        while let Some(line) = self.lines.next_line().await? {
+            if line.starts_with("%begin ") {
+                self.in_reply = true;
+                continue;
+            }
+            if line.starts_with("%end ") || line.starts_with("%error ") {
+                self.in_reply = false;
+                continue;
+            }
+            if self.in_reply {
+                continue;
+            }
+            if let Some(notification) = Notification::parse(&line) {
+                return Ok(Some(notification));
+            }
+        }
+        Ok(None)
+    }
+}
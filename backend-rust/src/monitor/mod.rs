@@ -5,15 +5,27 @@ use std::{
 };
 use tokio::{
     sync::{mpsc, RwLock},
-    time::interval,
+    time::{interval, sleep_until, Instant, MissedTickBehavior},
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
+    config::{MonitorConfig, MonitorMode},
     tmux,
     types::{ServerMessage, TmuxSession},
 };
 
+mod control;
+
+use control::ControlClient;
+
+// Notifications arrive in bursts (e.g. new-session adds a session and a window);
+// wait this long after the first one before rescanning
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+// How long to poll before trying control mode again after it failed or exited
+const CONTROL_RETRY: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone, PartialEq)]
 struct SessionState {
     sessions: Vec<TmuxSession>,
@@ -24,34 +36,108 @@ struct SessionState {
 pub struct TmuxMonitor {
     state: Arc<RwLock<SessionState>>,
     broadcast_tx: mpsc::UnboundedSender<ServerMessage>,
+    config: MonitorConfig,
 }
 
 impl TmuxMonitor {
-    pub fn new(broadcast_tx: mpsc::UnboundedSender<ServerMessage>) -> Self {
+    pub fn new(broadcast_tx: mpsc::UnboundedSender<ServerMessage>, config: MonitorConfig) -> Self {
         Self {
             state: Arc::new(RwLock::new(SessionState {
                 sessions: Vec::new(),
                 window_pane_counts: HashMap::new(),
             })),
             broadcast_tx,
+            config,
         }
     }
 
     pub async fn start(&self) {
-        info!("Starting tmux monitor");
+        info!("Starting tmux monitor ({:?} mode)", self.config.mode);
         
         // Initial state fetch
         self.check_for_changes().await;
-        
-        // Start monitoring loop
-        let mut interval = interval(Duration::from_millis(250)); // Check every 250ms for better responsiveness
+
+        if self.config.mode == MonitorMode::Poll {
+            self.poll(None).await;
+            return;
+        }
+
+        loop {
+            match ControlClient::connect().await {
+                Ok(client) => {
+                    info!("Watching tmux through control mode");
+                    self.watch(client).await;
+                    warn!("tmux control client exited");
+                    // Whatever ended the client may have changed sessions too
+                    self.check_for_changes().await;
+                }
+                Err(e) if self.config.mode == MonitorMode::Control => {
+                    error!("tmux control mode unavailable: {}", e);
+                }
+                Err(e) => {
+                    warn!("tmux control mode unavailable, falling back to polling: {}", e);
+                }
+            }
+
+            if self.config.mode == MonitorMode::Control {
+                tokio::time::sleep(CONTROL_RETRY).await;
+            } else {
+                self.poll(Some(CONTROL_RETRY)).await;
+            }
+        }
+    }
+
+    /// Poll for changes, forever or for `duration`
+    async fn poll(&self, duration: Option<Duration>) {
+        let deadline = duration.map(|d| Instant::now() + d);
+        let mut interval = interval(Duration::from_millis(self.config.interval_ms));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
         
         loop {
             interval.tick().await;
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return;
+            }
             self.check_for_changes().await;
         }
     }
 
+    /// Rescan whenever the control client reports a relevant change, until it exits
+    async fn watch(&self, mut client: ControlClient) {
+        let mut resync = interval(Duration::from_secs(self.config.resync_secs));
+        resync.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        resync.tick().await;
+        let mut pending: Option<Instant> = None;
+
+        loop {
+            tokio::select! {
+                notification = client.next_notification() => match notification {
+                    Ok(Some(notification)) if notification.affects_sessions() => {
+                        debug!("tmux notification: {:?}", notification);
+                        pending.get_or_insert_with(|| Instant::now() + DEBOUNCE);
+                    }
+                    Ok(Some(control::Notification::Exit(reason))) => {
+                        debug!("tmux control client exit: {:?}", reason);
+                        return;
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => return,
+                    Err(e) => {
+                        error!("Failed to read from tmux control client: {}", e);
+                        return;
+                    }
+                },
+                _ = sleep_until(pending.unwrap_or_else(Instant::now)), if pending.is_some() => {
+                    pending = None;
+                    self.check_for_changes().await;
+                }
+                _ = resync.tick() => {
+                    self.check_for_changes().await;
+                }
+            }
+        }
+    }
+
     async fn check_for_changes(&self) {
         // Get current tmux state
         let current_sessions = match tmux::list_sessions().await {
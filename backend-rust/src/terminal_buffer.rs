@@ -1,24 +1,16 @@
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::collections::VecDeque;
 use bytes::Bytes;
-use tokio::sync::Notify;
-use crossbeam::queue::ArrayQueue;
 use simdutf8;
 
-const RING_BUFFER_SIZE: usize = 1 << 20; // 1MB
-const MAX_CHUNK_SIZE: usize = 65536;
-
+/// Replay window of recent terminal output.
+/// Every write gets the next sequence number; old chunks are evicted once the
+/// window exceeds its byte capacity, so reconnecting clients can fetch what they missed.
 pub struct TerminalRingBuffer {
-    // Main ring buffer
-    buffer: Box<[u8; RING_BUFFER_SIZE]>,
-    // Write position (only written by producer)
-    write_pos: AtomicU64,
-    // Sequence number for ordering
-    sequence: AtomicU64,
-    // Notification for new data
-    notify: Arc<Notify>,
-    // Queue for completed chunks
-    chunks: Arc<ArrayQueue<TerminalChunk>>,
+    chunks: VecDeque<TerminalChunk>,
+    // Sequence number of the most recent chunk (0 = nothing written yet)
+    last_sequence: u64,
+    bytes: usize,
+    capacity: usize,
 }
 
 #[derive(Clone)]
@@ -27,119 +19,66 @@ pub struct TerminalChunk {
     pub data: Bytes,
 }
 
-#[derive(Clone)]
-pub struct TerminalReader {
-    // Last read sequence
-    last_sequence: u64,
-    // Shared chunks queue
-    chunks: Arc<ArrayQueue<TerminalChunk>>,
-    // Notification handle
-    notify: Arc<Notify>,
+/// Chunks needed to catch a reader up
+pub struct Replay {
+    pub chunks: Vec<TerminalChunk>,
+    // True when output after the requested sequence was already evicted
+    pub gap: bool,
 }
 
 impl TerminalRingBuffer {
-    pub fn new() -> Self {
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            buffer: Box::new([0u8; RING_BUFFER_SIZE]),
-            write_pos: AtomicU64::new(0),
-            sequence: AtomicU64::new(0),
-            notify: Arc::new(Notify::new()),
-            chunks: Arc::new(ArrayQueue::new(1024)),
+            chunks: VecDeque::new(),
+            last_sequence: 0,
+            bytes: 0,
+            capacity: capacity.max(1),
         }
     }
     
-    pub fn write(&mut self, data: &[u8]) -> Result<(), &'static str> {
-        if data.len() > MAX_CHUNK_SIZE {
-            return Err("Data too large for single write");
-        }
-        
-        let sequence = self.sequence.fetch_add(1, Ordering::AcqRel);
-        let write_pos = self.write_pos.load(Ordering::Acquire) as usize;
-        
-        // Copy data to ring buffer
-        let end_pos = (write_pos + data.len()) % RING_BUFFER_SIZE;
-        
-        if end_pos > write_pos {
-            // Simple case: contiguous write
-            self.buffer[write_pos..end_pos].copy_from_slice(data);
-        } else {
-            // Wrap around case
-            let first_part = RING_BUFFER_SIZE - write_pos;
-            self.buffer[write_pos..].copy_from_slice(&data[..first_part]);
-            self.buffer[..end_pos].copy_from_slice(&data[first_part..]);
-        }
-        
-        // Update write position
-        self.write_pos.store(end_pos as u64, Ordering::Release);
-        
-        // Create chunk and add to queue
+    /// Append a chunk and return its sequence number
+    pub fn write(&mut self, data: &[u8]) -> TerminalChunk {
+        self.last_sequence += 1;
         let chunk = TerminalChunk {
-            sequence,
+            sequence: self.last_sequence,
             data: Bytes::copy_from_slice(data),
         };
         
-        // Try to add to queue, if full, drop oldest
-        while self.chunks.push(chunk.clone()).is_err() {
-            let _ = self.chunks.pop();
-        }
+        self.bytes += chunk.data.len();
+        self.chunks.push_back(chunk.clone());
         
-        // Notify readers
-        self.notify.notify_waiters();
+        // Evict oldest chunks, always keeping the newest one
+        while self.bytes > self.capacity && self.chunks.len() > 1 {
+            if let Some(old) = self.chunks.pop_front() {
+                self.bytes -= old.data.len();
+            }
+        }
         
-        Ok(())
+        chunk
     }
     
-    pub fn create_reader(&self) -> TerminalReader {
-        TerminalReader {
-            last_sequence: 0,
-            chunks: self.chunks.clone(),
-            notify: self.notify.clone(),
-        }
-    }
-}
-
-impl TerminalReader {
-    pub async fn read_next(&mut self) -> Option<TerminalChunk> {
-        loop {
-            // Try to find next chunk in sequence
-            let mut found = None;
-            for _ in 0..self.chunks.len() {
-                if let Some(chunk) = self.chunks.pop() {
-                    if chunk.sequence == self.last_sequence + 1 {
-                        self.last_sequence = chunk.sequence;
-                        found = Some(chunk);
-                        break;
-                    } else if chunk.sequence > self.last_sequence {
-                        // Re-queue for later
-                        let _ = self.chunks.push(chunk);
-                    }
-                    // Drop chunks with sequence <= last_sequence (already read)
-                }
-            }
-            
-            if let Some(chunk) = found {
-                return Some(chunk);
-            }
-            
-            // Wait for new data
-            self.notify.notified().await;
-        }
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
     }
     
-    pub fn try_read_next(&mut self) -> Option<TerminalChunk> {
-        // Non-blocking version
-        for _ in 0..self.chunks.len() {
-            if let Some(chunk) = self.chunks.pop() {
-                if chunk.sequence == self.last_sequence + 1 {
-                    self.last_sequence = chunk.sequence;
-                    return Some(chunk);
-                } else if chunk.sequence > self.last_sequence {
-                    // Re-queue for later
-                    let _ = self.chunks.push(chunk);
-                }
-            }
+    /// Everything written after `sequence`
+    pub fn since(&self, sequence: u64) -> Replay {
+        let first_retained = self
+            .chunks
+            .front()
+            .map(|c| c.sequence)
+            .unwrap_or(self.last_sequence.saturating_add(1));
+        
+        Replay {
+            chunks: self
+                .chunks
+                .iter()
+                .filter(|c| c.sequence > sequence)
+                .cloned()
+                .collect(),
+            // `sequence` comes from the client and may be anything
+            gap: sequence.saturating_add(1) < first_retained,
         }
-        None
     }
 }
 
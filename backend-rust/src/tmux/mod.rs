@@ -1,10 +1,16 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use std::path::PathBuf;
 use std::process::Stdio;
 use tokio::process::Command;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::types::{TmuxSession, TmuxWindow};
+use crate::types::{
+    PaneDirection, SplitDirection, TmuxConfigError, TmuxConfigReload, TmuxPane, TmuxSession, TmuxWindow,
+};
+
+/// Private session the monitor's control-mode client attaches to; hidden from clients
+pub const MONITOR_SESSION: &str = "__webmux_monitor__";
 
 fn escape_single_quotes(s: &str) -> String {
     s.replace('\'', "'\\''")
@@ -34,7 +40,7 @@ pub async fn ensure_tmux_server() -> Result<()> {
 }
 
 pub async fn list_sessions() -> Result<Vec<TmuxSession>> {
-    // Always use fallback for now - control mode needs more testing
+    // A one-off query; change notifications come from the monitor's control client
     list_sessions_fallback().await
 }
 
@@ -70,6 +76,9 @@ async fn list_sessions_fallback() -> Result<Vec<TmuxSession>> {
         .filter(|line| !line.is_empty())
         .filter_map(|line| {
             let parts: Vec<&str> = line.split(':').collect();
+            if parts[0] == MONITOR_SESSION {
+                return None;
+            }
             if parts.len() >= 5 {
                 let created_timestamp = parts[2].parse::<i64>().ok()?;
                 Some(TmuxSession {
@@ -301,6 +310,252 @@ pub async fn select_window(session_name: &str, window_index: &str) -> Result<()>
     Ok(())
 }
 
+// Pane management
+
+const PANE_FORMAT: &str = "#{pane_id}\t#{pane_index}\t#{window_index}\t#{pane_width}\t#{pane_height}\t#{pane_left}\t#{pane_top}\t#{pane_active}\t#{window_zoomed_flag}\t#{pane_dead}\t#{pane_dead_status}\t#{pane_pid}\t#{pane_current_command}\t#{pane_current_path}\t#{pane_title}";
+
+/// Run a tmux command, returning its stdout or failing with its stderr
+pub(crate) async fn run_tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux").args(args).output().await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("tmux {} failed: {}", args[0], stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn parse_pane(line: &str) -> Option<TmuxPane> {
+    // Title goes last since it is free text
+    let parts: Vec<&str> = line.splitn(15, '\t').collect();
+    if parts.len() < 15 {
+        return None;
+    }
+    Some(TmuxPane {
+        id: parts[0].to_string(),
+        index: parts[1].parse().ok()?,
+        window_index: parts[2].parse().ok()?,
+        width: parts[3].parse().unwrap_or(0),
+        height: parts[4].parse().unwrap_or(0),
+        left: parts[5].parse().unwrap_or(0),
+        top: parts[6].parse().unwrap_or(0),
+        active: parts[7] == "1",
+        zoomed: parts[8] == "1",
+        dead: parts[9] == "1",
+        dead_status: parts[10].parse().ok(),
+        pid: parts[11].parse().unwrap_or(0),
+        current_command: parts[12].to_string(),
+        current_path: parts[13].to_string(),
+        title: parts[14].to_string(),
+    })
+}
+
+/// List panes of one window, or of every window in the session when `window_index` is `None`
+pub async fn list_panes(session_name: &str, window_index: Option<u32>) -> Result<Vec<TmuxPane>> {
+    let stdout = match window_index {
+        Some(index) => {
+            let target = format!("{}:{}", session_name, index);
+            run_tmux(&["list-panes", "-t", &target, "-F", PANE_FORMAT]).await?
+        }
+        None => run_tmux(&["list-panes", "-s", "-t", session_name, "-F", PANE_FORMAT]).await?,
+    };
+
+    Ok(stdout.lines().filter(|line| !line.is_empty()).filter_map(parse_pane).collect())
+}
+
+/// Split `pane_id`, returning the id of the new pane
+pub async fn split_window(
+    pane_id: &str,
+    direction: SplitDirection,
+    percent: Option<u8>,
+    start_directory: Option<&str>,
+) -> Result<String> {
+    let mut args = vec!["split-window", "-t", pane_id, "-P", "-F", "#{pane_id}"];
+    args.push(match direction {
+        SplitDirection::Horizontal => "-h",
+        SplitDirection::Vertical => "-v",
+    });
+
+    let size = percent.map(|p| format!("{}%", p.clamp(1, 99)));
+    if let Some(ref size) = size {
+        args.push("-l");
+        args.push(size);
+    }
+
+    // Default to the directory of the pane being split, like create_window
+    let directory = match start_directory {
+        Some(dir) => Some(dir.to_string()),
+        None => get_current_pane_directory(pane_id).await.ok(),
+    };
+    if let Some(ref dir) = directory {
+        args.push("-c");
+        args.push(dir);
+    }
+
+    Ok(run_tmux(&args).await?.trim().to_string())
+}
+
+pub async fn kill_pane(pane_id: &str) -> Result<()> {
+    run_tmux(&["kill-pane", "-t", pane_id]).await?;
+    Ok(())
+}
+
+pub async fn select_pane(pane_id: &str) -> Result<()> {
+    run_tmux(&["select-pane", "-t", pane_id]).await?;
+    Ok(())
+}
+
+/// Resize to an absolute `width`/`height`, or by `amount` cells towards `direction`
+pub async fn resize_pane(
+    pane_id: &str,
+    width: Option<u32>,
+    height: Option<u32>,
+    direction: Option<PaneDirection>,
+    amount: Option<u32>,
+) -> Result<()> {
+    let mut args: Vec<String> = vec!["resize-pane".into(), "-t".into(), pane_id.into()];
+    if let Some(width) = width {
+        args.extend(["-x".into(), width.to_string()]);
+    }
+    if let Some(height) = height {
+        args.extend(["-y".into(), height.to_string()]);
+    }
+    if let Some(direction) = direction {
+        let flag = match direction {
+            PaneDirection::Up => "-U",
+            PaneDirection::Down => "-D",
+            PaneDirection::Left => "-L",
+            PaneDirection::Right => "-R",
+        };
+        args.push(flag.into());
+        args.push(amount.unwrap_or(1).to_string());
+    }
+    if args.len() == 3 {
+        anyhow::bail!("Resize needs a width, height or direction");
+    }
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    run_tmux(&args).await?;
+    Ok(())
+}
+
+pub async fn swap_pane(source_pane_id: &str, target_pane_id: &str) -> Result<()> {
+    run_tmux(&["swap-pane", "-s", source_pane_id, "-t", target_pane_id]).await?;
+    Ok(())
+}
+
+/// Toggle zoom of the pane within its window
+pub async fn zoom_pane(pane_id: &str) -> Result<()> {
+    run_tmux(&["resize-pane", "-Z", "-t", pane_id]).await?;
+    Ok(())
+}
+
+/// Move a pane into a new window of its own, returning the pane id
+pub async fn break_pane(pane_id: &str, window_name: Option<&str>) -> Result<String> {
+    let mut args = vec!["break-pane", "-s", pane_id, "-P", "-F", "#{pane_id}"];
+    if let Some(name) = window_name {
+        args.push("-n");
+        args.push(name);
+    }
+    Ok(run_tmux(&args).await?.trim().to_string())
+}
+
+/// Move `source_pane_id` next to `target_pane_id`, splitting the target
+pub async fn join_pane(
+    source_pane_id: &str,
+    target_pane_id: &str,
+    direction: SplitDirection,
+    percent: Option<u8>,
+) -> Result<()> {
+    let mut args = vec!["join-pane", "-s", source_pane_id, "-t", target_pane_id];
+    args.push(match direction {
+        SplitDirection::Horizontal => "-h",
+        SplitDirection::Vertical => "-v",
+    });
+
+    let size = percent.map(|p| format!("{}%", p.clamp(1, 99)));
+    if let Some(ref size) = size {
+        args.push("-l");
+        args.push(size);
+    }
+
+    run_tmux(&args).await?;
+    Ok(())
+}
+
+// Configuration
+
+/// The config file tmux loads at startup: ~/.tmux.conf, or the XDG location when only that exists
+pub fn default_config_path() -> Result<PathBuf> {
+    let home_dir = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
+    let classic = home_dir.join(".tmux.conf");
+    let xdg = dirs::config_dir()
+        .unwrap_or_else(|| home_dir.join(".config"))
+        .join("tmux/tmux.conf");
+    Ok(if !classic.exists() && xdg.exists() { xdg } else { classic })
+}
+
+/// Source a config file into the running server. tmux's complaints come back as
+/// errors with the file and line they refer to rather than as a failure.
+pub async fn reload_config(path: Option<&str>) -> Result<TmuxConfigReload> {
+    let path = match path {
+        Some(path) => match path.strip_prefix("~/") {
+            Some(relative) => dirs::home_dir()
+                .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?
+                .join(relative),
+            None => PathBuf::from(path),
+        },
+        None => default_config_path()?,
+    };
+    let path_str = path.to_string_lossy().to_string();
+
+    let output = Command::new("tmux")
+        .arg("source-file")
+        .arg(&path)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    // Parse errors are printed on stdout, others (no server, missing file) on stderr
+    let mut report = String::from_utf8_lossy(&output.stdout).to_string();
+    report.push_str(&String::from_utf8_lossy(&output.stderr));
+    let errors: Vec<TmuxConfigError> = report
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_config_error)
+        .collect();
+
+    let success = output.status.success() && errors.is_empty();
+    if success {
+        info!("Reloaded tmux config {}", path_str);
+    } else {
+        warn!("Reloading tmux config {} reported {} error(s)", path_str, errors.len());
+    }
+    Ok(TmuxConfigReload {
+        path: path_str,
+        success,
+        errors,
+    })
+}
+
+// `<file>:<line>: <message>`, or a bare message such as `no server running on ...`
+fn parse_config_error(line: &str) -> TmuxConfigError {
+    let located = line.split_once(':').and_then(|(file, rest)| {
+        let (number, message) = rest.split_once(':')?;
+        Some(TmuxConfigError {
+            file: Some(file.to_string()),
+            line: Some(number.parse().ok()?),
+            message: message.trim().to_string(),
+        })
+    });
+    located.unwrap_or_else(|| TmuxConfigError {
+        file: None,
+        line: None,
+        message: line.trim().to_string(),
+    })
+}
+
 // Alternative session management functions that avoid direct attachment
 
 pub async fn capture_pane(session_name: &str) -> Result<String> {
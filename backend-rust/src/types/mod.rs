@@ -21,6 +21,103 @@ pub struct TmuxWindow {
     pub panes: u32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TmuxPane {
+    // Server-wide pane id such as `%3`, used to address the pane
+    pub id: String,
+    pub index: u32,
+    pub window_index: u32,
+    pub width: u32,
+    pub height: u32,
+    // Position of the top-left corner within the window, in cells
+    pub left: u32,
+    pub top: u32,
+    pub active: bool,
+    // The pane's window is zoomed
+    pub zoomed: bool,
+    pub current_command: String,
+    pub current_path: String,
+    pub pid: u32,
+    pub title: String,
+    // The pane's process exited but the pane is kept (remain-on-exit)
+    pub dead: bool,
+    pub dead_status: Option<i32>,
+}
+
+/// Outcome of sourcing a tmux config file into the running server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TmuxConfigReload {
+    pub path: String,
+    pub success: bool,
+    pub errors: Vec<TmuxConfigError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TmuxConfigError {
+    // The config file or one it sources; absent for errors not tied to a file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+// Horizontal puts the new pane beside the old one, vertical below it (tmux -h / -v)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaneDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaneAction {
+    Split,
+    Kill,
+    Select,
+    Resize,
+    Swap,
+    Zoom,
+    Break,
+    Join,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncAction {
+    Pull,
+    Push,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SshHostAction {
+    Add,
+    Update,
+    Remove,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorizedKeyAction {
+    Add,
+    Update,
+    Remove,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateSessionRequest {
@@ -89,7 +186,12 @@ pub struct CronJob {
     pub environment: Option<HashMap<String, String>>,
     pub log_output: Option<bool>,
     pub email_to: Option<String>,
+    // Run in a window of this tmux session, created if missing
     pub tmux_session: Option<String>,
+    // Window reused for every run; a fresh window per run when unset
+    pub tmux_window: Option<String>,
+    // Leave a fresh window open when the run fails
+    pub keep_window_on_failure: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,13 +203,40 @@ pub enum WebSocketMessage {
         session_name: String,
         cols: u16,
         rows: u16,
+        // Terminal channel on this connection; omitted for the default channel
+        channel: Option<String>,
+    },
+    // Close one terminal channel without dropping the connection
+    DetachSession {
+        channel: Option<String>,
     },
     Input {
         data: String,
+        channel: Option<String>,
+    },
+    // Opt in to binary terminal frames for this connection
+    Negotiate {
+        #[serde(rename = "binaryFrames", default)]
+        binary_frames: bool,
+        // Prefix binary output with stream sequence numbers (0x03 frames)
+        #[serde(rename = "sequencedFrames", default)]
+        sequenced_frames: bool,
+    },
+    // Reattach to a terminal stream after reconnecting
+    Resume {
+        #[serde(rename = "resumeToken")]
+        resume_token: String,
+        // Last output sequence the client rendered; 0 replays everything retained
+        #[serde(rename = "lastSequence", default)]
+        last_sequence: u64,
+        cols: Option<u16>,
+        rows: Option<u16>,
+        channel: Option<String>,
     },
     Resize {
         cols: u16,
         rows: u16,
+        channel: Option<String>,
     },
     ListWindows {
         #[serde(rename = "sessionName")]
@@ -158,6 +287,66 @@ pub enum WebSocketMessage {
         #[serde(rename = "newName")]
         new_name: String,
     },
+    // Pane management; panes are addressed by their `%N` id
+    ListPanes {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        // Omit to list the panes of every window in the session
+        #[serde(rename = "windowIndex")]
+        window_index: Option<u32>,
+    },
+    SplitWindow {
+        #[serde(rename = "paneId")]
+        pane_id: String,
+        direction: SplitDirection,
+        percent: Option<u8>,
+        #[serde(rename = "startDirectory")]
+        start_directory: Option<String>,
+    },
+    KillPane {
+        #[serde(rename = "paneId")]
+        pane_id: String,
+    },
+    SelectPane {
+        #[serde(rename = "paneId")]
+        pane_id: String,
+    },
+    ResizePane {
+        #[serde(rename = "paneId")]
+        pane_id: String,
+        width: Option<u32>,
+        height: Option<u32>,
+        direction: Option<PaneDirection>,
+        amount: Option<u32>,
+    },
+    SwapPane {
+        #[serde(rename = "sourcePaneId")]
+        source_pane_id: String,
+        #[serde(rename = "targetPaneId")]
+        target_pane_id: String,
+    },
+    ZoomPane {
+        #[serde(rename = "paneId")]
+        pane_id: String,
+    },
+    BreakPane {
+        #[serde(rename = "paneId")]
+        pane_id: String,
+        #[serde(rename = "windowName")]
+        window_name: Option<String>,
+    },
+    JoinPane {
+        #[serde(rename = "sourcePaneId")]
+        source_pane_id: String,
+        #[serde(rename = "targetPaneId")]
+        target_pane_id: String,
+        direction: SplitDirection,
+        percent: Option<u8>,
+    },
+    // Source a config file into the running server; ~/.tmux.conf by default
+    ReloadTmuxConfig {
+        path: Option<String>,
+    },
     // System stats
     GetStats,
     // Cron management
@@ -179,23 +368,176 @@ pub enum WebSocketMessage {
     TestCronCommand {
         command: String,
     },
+    // Validate a schedule and list its next fire times
+    PreviewCronSchedule {
+        schedule: String,
+        count: Option<usize>,
+    },
+    // Recorded executions of a job, newest first, without their output
+    ListCronExecutions {
+        #[serde(rename = "jobId")]
+        job_id: String,
+        limit: Option<usize>,
+    },
+    GetCronExecution {
+        #[serde(rename = "jobId")]
+        job_id: String,
+        #[serde(rename = "executionId")]
+        execution_id: String,
+    },
+    // Every job when `jobId` is omitted
+    PruneCronExecutions {
+        #[serde(rename = "jobId")]
+        job_id: Option<String>,
+        #[serde(rename = "keepLast")]
+        keep_last: Option<usize>,
+        #[serde(rename = "olderThanDays")]
+        older_than_days: Option<u32>,
+    },
     // Dotfile management
     ListDotfiles,
     ReadDotfile {
         path: String,
+        // Send secret values even when dotfiles.mask_secrets is on
+        reveal: Option<bool>,
     },
     WriteDotfile {
         path: String,
         content: String,
+        // Commit message in git mode
+        message: Option<String>,
+        // Write even if the syntax check fails
+        force: Option<bool>,
+        // From `dotfile-content`; omitted when creating the file
+        etag: Option<String>,
+    },
+    // Syntax-check content without writing it
+    ValidateDotfile {
+        path: String,
+        content: String,
     },
     GetDotfileHistory {
         path: String,
+        // As for `ReadDotfile`
+        reveal: Option<bool>,
     },
+    // By timestamp, or by revision in git mode
     RestoreDotfileVersion {
         path: String,
-        timestamp: DateTime<Utc>,
+        timestamp: Option<DateTime<Utc>>,
+        revision: Option<String>,
+        // Restore even if the syntax check fails
+        force: Option<bool>,
+        // From `dotfile-content`; the restore is refused if the file changed since
+        etag: Option<String>,
     },
     GetDotfileTemplates,
+    // Render a template and write it (or only preview it with `dryRun`)
+    ApplyDotfileTemplate {
+        name: String,
+        // The template's target by default
+        path: Option<String>,
+        #[serde(default)]
+        values: std::collections::HashMap<String, serde_json::Value>,
+        #[serde(rename = "dryRun")]
+        dry_run: Option<bool>,
+        // Write even if the syntax check fails
+        force: Option<bool>,
+    },
+    // Line diff from `from` to `to`
+    DiffDotfile {
+        path: String,
+        from: crate::dotfiles::diff::DiffSide,
+        to: crate::dotfiles::diff::DiffSide,
+        // Unchanged lines around each change, 3 by default
+        context: Option<usize>,
+        // As for `ReadDotfile`
+        reveal: Option<bool>,
+    },
+    // User-tracked paths and globs
+    ListTrackedDotfiles,
+    TrackDotfile {
+        pattern: String,
+    },
+    UntrackDotfile {
+        pattern: String,
+    },
+    // Directory tree under $HOME, the home directory by default
+    BrowseDotfileDir {
+        path: Option<String>,
+        // Levels to descend, 1 (just the directory) by default
+        depth: Option<usize>,
+    },
+    // Structured `~/.ssh/config` editing; hosts are addressed by their index in `ssh-config`
+    GetSshConfig,
+    AddSshHost {
+        host: crate::dotfiles::ssh_config::SshHost,
+        // Index of the host to insert before
+        before: Option<usize>,
+        // From `ssh-config`; omitted when the file doesn't exist yet
+        etag: Option<String>,
+        // Write even if `ssh -G` rejects the result
+        force: Option<bool>,
+    },
+    UpdateSshHost {
+        index: usize,
+        host: crate::dotfiles::ssh_config::SshHost,
+        etag: Option<String>,
+        force: Option<bool>,
+    },
+    RemoveSshHost {
+        index: usize,
+        etag: Option<String>,
+        force: Option<bool>,
+    },
+    // `~/.ssh/authorized_keys`; keys are addressed by their index in `authorized-keys`
+    GetAuthorizedKeys,
+    AddAuthorizedKey {
+        // A public key line, e.g. the content of `id_ed25519.pub`
+        key: String,
+        // Replace the options and comment in `key`
+        options: Option<Vec<crate::dotfiles::authorized_keys::KeyOption>>,
+        comment: Option<String>,
+        // From `authorized-keys`; omitted when the file doesn't exist yet
+        etag: Option<String>,
+    },
+    UpdateAuthorizedKey {
+        index: usize,
+        #[serde(default)]
+        options: Vec<crate::dotfiles::authorized_keys::KeyOption>,
+        comment: Option<String>,
+        etag: Option<String>,
+    },
+    RemoveAuthorizedKey {
+        index: usize,
+        etag: Option<String>,
+    },
+    // Git mode
+    GetDotfilesStatus,
+    GetDotfilesGitDiff {
+        path: Option<String>,
+    },
+    PullDotfiles,
+    PushDotfiles,
+}
+
+/// Reply wrapper echoing the `requestId` of the client message that caused it
+#[derive(Debug, Serialize)]
+pub struct ServerEnvelope<'a> {
+    #[serde(flatten)]
+    pub message: &'a ServerMessage,
+    #[serde(rename = "requestId", skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProtocolErrorCode {
+    InvalidJson,
+    InvalidEnvelope,
+    UnknownType,
+    InvalidMessage,
+    InvalidFrame,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -214,11 +556,57 @@ pub enum ServerMessage {
     Attached {
         #[serde(rename = "sessionName")]
         session_name: String,
+        #[serde(rename = "resumeToken")]
+        resume_token: String,
+        sequence: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+        #[serde(rename = "streamId")]
+        stream_id: u32,
+    },
+    Detached {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
     },
     Output {
         data: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+    },
+    Negotiated {
+        #[serde(rename = "protocolVersion")]
+        protocol_version: u32,
+        #[serde(rename = "binaryFrames")]
+        binary_frames: bool,
+        #[serde(rename = "sequencedFrames")]
+        sequenced_frames: bool,
+    },
+    Resumed {
+        #[serde(rename = "resumeToken")]
+        resume_token: String,
+        // Latest sequence number; replayed output ends here
+        sequence: u64,
+        replayed: usize,
+        // Output after the client's lastSequence was evicted before it could be replayed
+        gap: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+        #[serde(rename = "streamId")]
+        stream_id: u32,
+    },
+    ResumeFailed {
+        #[serde(rename = "resumeToken")]
+        resume_token: String,
+        reason: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+    },
+    Disconnected {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
     },
-    Disconnected,
     WindowsList {
         #[serde(rename = "sessionName")]
         session_name: String,
@@ -274,6 +662,27 @@ pub enum ServerMessage {
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
     },
+    // Pane management responses
+    PanesList {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex", skip_serializing_if = "Option::is_none")]
+        window_index: Option<u32>,
+        panes: Vec<TmuxPane>,
+    },
+    PaneUpdated {
+        action: PaneAction,
+        success: bool,
+        // Pane created or moved by split/break
+        #[serde(rename = "paneId", skip_serializing_if = "Option::is_none")]
+        pane_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    TmuxConfigReloaded {
+        #[serde(flatten)]
+        reload: TmuxConfigReload,
+    },
     // System stats response
     Stats {
         stats: SystemStats,
@@ -282,6 +691,11 @@ pub enum ServerMessage {
     Error {
         message: String,
     },
+    // Client sent something the server could not interpret
+    ProtocolError {
+        code: ProtocolErrorCode,
+        message: String,
+    },
     // Cron management responses
     CronJobsList {
         jobs: Vec<CronJob>,
@@ -300,6 +714,31 @@ pub enum ServerMessage {
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
     },
+    CronSchedulePreview {
+        schedule: String,
+        // Timezone the schedule is evaluated in (`local (UTC+hh:mm)` for the server's time)
+        timezone: String,
+        // The server's local time, which the cron daemon actually fires jobs in
+        #[serde(rename = "daemonTimezone")]
+        daemon_timezone: String,
+        #[serde(rename = "nextRuns")]
+        next_runs: Vec<DateTime<Utc>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<crate::cron::schedule::ScheduleError>,
+    },
+    CronExecutionsList {
+        #[serde(rename = "jobId")]
+        job_id: String,
+        executions: Vec<crate::cron::JobExecution>,
+    },
+    CronExecution {
+        execution: crate::cron::JobExecution,
+    },
+    CronExecutionsPruned {
+        #[serde(rename = "jobId", skip_serializing_if = "Option::is_none")]
+        job_id: Option<String>,
+        removed: usize,
+    },
     // Dotfile management responses
     DotfilesList {
         files: Vec<crate::dotfiles::DotFile>,
@@ -307,6 +746,13 @@ pub enum ServerMessage {
     DotfileContent {
         path: String,
         content: String,
+        // Secrets found in the content, without their values
+        secrets: Vec<crate::dotfiles::secrets::SecretFinding>,
+        // Secret values in `content` were replaced by a placeholder
+        masked: bool,
+        // Hash of the content on disk, for `write-dotfile`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        etag: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
     },
@@ -315,6 +761,32 @@ pub enum ServerMessage {
         success: bool,
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
+        // Why the syntax check refused the write
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diagnostics: Option<Vec<crate::dotfiles::validate::Diagnostic>>,
+        // Set when the written tmux config was reloaded (dotfiles.reload_tmux_on_write)
+        #[serde(rename = "tmuxReload", skip_serializing_if = "Option::is_none")]
+        tmux_reload: Option<TmuxConfigReload>,
+        // Of the written content, for the next write
+        #[serde(skip_serializing_if = "Option::is_none")]
+        etag: Option<String>,
+        // The file changed since it was read; nothing was written
+        #[serde(skip_serializing_if = "Option::is_none")]
+        conflict: Option<crate::dotfiles::merge::WriteConflict>,
+    },
+    // Broadcast when a tracked dotfile is edited, created or deleted outside webmux
+    DotfileChanged {
+        name: String,
+        path: String,
+        change: crate::dotfiles::watch::DotfileChange,
+        // Of the new content; absent when the file was deleted
+        #[serde(skip_serializing_if = "Option::is_none")]
+        etag: Option<String>,
+    },
+    DotfileValidation {
+        path: String,
+        #[serde(flatten)]
+        validation: crate::dotfiles::validate::Validation,
     },
     DotfileHistory {
         path: String,
@@ -325,10 +797,90 @@ pub enum ServerMessage {
         success: bool,
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
+        // Why the syntax check refused the restore
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diagnostics: Option<Vec<crate::dotfiles::validate::Diagnostic>>,
+        // The file changed since it was read; nothing was written
+        #[serde(skip_serializing_if = "Option::is_none")]
+        conflict: Option<crate::dotfiles::merge::WriteConflict>,
     },
     DotfileTemplates {
         templates: Vec<crate::dotfiles::DotFileTemplate>,
     },
+    DotfileTemplateApplied {
+        name: String,
+        success: bool,
+        #[serde(flatten)]
+        application: Option<crate::dotfiles::template::TemplateApplication>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diagnostics: Option<Vec<crate::dotfiles::validate::Diagnostic>>,
+    },
+    DotfileDiff {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diff: Option<crate::dotfiles::diff::FileDiff>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    SshConfig {
+        #[serde(flatten)]
+        config: crate::dotfiles::ssh_config::SshConfigView,
+    },
+    SshConfigUpdated {
+        action: SshHostAction,
+        success: bool,
+        // The file after the edit
+        #[serde(skip_serializing_if = "Option::is_none")]
+        config: Option<crate::dotfiles::ssh_config::SshConfigView>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        // Why `ssh -G` refused the result
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diagnostics: Option<Vec<crate::dotfiles::validate::Diagnostic>>,
+        // The file changed since it was read; nothing was written
+        #[serde(skip_serializing_if = "Option::is_none")]
+        conflict: Option<crate::dotfiles::merge::WriteConflict>,
+    },
+    AuthorizedKeys {
+        #[serde(flatten)]
+        keys: crate::dotfiles::authorized_keys::AuthorizedKeysView,
+    },
+    AuthorizedKeysUpdated {
+        action: AuthorizedKeyAction,
+        success: bool,
+        // The file after the edit
+        #[serde(skip_serializing_if = "Option::is_none")]
+        keys: Option<crate::dotfiles::authorized_keys::AuthorizedKeysView>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        // The file changed since it was read; nothing was written
+        #[serde(skip_serializing_if = "Option::is_none")]
+        conflict: Option<crate::dotfiles::merge::WriteConflict>,
+    },
+    TrackedDotfiles {
+        patterns: Vec<crate::dotfiles::tracked::TrackedPattern>,
+    },
+    DotfileDirListing {
+        #[serde(flatten)]
+        listing: crate::dotfiles::browse::DirListing,
+    },
+    DotfilesStatus {
+        status: crate::dotfiles::git::GitStatus,
+    },
+    DotfilesGitDiff {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        diff: String,
+    },
+    DotfilesSynced {
+        action: SyncAction,
+        success: bool,
+        output: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize)]
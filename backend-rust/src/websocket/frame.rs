@@ -0,0 +1,106 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use thiserror::Error;
+
+/// Binary frames carry terminal I/O without JSON/UTF-8 overhead:
+/// `[type: u8][payload length: u32 LE][payload]`
+pub const FRAME_HEADER_LEN: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FrameType {
+    /// Server -> client raw PTY output
+    Output = 0x01,
+    /// Client -> server raw keyboard input
+    Input = 0x02,
+    /// Server -> client PTY output prefixed with its u64 LE stream sequence number
+    SequencedOutput = 0x03,
+    /// Server -> client output of a named channel: u32 LE stream id, u64 LE sequence, data
+    ChannelOutput = 0x04,
+    /// Client -> server input for a named channel: u32 LE stream id, data
+    ChannelInput = 0x05,
+}
+
+impl TryFrom<u8> for FrameType {
+    type Error = FrameError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(FrameType::Output),
+            0x02 => Ok(FrameType::Input),
+            0x03 => Ok(FrameType::SequencedOutput),
+            0x04 => Ok(FrameType::ChannelOutput),
+            0x05 => Ok(FrameType::ChannelInput),
+            other => Err(FrameError::UnknownType(other)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("Frame shorter than the {FRAME_HEADER_LEN}-byte header")]
+    Truncated,
+    #[error("Channel frame payload shorter than its 4-byte stream id")]
+    MissingStreamId,
+    #[error("Unknown frame type 0x{0:02x}")]
+    UnknownType(u8),
+    #[error("Frame length {declared} does not match payload length {actual}")]
+    LengthMismatch { declared: usize, actual: usize },
+}
+
+#[derive(Debug)]
+pub struct Frame<'a> {
+    pub frame_type: FrameType,
+    pub payload: &'a [u8],
+}
+
+pub fn encode(frame_type: FrameType, payload: &[u8]) -> Bytes {
+    let mut buffer = BytesMut::with_capacity(FRAME_HEADER_LEN + payload.len());
+    buffer.put_u8(frame_type as u8);
+    buffer.put_u32_le(payload.len() as u32);
+    buffer.extend_from_slice(payload);
+    buffer.freeze()
+}
+
+pub fn encode_sequenced(sequence: u64, payload: &[u8]) -> Bytes {
+    let mut buffer = BytesMut::with_capacity(FRAME_HEADER_LEN + 8 + payload.len());
+    buffer.put_u8(FrameType::SequencedOutput as u8);
+    buffer.put_u32_le((payload.len() + 8) as u32);
+    buffer.put_u64_le(sequence);
+    buffer.extend_from_slice(payload);
+    buffer.freeze()
+}
+
+pub fn encode_channel(stream_id: u32, sequence: u64, payload: &[u8]) -> Bytes {
+    let mut buffer = BytesMut::with_capacity(FRAME_HEADER_LEN + 12 + payload.len());
+    buffer.put_u8(FrameType::ChannelOutput as u8);
+    buffer.put_u32_le((payload.len() + 12) as u32);
+    buffer.put_u32_le(stream_id);
+    buffer.put_u64_le(sequence);
+    buffer.extend_from_slice(payload);
+    buffer.freeze()
+}
+
+/// Split a `ChannelInput` payload into its stream id and data
+pub fn split_stream_id(payload: &[u8]) -> Result<(u32, &[u8]), FrameError> {
+    if payload.len() < 4 {
+        return Err(FrameError::MissingStreamId);
+    }
+    let stream_id = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    Ok((stream_id, &payload[4..]))
+}
+
+pub fn decode(data: &[u8]) -> Result<Frame<'_>, FrameError> {
+    if data.len() < FRAME_HEADER_LEN {
+        return Err(FrameError::Truncated);
+    }
+    let frame_type = FrameType::try_from(data[0])?;
+    let declared = u32::from_le_bytes([data[1], data[2], data[3], data[4]]) as usize;
+    let payload = &data[FRAME_HEADER_LEN..];
+    if declared != payload.len() {
+        return Err(FrameError::LengthMismatch {
+            declared,
+            actual: payload.len(),
+        });
+    }
+    Ok(Frame { frame_type, payload })
+}
@@ -2,41 +2,65 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Query, State,
     },
-    response::IntoResponse,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
 };
 use futures::{sink::SinkExt, stream::StreamExt};
-use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use std::{
-    sync::Arc,
-    io::{Read, Write},
+    sync::{atomic::Ordering, Arc},
     collections::HashMap,
 };
-use tokio::{
-    sync::{mpsc, Mutex, RwLock},
-    task::JoinHandle,
-};
+use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 use bytes::Bytes;
 
 use crate::{
     audio,
+    config::{Feature, FeatureConfig},
+    dotfiles::{authorized_keys::AuthorizedKeyEdit, ssh_config::SshHostEdit},
     tmux,
     types::*,
-    terminal_buffer::TerminalRingBuffer,
     AppState,
 };
 use sysinfo::System;
 
+pub mod frame;
+pub mod stream;
+
+use stream::{ChannelTag, OutputOptions, StreamRegistry, StreamSubscriber, TerminalStream};
+
 type ClientId = String;
 
+// Version of the WebSocket protocol reported during negotiation
+const PROTOCOL_VERSION: u32 = 1;
+
 // Pre-serialized message for zero-copy broadcasting
 #[derive(Clone)]
 pub enum BroadcastMessage {
     Text(Arc<String>),
     Binary(Bytes),
+    // Terminal output, kept apart so the writer can throttle it
+    Output(TerminalOutput),
+}
+
+#[derive(Clone)]
+pub enum TerminalOutput {
+    // Legacy `{"type":"output"}` message
+    Json(Arc<String>),
+    // Type-tagged binary frame (see `frame`)
+    Binary(Bytes),
+}
+
+impl TerminalOutput {
+    fn len(&self) -> usize {
+        match self {
+            TerminalOutput::Json(json) => json.len(),
+            TerminalOutput::Binary(data) => data.len(),
+        }
+    }
 }
 
 // Client manager for broadcasting messages to all connected clients
@@ -87,27 +111,40 @@ impl ClientManager {
     }
 }
 
-struct PtySession {
-    writer: Arc<Mutex<Box<dyn Write + Send>>>,
-    master: Arc<Mutex<Box<dyn portable_pty::MasterPty + Send>>>,
-    reader_task: JoinHandle<()>,
-    child: Arc<Mutex<Box<dyn portable_pty::Child + Send>>>,
-    tmux_session: String,
-    terminal_buffer: Arc<Mutex<TerminalRingBuffer>>,
+// Upper bound on terminal channels a single connection may hold open
+const MAX_CHANNELS: usize = 8;
+
+struct Channel {
+    stream: Arc<TerminalStream>,
+    stream_id: u32,
 }
 
 struct WsState {
     client_id: ClientId,
-    current_pty: Arc<Mutex<Option<PtySession>>>,
-    current_session: Arc<Mutex<Option<String>>>,
+    streams: Arc<StreamRegistry>,
+    // Attached terminals keyed by channel name (`None` is the default channel)
+    channels: HashMap<Option<String>, Channel>,
+    next_stream_id: u32,
     audio_tx: Option<mpsc::UnboundedSender<BroadcastMessage>>,
     message_tx: mpsc::UnboundedSender<BroadcastMessage>,
+    features: FeatureConfig,
+    // `requestId` of the client message currently being handled
+    request_id: Option<String>,
+    // Output format the client negotiated (shared with the PTY reader)
+    output_options: Arc<OutputOptions>,
 }
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
+) -> Response {
+    // Reject before upgrading so unauthenticated clients never reach a shell
+    if let Err(e) = state.auth.authorize(&headers, params.get("token").map(String::as_str)).await {
+        info!("Rejected unauthenticated WebSocket connection: {}", e);
+        return e.into_response();
+    }
     ws.on_upgrade(|socket| handle_socket(socket, state))
 }
 
@@ -125,10 +162,14 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     
     let mut ws_state = WsState {
         client_id: client_id.clone(),
-        current_pty: Arc::new(Mutex::new(None)),
-        current_session: Arc::new(Mutex::new(None)),
+        streams: state.streams.clone(),
+        channels: HashMap::new(),
+        next_stream_id: 1,
         audio_tx: None,
         message_tx: tx.clone(),
+        features: state.config.features.clone(),
+        request_id: None,
+        output_options: Arc::new(OutputOptions::default()),
     };
     
     // Clone client_id for the spawned task
@@ -144,10 +185,6 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                         error!("Failed to send message to WebSocket: {}", e);
                         break;
                     }
-                    // Add small delay to prevent flooding
-                    if json.contains("\"type\":\"output\"") && json.len() > 1000 {
-                        tokio::time::sleep(tokio::time::Duration::from_micros(100)).await;
-                    }
                 }
                 BroadcastMessage::Binary(data) => {
                     if let Err(e) = sender.send(Message::Binary(data.to_vec())).await {
@@ -155,6 +192,21 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                         break;
                     }
                 }
+                BroadcastMessage::Output(output) => {
+                    let len = output.len();
+                    let result = match output {
+                        TerminalOutput::Json(json) => sender.send(Message::Text(json.to_string())).await,
+                        TerminalOutput::Binary(data) => sender.send(Message::Binary(data.to_vec())).await,
+                    };
+                    if let Err(e) = result {
+                        error!("Failed to send output to WebSocket: {}", e);
+                        break;
+                    }
+                    // Add small delay to prevent flooding
+                    if len > 1000 {
+                        tokio::time::sleep(tokio::time::Duration::from_micros(100)).await;
+                    }
+                }
             }
         }
     });
@@ -163,12 +215,33 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     while let Some(Ok(msg)) = receiver.next().await {
         match msg {
             Message::Text(text) => {
-                if let Ok(ws_msg) = serde_json::from_str::<WebSocketMessage>(&text) {
-                    if let Err(e) = handle_message(ws_msg, &mut ws_state).await {
-                        error!("Error handling message: {}", e);
+                match parse_client_message(&text) {
+                    Ok((request_id, ws_msg)) => {
+                        ws_state.request_id = request_id;
+                        if let Err(e) = handle_message(ws_msg, &mut ws_state).await {
+                            error!("Error handling message: {}", e);
+                            let response = ServerMessage::Error { message: e.to_string() };
+                            let _ = reply(&ws_state, response).await;
+                        }
+                        ws_state.request_id = None;
+                    }
+                    Err((request_id, code, message)) => {
+                        debug!("Rejecting client message ({:?}): {}", code, message);
+                        let response = ServerMessage::ProtocolError { code, message };
+                        let _ = send_with_request_id(&ws_state.message_tx, &response, request_id.as_deref());
                     }
                 }
             }
+            Message::Binary(data) => {
+                if let Err(e) = handle_binary_frame(&data, &ws_state).await {
+                    debug!("Rejecting binary frame: {}", e);
+                    let response = ServerMessage::ProtocolError {
+                        code: ProtocolErrorCode::InvalidFrame,
+                        message: e.to_string(),
+                    };
+                    let _ = send_with_request_id(&ws_state.message_tx, &response, None);
+                }
+            }
             Message::Close(_) => {
                 info!("WebSocket connection closed: {}", client_id);
                 break;
@@ -180,7 +253,7 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     }
 
     // Cleanup
-    cleanup_session(&ws_state).await;
+    cleanup_session(&mut ws_state).await;
     state.client_manager.remove_client(&client_id).await;
 }
 
@@ -188,47 +261,91 @@ async fn handle_message(
     msg: WebSocketMessage,
     state: &mut WsState,
 ) -> anyhow::Result<()> {
+    if let Some(feature) = required_feature(&msg) {
+        if !state.features.is_enabled(feature) {
+            let response = ServerMessage::Error {
+                message: format!("The {:?} feature is disabled on this server", feature),
+            };
+            return reply(state, response).await;
+        }
+    }
+
     match msg {
         WebSocketMessage::ListSessions => {
             let sessions = tmux::list_sessions().await.unwrap_or_default();
             let response = ServerMessage::SessionsList { sessions };
-            send_message(&state.message_tx, response).await?;
+            reply(state, response).await?;
         }
         
-        WebSocketMessage::AttachSession { session_name, cols, rows } => {
-            info!("Attaching to session: {}", session_name);
-            attach_to_session(state, &session_name, cols, rows).await?;
+        WebSocketMessage::AttachSession { session_name, cols, rows, channel } => {
+            info!("Attaching to session: {} (channel {:?})", session_name, channel);
+            attach_to_session(state, channel, &session_name, cols, rows).await?;
         }
-        
-        WebSocketMessage::Input { data } => {
-            let pty_opt = state.current_pty.lock().await;
-            if let Some(ref pty) = *pty_opt {
-                let mut writer = pty.writer.lock().await;
-                if let Err(e) = writer.write_all(data.as_bytes()) {
-                    error!("Failed to write to PTY: {}", e);
-                    return Err(e.into());
-                }
-                writer.flush()?;
-            } else {
-                debug!("No PTY session active, ignoring input");
+
+        WebSocketMessage::DetachSession { channel } => {
+            match state.channels.remove(&channel) {
+                Some(detached) => {
+                    info!("Detaching channel {:?} from session {}", channel, detached.stream.session_name);
+                    state.streams.release(&detached.stream, &state.client_id).await;
+                    reply(state, ServerMessage::Detached { channel }).await?;
+                }
+                None => {
+                    let response = ServerMessage::Error {
+                        message: format!("No session attached on channel {}", channel.as_deref().unwrap_or("default")),
+                    };
+                    reply(state, response).await?;
+                }
             }
         }
         
-        WebSocketMessage::Resize { cols, rows } => {
-            let pty_opt = state.current_pty.lock().await;
-            if let Some(ref pty) = *pty_opt {
-                let master = pty.master.lock().await;
-                master.resize(PtySize {
-                    rows,
-                    cols,
-                    pixel_width: 0,
-                    pixel_height: 0,
-                })?;
-                debug!("Resized PTY to {}x{}", cols, rows);
+        WebSocketMessage::Input { data, channel } => {
+            write_input(state, &channel, data.as_bytes()).await?;
+        }
+
+        WebSocketMessage::Negotiate { binary_frames, sequenced_frames } => {
+            // Sequence numbers only exist on binary output frames
+            let sequenced_frames = binary_frames && sequenced_frames;
+            state.output_options.binary.store(binary_frames, Ordering::Relaxed);
+            state.output_options.sequenced.store(sequenced_frames, Ordering::Relaxed);
+            info!(
+                "Client {} negotiated binary frames: {}, sequenced: {}",
+                state.client_id, binary_frames, sequenced_frames
+            );
+            let response = ServerMessage::Negotiated {
+                protocol_version: PROTOCOL_VERSION,
+                binary_frames,
+                sequenced_frames,
+            };
+            reply(state, response).await?;
+        }
+        
+        WebSocketMessage::Resize { cols, rows, channel } => {
+            if let Some(stream) = channel_stream(state, &channel) {
+                stream.resize(cols, rows).await?;
             } else {
                 debug!("No PTY session active, ignoring resize");
             }
         }
+
+        WebSocketMessage::Resume { resume_token, last_sequence, cols, rows, channel } => {
+            info!("Resuming terminal stream for client {} (channel {:?})", state.client_id, channel);
+            match resume_stream(state, channel.clone(), &resume_token, last_sequence).await {
+                Ok(stream) => {
+                    if let (Some(cols), Some(rows)) = (cols, rows) {
+                        stream.resize(cols, rows).await?;
+                    }
+                }
+                Err(e) => {
+                    info!("Failed to resume terminal stream: {}", e);
+                    let response = ServerMessage::ResumeFailed {
+                        resume_token,
+                        reason: e.to_string(),
+                        channel,
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
         
         WebSocketMessage::ListWindows { session_name } => {
             debug!("Listing windows for session: {}", session_name);
@@ -238,14 +355,14 @@ async fn handle_message(
                         session_name: session_name.clone(),
                         windows 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     error!("Failed to list windows for session {}: {}", session_name, e);
                     let response = ServerMessage::Error {
                         message: format!("Failed to list windows: {}", e),
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -254,12 +371,11 @@ async fn handle_message(
             debug!("Selecting window {} in session {}", window_index, session_name);
             
             // First, ensure we're in the right session
-            let current_session = state.current_session.lock().await;
-            if current_session.as_ref() != Some(&session_name) {
-                drop(current_session);
+            let attached = state.channels.values().any(|c| c.stream.session_name == session_name);
+            if !attached {
                 // Need to switch sessions first
                 info!("Switching to session {} before selecting window", session_name);
-                attach_to_session(state, &session_name, 80, 24).await?;
+                attach_to_session(state, None, &session_name, 80, 24).await?;
             }
             
             // Now select the window using tmux command
@@ -273,7 +389,7 @@ async fn handle_message(
                         window_index: Some(window_index),
                         error: None,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                     
                     // Don't broadcast windows list - let frontend handle refreshing
                 }
@@ -283,13 +399,13 @@ async fn handle_message(
                         window_index: None,
                         error: Some(e.to_string()),
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
         
         WebSocketMessage::Ping => {
-            send_message(&state.message_tx, ServerMessage::Pong).await?;
+            reply(state, ServerMessage::Pong).await?;
         }
         
         WebSocketMessage::AudioControl { action } => {
@@ -324,7 +440,7 @@ async fn handle_message(
                         session_name: Some(session_name),
                         error: None,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     error!("Failed to create session: {}", e);
@@ -333,7 +449,7 @@ async fn handle_message(
                         session_name: None,
                         error: Some(format!("Failed to create session: {}", e)),
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -348,7 +464,7 @@ async fn handle_message(
                         success: true,
                         error: None,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     error!("Failed to kill session: {}", e);
@@ -356,7 +472,7 @@ async fn handle_message(
                         success: false,
                         error: Some(format!("Failed to kill session: {}", e)),
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -367,7 +483,7 @@ async fn handle_message(
                     success: false,
                     error: Some("Session name cannot be empty".to_string()),
                 };
-                send_message(&state.message_tx, response).await?;
+                reply(state, response).await?;
             } else {
                 match tmux::rename_session(&session_name, &new_name).await {
                     Ok(_) => {
@@ -375,14 +491,14 @@ async fn handle_message(
                             success: true,
                             error: None,
                         };
-                        send_message(&state.message_tx, response).await?;
+                        reply(state, response).await?;
                     }
                     Err(e) => {
                         let response = ServerMessage::SessionRenamed {
                             success: false,
                             error: Some(format!("Failed to rename session: {}", e)),
                         };
-                        send_message(&state.message_tx, response).await?;
+                        reply(state, response).await?;
                     }
                 }
             }
@@ -396,14 +512,14 @@ async fn handle_message(
                         success: true,
                         error: None,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::WindowCreated {
                         success: false,
                         error: Some(format!("Failed to create window: {}", e)),
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -415,14 +531,14 @@ async fn handle_message(
                         success: true,
                         error: None,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::WindowKilled {
                         success: false,
                         error: Some(format!("Failed to kill window: {}", e)),
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -433,7 +549,7 @@ async fn handle_message(
                     success: false,
                     error: Some("Window name cannot be empty".to_string()),
                 };
-                send_message(&state.message_tx, response).await?;
+                reply(state, response).await?;
             } else {
                 match tmux::rename_window(&session_name, &window_index, &new_name).await {
                     Ok(_) => {
@@ -441,19 +557,90 @@ async fn handle_message(
                             success: true,
                             error: None,
                         };
-                        send_message(&state.message_tx, response).await?;
+                        reply(state, response).await?;
                     }
                     Err(e) => {
                         let response = ServerMessage::WindowRenamed {
                             success: false,
                             error: Some(format!("Failed to rename window: {}", e)),
                         };
-                        send_message(&state.message_tx, response).await?;
+                        reply(state, response).await?;
                     }
                 }
             }
         }
         
+        // Pane management
+        WebSocketMessage::ListPanes { session_name, window_index } => {
+            match tmux::list_panes(&session_name, window_index).await {
+                Ok(panes) => {
+                    let response = ServerMessage::PanesList { session_name, window_index, panes };
+                    reply(state, response).await?;
+                }
+                Err(e) => {
+                    error!("Failed to list panes for session {}: {}", session_name, e);
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to list panes: {}", e),
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::SplitWindow { pane_id, direction, percent, start_directory } => {
+            let result = tmux::split_window(&pane_id, direction, percent, start_directory.as_deref()).await;
+            reply_pane_updated(state, PaneAction::Split, result.map(Some)).await?;
+        }
+
+        WebSocketMessage::KillPane { pane_id } => {
+            let result = tmux::kill_pane(&pane_id).await;
+            reply_pane_updated(state, PaneAction::Kill, result.map(|_| None)).await?;
+        }
+
+        WebSocketMessage::SelectPane { pane_id } => {
+            let result = tmux::select_pane(&pane_id).await;
+            reply_pane_updated(state, PaneAction::Select, result.map(|_| None)).await?;
+        }
+
+        WebSocketMessage::ResizePane { pane_id, width, height, direction, amount } => {
+            let result = tmux::resize_pane(&pane_id, width, height, direction, amount).await;
+            reply_pane_updated(state, PaneAction::Resize, result.map(|_| None)).await?;
+        }
+
+        WebSocketMessage::SwapPane { source_pane_id, target_pane_id } => {
+            let result = tmux::swap_pane(&source_pane_id, &target_pane_id).await;
+            reply_pane_updated(state, PaneAction::Swap, result.map(|_| None)).await?;
+        }
+
+        WebSocketMessage::ZoomPane { pane_id } => {
+            let result = tmux::zoom_pane(&pane_id).await;
+            reply_pane_updated(state, PaneAction::Zoom, result.map(|_| None)).await?;
+        }
+
+        WebSocketMessage::BreakPane { pane_id, window_name } => {
+            let result = tmux::break_pane(&pane_id, window_name.as_deref()).await;
+            reply_pane_updated(state, PaneAction::Break, result.map(Some)).await?;
+        }
+
+        WebSocketMessage::JoinPane { source_pane_id, target_pane_id, direction, percent } => {
+            let result = tmux::join_pane(&source_pane_id, &target_pane_id, direction, percent).await;
+            reply_pane_updated(state, PaneAction::Join, result.map(|_| None)).await?;
+        }
+        
+        WebSocketMessage::ReloadTmuxConfig { path } => {
+            match tmux::reload_config(path.as_deref()).await {
+                Ok(reload) => {
+                    reply(state, ServerMessage::TmuxConfigReloaded { reload }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to reload tmux config: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
         // System stats
         WebSocketMessage::GetStats => {
             let mut sys = System::new_all();
@@ -480,27 +667,27 @@ async fn handle_message(
             };
 
             let response = ServerMessage::Stats { stats };
-            send_message(&state.message_tx, response).await?;
+            reply(state, response).await?;
         }
         
         // Cron management
         WebSocketMessage::ListCronJobs => {
             let jobs = crate::cron::CRON_MANAGER.list_jobs().await;
             let response = ServerMessage::CronJobsList { jobs };
-            send_message(&state.message_tx, response).await?;
+            reply(state, response).await?;
         }
         
         WebSocketMessage::CreateCronJob { job } => {
             match crate::cron::CRON_MANAGER.create_job(job).await {
                 Ok(created_job) => {
                     let response = ServerMessage::CronJobCreated { job: created_job };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::Error { 
                         message: format!("Failed to create cron job: {}", e) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -509,13 +696,13 @@ async fn handle_message(
             match crate::cron::CRON_MANAGER.update_job(id, job).await {
                 Ok(updated_job) => {
                     let response = ServerMessage::CronJobUpdated { job: updated_job };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::Error { 
                         message: format!("Failed to update cron job: {}", e) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -524,13 +711,13 @@ async fn handle_message(
             match crate::cron::CRON_MANAGER.delete_job(&id).await {
                 Ok(_) => {
                     let response = ServerMessage::CronJobDeleted { id };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::Error { 
                         message: format!("Failed to delete cron job: {}", e) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -539,13 +726,13 @@ async fn handle_message(
             match crate::cron::CRON_MANAGER.toggle_job(&id, enabled).await {
                 Ok(toggled_job) => {
                     let response = ServerMessage::CronJobUpdated { job: toggled_job };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::Error { 
                         message: format!("Failed to toggle cron job: {}", e) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -557,14 +744,84 @@ async fn handle_message(
                         output, 
                         error: None 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::CronCommandOutput { 
                         output: String::new(),
                         error: Some(format!("Failed to test command: {}", e)) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
+                }
+            }
+        }
+        
+        WebSocketMessage::PreviewCronSchedule { schedule, count } => {
+            let count = count.unwrap_or(5).clamp(1, 100);
+            let (timezone, runs) = crate::cron::CRON_MANAGER.preview_schedule(&schedule, count);
+            let (next_runs, error) = match runs {
+                Ok(runs) => (runs, None),
+                Err(e) => (Vec::new(), Some(e)),
+            };
+            let response = ServerMessage::CronSchedulePreview {
+                schedule,
+                timezone,
+                daemon_timezone: crate::cron::daemon_timezone(),
+                next_runs,
+                error,
+            };
+            reply(state, response).await?;
+        }
+
+        WebSocketMessage::ListCronExecutions { job_id, limit } => {
+            let limit = limit.unwrap_or(50).clamp(1, 1000);
+            match crate::cron::CRON_MANAGER.get_job_history(&job_id, limit) {
+                Ok(mut executions) => {
+                    // Output can be large; fetch it per execution with GetCronExecution
+                    for execution in &mut executions {
+                        execution.output = None;
+                        execution.error = None;
+                    }
+                    let response = ServerMessage::CronExecutionsList { job_id, executions };
+                    reply(state, response).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to list cron executions: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::GetCronExecution { job_id, execution_id } => {
+            match crate::cron::CRON_MANAGER.get_execution(&job_id, &execution_id) {
+                Ok(execution) => {
+                    let response = ServerMessage::CronExecution { execution };
+                    reply(state, response).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to get cron execution: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::PruneCronExecutions { job_id, keep_last, older_than_days } => {
+            let older_than = older_than_days
+                .map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
+            match crate::cron::CRON_MANAGER.prune_history(job_id.as_deref(), keep_last, older_than) {
+                Ok(removed) => {
+                    let response = ServerMessage::CronExecutionsPruned { job_id, removed };
+                    reply(state, response).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to prune cron executions: {}", e)
+                    };
+                    reply(state, response).await?;
                 }
             }
         }
@@ -574,91 +831,155 @@ async fn handle_message(
             match crate::dotfiles::DOTFILES_MANAGER.list_dotfiles().await {
                 Ok(files) => {
                     let response = ServerMessage::DotfilesList { files };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::Error { 
                         message: format!("Failed to list dotfiles: {}", e) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
         
-        WebSocketMessage::ReadDotfile { path } => {
+        WebSocketMessage::ReadDotfile { path, reveal } => {
             match crate::dotfiles::DOTFILES_MANAGER.read_dotfile(&path).await {
                 Ok(content) => {
+                    let mask = !reveal.unwrap_or(false) && crate::dotfiles::DOTFILES_MANAGER.masks_secrets();
+                    let etag = crate::dotfiles::history::content_hash(&content);
+                    let redacted = crate::dotfiles::secrets::redact(content, mask);
                     let response = ServerMessage::DotfileContent { 
                         path, 
-                        content,
+                        content: redacted.content,
+                        secrets: redacted.secrets,
+                        masked: redacted.masked,
+                        etag: Some(etag),
                         error: None 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::DotfileContent { 
                         path,
                         content: String::new(),
+                        secrets: Vec::new(),
+                        masked: false,
+                        etag: None,
                         error: Some(format!("{}", e)) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
         
-        WebSocketMessage::WriteDotfile { path, content } => {
-            match crate::dotfiles::DOTFILES_MANAGER.write_dotfile(&path, &content).await {
+        WebSocketMessage::WriteDotfile { path, content, message, force, etag } => {
+            let force = force.unwrap_or(false);
+            let written = crate::dotfiles::DOTFILES_MANAGER
+                .write_dotfile(&path, &content, message.as_deref(), force, etag.as_deref())
+                .await;
+            match written {
                 Ok(_) => {
+                    let tmux_reload = match crate::dotfiles::DOTFILES_MANAGER.tmux_reload_path(&path) {
+                        Some(config_path) => match tmux::reload_config(Some(&config_path)).await {
+                            Ok(reload) => Some(reload),
+                            Err(e) => {
+                                error!("Failed to reload tmux config {}: {}", config_path, e);
+                                None
+                            }
+                        },
+                        None => None,
+                    };
                     let response = ServerMessage::DotfileWritten { 
                         path,
                         success: true,
-                        error: None 
+                        error: None,
+                        diagnostics: None,
+                        tmux_reload,
+                        etag: Some(crate::dotfiles::history::content_hash(&content)),
+                        conflict: None,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
+                    let diagnostics = e
+                        .downcast_ref::<crate::dotfiles::validate::ValidationError>()
+                        .map(|e| e.validation.diagnostics.clone());
+                    let conflict = write_conflict(&e);
                     let response = ServerMessage::DotfileWritten { 
                         path,
                         success: false,
-                        error: Some(format!("{}", e)) 
+                        error: Some(format!("{}", e)),
+                        diagnostics,
+                        tmux_reload: None,
+                        etag: None,
+                        conflict,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
         
-        WebSocketMessage::GetDotfileHistory { path } => {
+        WebSocketMessage::ValidateDotfile { path, content } => {
+            match crate::dotfiles::DOTFILES_MANAGER.validate_dotfile(&path, &content).await {
+                Ok(validation) => {
+                    reply(state, ServerMessage::DotfileValidation { path, validation }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to validate dotfile: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::GetDotfileHistory { path, reveal } => {
             match crate::dotfiles::DOTFILES_MANAGER.get_file_history(&path).await {
-                Ok(versions) => {
+                Ok(mut versions) => {
+                    if !reveal.unwrap_or(false) && crate::dotfiles::DOTFILES_MANAGER.masks_secrets() {
+                        for version in &mut versions {
+                            let content = std::mem::take(&mut version.content);
+                            version.content = crate::dotfiles::secrets::redact(content, true).content;
+                        }
+                    }
                     let response = ServerMessage::DotfileHistory { path, versions };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::Error { 
                         message: format!("Failed to get dotfile history: {}", e) 
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
         
-        WebSocketMessage::RestoreDotfileVersion { path, timestamp } => {
-            match crate::dotfiles::DOTFILES_MANAGER.restore_version(&path, timestamp).await {
+        WebSocketMessage::RestoreDotfileVersion { path, timestamp, revision, force, etag } => {
+            let restored = crate::dotfiles::DOTFILES_MANAGER
+                .restore_version(&path, timestamp, revision.as_deref(), force.unwrap_or(false), etag.as_deref())
+                .await;
+            match restored {
                 Ok(_) => {
                     let response = ServerMessage::DotfileRestored { 
                         path,
                         success: true,
-                        error: None 
+                        error: None,
+                        diagnostics: None,
+                        conflict: None,
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
                 Err(e) => {
                     let response = ServerMessage::DotfileRestored { 
                         path,
                         success: false,
-                        error: Some(format!("{}", e)) 
+                        error: Some(format!("{}", e)),
+                        diagnostics: e
+                            .downcast_ref::<crate::dotfiles::validate::ValidationError>()
+                            .map(|e| e.validation.diagnostics.clone()),
+                        conflict: write_conflict(&e),
                     };
-                    send_message(&state.message_tx, response).await?;
+                    reply(state, response).await?;
                 }
             }
         }
@@ -666,217 +987,545 @@ async fn handle_message(
         WebSocketMessage::GetDotfileTemplates => {
             let templates = crate::dotfiles::DOTFILES_MANAGER.get_templates();
             let response = ServerMessage::DotfileTemplates { templates };
-            send_message(&state.message_tx, response).await?;
+            reply(state, response).await?;
+        }
+
+        WebSocketMessage::ApplyDotfileTemplate { name, path, values, dry_run, force } => {
+            let result = crate::dotfiles::DOTFILES_MANAGER
+                .apply_template(&name, path.as_deref(), &values, dry_run.unwrap_or(false), force.unwrap_or(false))
+                .await;
+            let response = match result {
+                Ok(application) => ServerMessage::DotfileTemplateApplied {
+                    name,
+                    success: true,
+                    application: Some(application),
+                    error: None,
+                    diagnostics: None,
+                },
+                Err(e) => ServerMessage::DotfileTemplateApplied {
+                    name,
+                    success: false,
+                    application: None,
+                    diagnostics: e
+                        .downcast_ref::<crate::dotfiles::validate::ValidationError>()
+                        .map(|e| e.validation.diagnostics.clone()),
+                    error: Some(e.to_string()),
+                },
+            };
+            reply(state, response).await?;
+        }
+
+        WebSocketMessage::DiffDotfile { path, from, to, context, reveal } => {
+            let context = context.unwrap_or(crate::dotfiles::diff::DEFAULT_CONTEXT).min(100);
+            let mask = !reveal.unwrap_or(false) && crate::dotfiles::DOTFILES_MANAGER.masks_secrets();
+            let response = match crate::dotfiles::DOTFILES_MANAGER.diff_dotfile(&path, &from, &to, context, mask).await {
+                Ok(diff) => ServerMessage::DotfileDiff { path, diff: Some(diff), error: None },
+                Err(e) => ServerMessage::DotfileDiff { path, diff: None, error: Some(e.to_string()) },
+            };
+            reply(state, response).await?;
+        }
+
+        WebSocketMessage::ListTrackedDotfiles => {
+            let result = crate::dotfiles::DOTFILES_MANAGER.tracked_patterns().await;
+            reply_tracked_dotfiles(state, result).await?;
+        }
+
+        WebSocketMessage::TrackDotfile { pattern } => {
+            let result = crate::dotfiles::DOTFILES_MANAGER.track(&pattern).await;
+            reply_tracked_dotfiles(state, result).await?;
+        }
+
+        WebSocketMessage::UntrackDotfile { pattern } => {
+            let result = crate::dotfiles::DOTFILES_MANAGER.untrack(&pattern).await;
+            reply_tracked_dotfiles(state, result).await?;
+        }
+
+        WebSocketMessage::BrowseDotfileDir { path, depth } => {
+            let depth = depth.unwrap_or(crate::dotfiles::browse::DEFAULT_DEPTH);
+            match crate::dotfiles::DOTFILES_MANAGER.browse_dir(path.as_deref(), depth).await {
+                Ok(listing) => {
+                    reply(state, ServerMessage::DotfileDirListing { listing }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to browse directory: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::GetSshConfig => {
+            match crate::dotfiles::DOTFILES_MANAGER.ssh_config().await {
+                Ok(config) => {
+                    reply(state, ServerMessage::SshConfig { config }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to read SSH config: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::AddSshHost { host, before, etag, force } => {
+            let edit = SshHostEdit::Add { host, before };
+            reply_ssh_config_updated(state, SshHostAction::Add, &edit, etag, force).await?;
+        }
+
+        WebSocketMessage::UpdateSshHost { index, host, etag, force } => {
+            let edit = SshHostEdit::Update { index, host };
+            reply_ssh_config_updated(state, SshHostAction::Update, &edit, etag, force).await?;
+        }
+
+        WebSocketMessage::RemoveSshHost { index, etag, force } => {
+            let edit = SshHostEdit::Remove { index };
+            reply_ssh_config_updated(state, SshHostAction::Remove, &edit, etag, force).await?;
+        }
+
+        WebSocketMessage::GetAuthorizedKeys => {
+            match crate::dotfiles::DOTFILES_MANAGER.authorized_keys().await {
+                Ok(keys) => {
+                    reply(state, ServerMessage::AuthorizedKeys { keys }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to read authorized keys: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::AddAuthorizedKey { key, options, comment, etag } => {
+            let edit = AuthorizedKeyEdit::Add { key, options, comment };
+            reply_authorized_keys_updated(state, AuthorizedKeyAction::Add, &edit, etag).await?;
+        }
+
+        WebSocketMessage::UpdateAuthorizedKey { index, options, comment, etag } => {
+            let edit = AuthorizedKeyEdit::Update { index, options, comment };
+            reply_authorized_keys_updated(state, AuthorizedKeyAction::Update, &edit, etag).await?;
+        }
+
+        WebSocketMessage::RemoveAuthorizedKey { index, etag } => {
+            let edit = AuthorizedKeyEdit::Remove { index };
+            reply_authorized_keys_updated(state, AuthorizedKeyAction::Remove, &edit, etag).await?;
+        }
+
+        WebSocketMessage::GetDotfilesStatus => {
+            match crate::dotfiles::DOTFILES_MANAGER.git_status().await {
+                Ok(status) => {
+                    reply(state, ServerMessage::DotfilesStatus { status }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to get dotfiles status: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::GetDotfilesGitDiff { path } => {
+            match crate::dotfiles::DOTFILES_MANAGER.git_diff(path.as_deref()).await {
+                Ok(diff) => {
+                    reply(state, ServerMessage::DotfilesGitDiff { path, diff }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to diff dotfiles: {}", e)
+                    };
+                    reply(state, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::PullDotfiles => {
+            let result = crate::dotfiles::DOTFILES_MANAGER.pull().await;
+            reply_dotfiles_synced(state, SyncAction::Pull, result).await?;
+        }
+
+        WebSocketMessage::PushDotfiles => {
+            let result = crate::dotfiles::DOTFILES_MANAGER.push().await;
+            reply_dotfiles_synced(state, SyncAction::Push, result).await?;
         }
     }
     
     Ok(())
 }
 
-async fn send_message(tx: &mpsc::UnboundedSender<BroadcastMessage>, msg: ServerMessage) -> anyhow::Result<()> {
-    if let Ok(json) = serde_json::to_string(&msg) {
+/// Feature toggle a message depends on, if any
+fn required_feature(msg: &WebSocketMessage) -> Option<Feature> {
+    match msg {
+        WebSocketMessage::AudioControl { .. } => Some(Feature::Audio),
+        WebSocketMessage::ListCronJobs
+        | WebSocketMessage::CreateCronJob { .. }
+        | WebSocketMessage::UpdateCronJob { .. }
+        | WebSocketMessage::DeleteCronJob { .. }
+        | WebSocketMessage::ToggleCronJob { .. }
+        | WebSocketMessage::TestCronCommand { .. }
+        | WebSocketMessage::PreviewCronSchedule { .. }
+        | WebSocketMessage::ListCronExecutions { .. }
+        | WebSocketMessage::GetCronExecution { .. }
+        | WebSocketMessage::PruneCronExecutions { .. } => Some(Feature::Cron),
+        WebSocketMessage::ListDotfiles
+        | WebSocketMessage::ReadDotfile { .. }
+        | WebSocketMessage::WriteDotfile { .. }
+        | WebSocketMessage::ValidateDotfile { .. }
+        | WebSocketMessage::GetDotfileHistory { .. }
+        | WebSocketMessage::RestoreDotfileVersion { .. }
+        | WebSocketMessage::GetDotfileTemplates
+        | WebSocketMessage::ApplyDotfileTemplate { .. }
+        | WebSocketMessage::DiffDotfile { .. }
+        | WebSocketMessage::ListTrackedDotfiles
+        | WebSocketMessage::TrackDotfile { .. }
+        | WebSocketMessage::UntrackDotfile { .. }
+        | WebSocketMessage::BrowseDotfileDir { .. }
+        | WebSocketMessage::GetSshConfig
+        | WebSocketMessage::AddSshHost { .. }
+        | WebSocketMessage::UpdateSshHost { .. }
+        | WebSocketMessage::RemoveSshHost { .. }
+        | WebSocketMessage::GetAuthorizedKeys
+        | WebSocketMessage::AddAuthorizedKey { .. }
+        | WebSocketMessage::UpdateAuthorizedKey { .. }
+        | WebSocketMessage::RemoveAuthorizedKey { .. }
+        | WebSocketMessage::GetDotfilesStatus
+        | WebSocketMessage::GetDotfilesGitDiff { .. }
+        | WebSocketMessage::PullDotfiles
+        | WebSocketMessage::PushDotfiles => Some(Feature::Dotfiles),
+        _ => None,
+    }
+}
+
+fn channel_stream<'a>(state: &'a WsState, channel: &Option<String>) -> Option<&'a Arc<TerminalStream>> {
+    state.channels.get(channel).map(|c| &c.stream)
+}
+
+async fn write_input(state: &WsState, channel: &Option<String>, data: &[u8]) -> anyhow::Result<()> {
+    match channel_stream(state, channel) {
+        Some(stream) => stream.write_input(data).await,
+        None => {
+            debug!("No PTY session active on channel {:?}, ignoring input", channel);
+            Ok(())
+        }
+    }
+}
+
+async fn handle_binary_frame(data: &[u8], state: &WsState) -> anyhow::Result<()> {
+    let frame = frame::decode(data)?;
+    match frame.frame_type {
+        frame::FrameType::Input => write_input(state, &None, frame.payload).await,
+        frame::FrameType::ChannelInput => {
+            let (stream_id, payload) = frame::split_stream_id(frame.payload)?;
+            let channel = state
+                .channels
+                .values()
+                .find(|c| c.stream_id == stream_id)
+                .ok_or_else(|| anyhow::anyhow!("No channel with stream id {}", stream_id))?;
+            channel.stream.write_input(payload).await
+        }
+        other => anyhow::bail!("Unexpected {:?} frame from client", other),
+    }
+}
+
+type ProtocolFailure = (Option<String>, ProtocolErrorCode, String);
+
+/// Split a client frame into its `requestId` and message, classifying anything unusable
+fn parse_client_message(text: &str) -> Result<(Option<String>, WebSocketMessage), ProtocolFailure> {
+    let value: serde_json::Value = serde_json::from_str(text)
+        .map_err(|e| (None, ProtocolErrorCode::InvalidJson, e.to_string()))?;
+
+    let object = value.as_object().ok_or_else(|| {
+        (None, ProtocolErrorCode::InvalidEnvelope, "Message must be a JSON object".to_string())
+    })?;
+
+    let request_id = match object.get("requestId") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(id)) => Some(id.clone()),
+        Some(_) => {
+            return Err((None, ProtocolErrorCode::InvalidEnvelope, "requestId must be a string".to_string()));
+        }
+    };
+
+    match object.get("type") {
+        Some(serde_json::Value::String(_)) => {}
+        Some(_) => {
+            return Err((request_id, ProtocolErrorCode::InvalidEnvelope, "type must be a string".to_string()));
+        }
+        None => {
+            return Err((request_id, ProtocolErrorCode::InvalidEnvelope, "Missing message type".to_string()));
+        }
+    }
+
+    serde_json::from_value::<WebSocketMessage>(value).map(|msg| (request_id.clone(), msg)).map_err(|e| {
+        let message = e.to_string();
+        let code = if message.starts_with("unknown variant") {
+            ProtocolErrorCode::UnknownType
+        } else {
+            ProtocolErrorCode::InvalidMessage
+        };
+        (request_id, code, message)
+    })
+}
+
+async fn reply_dotfiles_synced(
+    state: &WsState,
+    action: SyncAction,
+    result: anyhow::Result<String>,
+) -> anyhow::Result<()> {
+    let response = match result {
+        Ok(output) => ServerMessage::DotfilesSynced {
+            action,
+            success: true,
+            output,
+            error: None,
+        },
+        Err(e) => {
+            error!("Dotfiles {:?} failed: {}", action, e);
+            ServerMessage::DotfilesSynced {
+                action,
+                success: false,
+                output: String::new(),
+                error: Some(e.to_string()),
+            }
+        }
+    };
+    reply(state, response).await
+}
+
+async fn reply_tracked_dotfiles(
+    state: &WsState,
+    result: anyhow::Result<Vec<crate::dotfiles::tracked::TrackedPattern>>,
+) -> anyhow::Result<()> {
+    let response = match result {
+        Ok(patterns) => ServerMessage::TrackedDotfiles { patterns },
+        Err(e) => ServerMessage::Error {
+            message: format!("Failed to update tracked dotfiles: {}", e),
+        },
+    };
+    reply(state, response).await
+}
+
+async fn reply_ssh_config_updated(
+    state: &WsState,
+    action: SshHostAction,
+    edit: &SshHostEdit,
+    etag: Option<String>,
+    force: Option<bool>,
+) -> anyhow::Result<()> {
+    let result = crate::dotfiles::DOTFILES_MANAGER
+        .edit_ssh_config(edit, etag.as_deref(), force.unwrap_or(false))
+        .await;
+    let response = match result {
+        Ok(config) => ServerMessage::SshConfigUpdated {
+            action,
+            success: true,
+            config: Some(config),
+            error: None,
+            diagnostics: None,
+            conflict: None,
+        },
+        Err(e) => ServerMessage::SshConfigUpdated {
+            action,
+            success: false,
+            config: None,
+            diagnostics: e
+                .downcast_ref::<crate::dotfiles::validate::ValidationError>()
+                .map(|e| e.validation.diagnostics.clone()),
+            conflict: write_conflict(&e),
+            error: Some(e.to_string()),
+        },
+    };
+    reply(state, response).await
+}
+
+async fn reply_authorized_keys_updated(
+    state: &WsState,
+    action: AuthorizedKeyAction,
+    edit: &AuthorizedKeyEdit,
+    etag: Option<String>,
+) -> anyhow::Result<()> {
+    let result = crate::dotfiles::DOTFILES_MANAGER
+        .edit_authorized_keys(edit, etag.as_deref())
+        .await;
+    let response = match result {
+        Ok(keys) => ServerMessage::AuthorizedKeysUpdated {
+            action,
+            success: true,
+            keys: Some(keys),
+            error: None,
+            conflict: None,
+        },
+        Err(e) => ServerMessage::AuthorizedKeysUpdated {
+            action,
+            success: false,
+            keys: None,
+            conflict: write_conflict(&e),
+            error: Some(e.to_string()),
+        },
+    };
+    reply(state, response).await
+}
+
+// The conflict that refused a write, its contents masked when `dotfiles.mask_secrets` is on
+fn write_conflict(e: &anyhow::Error) -> Option<crate::dotfiles::merge::WriteConflict> {
+    let mut conflict = e.downcast_ref::<crate::dotfiles::merge::WriteConflict>().cloned()?;
+    if crate::dotfiles::DOTFILES_MANAGER.masks_secrets() {
+        conflict.content = crate::dotfiles::secrets::redact(conflict.content, true).content;
+        if let Some(merged) = conflict.merged.as_mut() {
+            let content = std::mem::take(&mut merged.content);
+            merged.content = crate::dotfiles::secrets::redact(content, true).content;
+        }
+    }
+    Some(conflict)
+}
+
+async fn reply_pane_updated(
+    state: &WsState,
+    action: PaneAction,
+    result: anyhow::Result<Option<String>>,
+) -> anyhow::Result<()> {
+    let response = match result {
+        Ok(pane_id) => ServerMessage::PaneUpdated {
+            action,
+            success: true,
+            pane_id,
+            error: None,
+        },
+        Err(e) => {
+            error!("Pane {:?} failed: {}", action, e);
+            ServerMessage::PaneUpdated {
+                action,
+                success: false,
+                pane_id: None,
+                error: Some(e.to_string()),
+            }
+        }
+    };
+    reply(state, response).await
+}
+
+/// Send a reply to the client message currently being handled
+async fn reply(state: &WsState, msg: ServerMessage) -> anyhow::Result<()> {
+    send_with_request_id(&state.message_tx, &msg, state.request_id.as_deref())
+}
+
+fn send_with_request_id(
+    tx: &mpsc::UnboundedSender<BroadcastMessage>,
+    msg: &ServerMessage,
+    request_id: Option<&str>,
+) -> anyhow::Result<()> {
+    let envelope = ServerEnvelope { message: msg, request_id };
+    if let Ok(json) = serde_json::to_string(&envelope) {
         tx.send(BroadcastMessage::Text(Arc::new(json)))?;
     }
     Ok(())
 }
 
+/// Tag for output on `channel`, allocating a stream id the first time a named channel is used
+fn channel_tag(state: &mut WsState, channel: &Option<String>) -> anyhow::Result<ChannelTag> {
+    let stream_id = match (channel, state.channels.get(channel)) {
+        (None, _) => 0,
+        (Some(_), Some(existing)) => existing.stream_id,
+        (Some(name), None) => {
+            if state.channels.len() >= MAX_CHANNELS {
+                anyhow::bail!("Cannot open channel {}: at most {} channels per connection", name, MAX_CHANNELS);
+            }
+            let id = state.next_stream_id;
+            state.next_stream_id += 1;
+            id
+        }
+    };
+    Ok(ChannelTag { name: channel.clone(), stream_id })
+}
+
 async fn attach_to_session(
     state: &mut WsState,
+    channel: Option<String>,
     session_name: &str,
     cols: u16,
     rows: u16,
 ) -> anyhow::Result<()> {
-    let tx = &state.message_tx;
-    // Update current session
-    {
-        let mut current = state.current_session.lock().await;
-        *current = Some(session_name.to_string());
-    }
-    
-    // Clean up any existing PTY session first
-    let mut pty_guard = state.current_pty.lock().await;
-    if let Some(old_pty) = pty_guard.take() {
-        debug!("Cleaning up previous PTY session for tmux: {}", old_pty.tmux_session);
-        // Kill the child process
-        {
-            let mut child = old_pty.child.lock().await;
-            let _ = child.kill();
-            let _ = child.wait();
-        }
-        // Abort the reader task
-        old_pty.reader_task.abort();
-        let _ = old_pty.reader_task.await;
-    }
-    
-    // Small delay to ensure cleanup is complete
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
-    
-    // Create new PTY session
-    debug!("Creating new PTY session for: {}", session_name);
-    
-    let pty_system = native_pty_system();
-    let pair = pty_system.openpty(PtySize {
-        rows,
-        cols,
-        pixel_width: 0,
-        pixel_height: 0,
-    })?;
-    
-    let mut cmd = CommandBuilder::new("tmux");
-    cmd.args(&["attach-session", "-t", session_name]);
-    cmd.env("TERM", "xterm-256color");
-    cmd.env("COLORTERM", "truecolor");
-    
-    // Clear SSH-related environment variables that might confuse starship
-    cmd.env_remove("SSH_CLIENT");
-    cmd.env_remove("SSH_CONNECTION");
-    cmd.env_remove("SSH_TTY");
-    cmd.env_remove("SSH_AUTH_SOCK");
-    
-    // Set up proper environment for local terminal
-    cmd.env("WEBMUX", "1");
-    
-    // Get reader before we move master
-    let reader = pair.master.try_clone_reader()?;
-    
-    // Get writer and spawn command
-    let writer = pair.master.take_writer()?;
-    let writer = Arc::new(Mutex::new(writer));
-    
-    // First check if session exists, if not create it
-    let check_output = tokio::process::Command::new("tmux")
-        .args(&["has-session", "-t", session_name])
-        .output()
-        .await?;
-    
-    if !check_output.status.success() {
-        // Create the session first
-        info!("Session {} doesn't exist, creating it", session_name);
-        tmux::create_session(session_name).await?;
+    let tag = channel_tag(state, &channel)?;
+
+    // Clean up any existing stream on this channel first
+    if let Some(old) = state.channels.remove(&channel) {
+        state.streams.release(&old.stream, &state.client_id).await;
     }
-    
-    let child = pair.slave.spawn_command(cmd)?;
-    let child: Arc<Mutex<Box<dyn portable_pty::Child + Send>>> = Arc::new(Mutex::new(child));
-    
-    // Set up reader task - DIRECT sending for now to fix the issue
-    let tx_clone = tx.clone();
-    let client_id = state.client_id.clone();
-    let reader_task = tokio::task::spawn_blocking(move || {
-        let mut reader = reader;
-        let mut buffer = vec![0u8; 8192]; // Smaller buffer to prevent overwhelming
-        let mut consecutive_errors = 0;
-        let mut utf8_decoder = crate::terminal_buffer::Utf8StreamDecoder::new();
-        let mut pending_output = String::with_capacity(16384);
-        let mut last_send = std::time::Instant::now();
-        let mut bytes_since_pause = 0usize;
-        
-        loop {
-            match reader.read(&mut buffer) {
-                Ok(0) => {
-                    info!("PTY EOF for client {}", client_id);
-                    // Send any pending output
-                    if !pending_output.is_empty() {
-                        let output = ServerMessage::Output { data: pending_output };
-                        if let Ok(json) = serde_json::to_string(&output) {
-                            let _ = tx_clone.send(BroadcastMessage::Text(Arc::new(json)));
-                        }
-                    }
-                    break;
-                }
-                Ok(n) => {
-                    consecutive_errors = 0;
-                    
-                    // Decode and accumulate
-                    let (text, _) = utf8_decoder.decode_chunk(&buffer[..n]);
-                    if !text.is_empty() {
-                        pending_output.push_str(&text);
-                        
-                        bytes_since_pause += text.len();
-                        
-                        // More aggressive sending for better responsiveness
-                        let should_send = pending_output.len() > 1024 || 
-                                         last_send.elapsed() > std::time::Duration::from_millis(10) ||
-                                         pending_output.contains('\n'); // Send on newlines
-                        
-                        if should_send && !pending_output.is_empty() {
-                            let output = ServerMessage::Output { data: pending_output.clone() };
-                            if let Ok(json) = serde_json::to_string(&output) {
-                                if tx_clone.send(BroadcastMessage::Text(Arc::new(json))).is_err() {
-                                    error!("Client {} disconnected, stopping PTY reader", client_id);
-                                    break;
-                                }
-                            }
-                            pending_output.clear();
-                            last_send = std::time::Instant::now();
-                            
-                            // Flow control: pause if we're sending too much data
-                            if bytes_since_pause > 65536 { // 64KB threshold
-                                std::thread::sleep(std::time::Duration::from_millis(5));
-                                bytes_since_pause = 0;
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    consecutive_errors += 1;
-                    if consecutive_errors > 5 {
-                        error!("Too many consecutive PTY read errors for client {}: {}", client_id, e);
-                        break;
-                    }
-                    error!("PTY read error for client {} (attempt {}): {}", client_id, consecutive_errors, e);
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                }
-            }
-        }
-        
-        let disconnected = ServerMessage::Disconnected;
-        if let Ok(json) = serde_json::to_string(&disconnected) {
-            let _ = tx_clone.send(BroadcastMessage::Text(Arc::new(json)));
-        }
-    });
-    
-    let pty_session = PtySession {
-        writer: writer.clone(),
-        master: Arc::new(Mutex::new(pair.master)),
-        reader_task,
-        child,
-        tmux_session: session_name.to_string(),
-        terminal_buffer: Arc::new(Mutex::new(TerminalRingBuffer::new())), // Keep but don't use yet
-    };
-    
-    *pty_guard = Some(pty_session);
-    drop(pty_guard);
-    
+
+    let subscriber = StreamSubscriber::new(
+        state.client_id.clone(),
+        tag.clone(),
+        state.message_tx.clone(),
+        state.output_options.clone(),
+    );
+    let stream = state.streams.open(session_name, cols, rows, subscriber).await?;
+
     // Send attached confirmation
     let response = ServerMessage::Attached {
         session_name: session_name.to_string(),
+        resume_token: stream.resume_token.clone(),
+        sequence: stream.last_sequence(),
+        channel: channel.clone(),
+        stream_id: tag.stream_id,
     };
-    send_message(tx, response).await?;
-    
+    state.channels.insert(channel, Channel { stream, stream_id: tag.stream_id });
+    reply(state, response).await?;
+
     Ok(())
 }
 
-async fn cleanup_session(state: &WsState) {
+async fn resume_stream(
+    state: &mut WsState,
+    channel: Option<String>,
+    resume_token: &str,
+    last_sequence: u64,
+) -> anyhow::Result<Arc<TerminalStream>> {
+    let tag = channel_tag(state, &channel)?;
+
+    if let Some(old) = state.channels.remove(&channel) {
+        if old.stream.resume_token != resume_token {
+            state.streams.release(&old.stream, &state.client_id).await;
+        }
+    }
+    // Resuming into a different channel moves the stream rather than sharing it
+    state.channels.retain(|_, c| c.stream.resume_token != resume_token);
+
+    let subscriber = StreamSubscriber::new(
+        state.client_id.clone(),
+        tag.clone(),
+        state.message_tx.clone(),
+        state.output_options.clone(),
+    );
+    let tx = state.message_tx.clone();
+    let request_id = state.request_id.clone();
+    let stream = state
+        .streams
+        .resume(resume_token, last_sequence, subscriber, |info| {
+            let response = ServerMessage::Resumed {
+                resume_token: resume_token.to_string(),
+                sequence: info.sequence,
+                replayed: info.replayed,
+                gap: info.gap,
+                channel: channel.clone(),
+                stream_id: tag.stream_id,
+            };
+            let _ = send_with_request_id(&tx, &response, request_id.as_deref());
+        })
+        .await?;
+
+    state.channels.insert(channel, Channel { stream: stream.clone(), stream_id: tag.stream_id });
+    Ok(stream)
+}
+
+async fn cleanup_session(state: &mut WsState) {
     info!("Cleaning up session for client: {}", state.client_id);
     
-    // Clean up PTY session
-    let mut pty_guard = state.current_pty.lock().await;
-    if let Some(pty) = pty_guard.take() {
-        info!("Cleaning up PTY for tmux session: {}", pty.tmux_session);
-        
-        // Kill the child process first
-        {
-            let mut child = pty.child.lock().await;
-            let _ = child.kill();
-            let _ = child.wait();
-        }
-        
-        // Abort the reader task
-        pty.reader_task.abort();
-        
-        // Writer and master will be dropped automatically
+    // Keep the terminals around so the client can resume them after a reconnect
+    for (_, channel) in state.channels.drain() {
+        state.streams.detach(channel.stream, &state.client_id).await;
     }
-    drop(pty_guard);
     
     // Clean up audio streaming
     if let Some(ref audio_tx) = state.audio_tx {
@@ -0,0 +1,451 @@
+use anyhow::Result;
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, Mutex, RwLock},
+    task::JoinHandle,
+};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use super::{frame, BroadcastMessage, ClientId, TerminalOutput};
+use crate::{
+    terminal_buffer::{TerminalChunk, TerminalRingBuffer, Utf8StreamDecoder},
+    tmux,
+    types::ServerMessage,
+};
+
+/// Output format negotiated by a connection, shared with the PTY readers feeding it
+#[derive(Default)]
+pub struct OutputOptions {
+    pub binary: AtomicBool,
+    pub sequenced: AtomicBool,
+}
+
+/// Terminal channel of a connection that output is addressed to
+#[derive(Debug, Clone, Default)]
+pub struct ChannelTag {
+    // `None` is the connection's default channel
+    pub name: Option<String>,
+    // Id used in binary channel frames; 0 for the default channel
+    pub stream_id: u32,
+}
+
+/// The connection currently receiving a stream's output
+pub struct StreamSubscriber {
+    client_id: ClientId,
+    channel: ChannelTag,
+    tx: mpsc::UnboundedSender<BroadcastMessage>,
+    options: Arc<OutputOptions>,
+    decoder: Utf8StreamDecoder,
+}
+
+impl StreamSubscriber {
+    pub fn new(
+        client_id: ClientId,
+        channel: ChannelTag,
+        tx: mpsc::UnboundedSender<BroadcastMessage>,
+        options: Arc<OutputOptions>,
+    ) -> Self {
+        Self {
+            client_id,
+            channel,
+            tx,
+            options,
+            decoder: Utf8StreamDecoder::new(),
+        }
+    }
+
+    /// Encode a chunk in the negotiated format. Returns false once the connection is gone.
+    fn send_chunk(&mut self, chunk: &TerminalChunk) -> bool {
+        let output = if self.options.binary.load(Ordering::Relaxed) {
+            let data = if self.channel.name.is_some() {
+                frame::encode_channel(self.channel.stream_id, chunk.sequence, &chunk.data)
+            } else if self.options.sequenced.load(Ordering::Relaxed) {
+                frame::encode_sequenced(chunk.sequence, &chunk.data)
+            } else {
+                frame::encode(frame::FrameType::Output, &chunk.data)
+            };
+            Some(TerminalOutput::Binary(data))
+        } else {
+            let (text, _) = self.decoder.decode_chunk(&chunk.data);
+            if text.is_empty() {
+                None
+            } else {
+                let message = ServerMessage::Output {
+                    data: text,
+                    seq: Some(chunk.sequence),
+                    channel: self.channel.name.clone(),
+                };
+                serde_json::to_string(&message)
+                    .ok()
+                    .map(|json| TerminalOutput::Json(Arc::new(json)))
+            }
+        };
+
+        match output {
+            Some(output) => self.tx.send(BroadcastMessage::Output(output)).is_ok(),
+            None => true,
+        }
+    }
+
+    fn send(&self, message: &ServerMessage) {
+        if let Ok(json) = serde_json::to_string(message) {
+            let _ = self.tx.send(BroadcastMessage::Text(Arc::new(json)));
+        }
+    }
+}
+
+// Replay window and live subscriber share a lock so a resuming client
+// never misses or duplicates a chunk
+struct StreamOutput {
+    replay: TerminalRingBuffer,
+    subscriber: Option<StreamSubscriber>,
+}
+
+/// A tmux client running in a PTY whose output outlives the WebSocket that opened it
+pub struct TerminalStream {
+    pub resume_token: String,
+    pub session_name: String,
+    writer: Mutex<Box<dyn Write + Send>>,
+    master: Mutex<Box<dyn portable_pty::MasterPty + Send>>,
+    child: Mutex<Box<dyn portable_pty::Child + Send>>,
+    reader_task: JoinHandle<()>,
+    output: Arc<std::sync::Mutex<StreamOutput>>,
+    closed: Arc<AtomicBool>,
+    // Bumped on every attach/detach so stale expiry timers do nothing
+    attach_generation: AtomicU64,
+}
+
+impl TerminalStream {
+    pub async fn write_input(&self, data: &[u8]) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+        if let Err(e) = writer.write_all(data) {
+            error!("Failed to write to PTY: {}", e);
+            return Err(e.into());
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub async fn resize(&self, cols: u16, rows: u16) -> Result<()> {
+        let master = self.master.lock().await;
+        master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+        debug!("Resized PTY to {}x{}", cols, rows);
+        Ok(())
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.lock_output().replay.last_sequence()
+    }
+
+    fn lock_output(&self) -> std::sync::MutexGuard<'_, StreamOutput> {
+        self.output.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Another connection has taken the stream over. Not the case without a subscriber,
+    // which is also how a client whose sends started failing is left.
+    fn is_subscribed_by_other(&self, client_id: &str) -> bool {
+        self.lock_output()
+            .subscriber
+            .as_ref()
+            .is_some_and(|s| s.client_id != client_id)
+    }
+
+    async fn kill(&self) {
+        debug!("Cleaning up PTY session for tmux: {}", self.session_name);
+        // Kill the child process
+        {
+            let mut child = self.child.lock().await;
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        // Abort the reader task
+        self.reader_task.abort();
+    }
+}
+
+pub struct ResumeInfo {
+    // Latest sequence number in the stream
+    pub sequence: u64,
+    pub replayed: usize,
+    // Some output after the client's last sequence has already been evicted
+    pub gap: bool,
+}
+
+/// All live terminal streams, keyed by resume token
+pub struct StreamRegistry {
+    streams: RwLock<HashMap<String, Arc<TerminalStream>>>,
+    resume_grace: Duration,
+    replay_bytes: usize,
+}
+
+impl StreamRegistry {
+    pub fn new(resume_grace: Duration, replay_bytes: usize) -> Self {
+        Self {
+            streams: RwLock::new(HashMap::new()),
+            resume_grace,
+            replay_bytes,
+        }
+    }
+
+    /// Spawn `tmux attach-session` in a new PTY streaming to `subscriber`
+    pub async fn open(
+        &self,
+        session_name: &str,
+        cols: u16,
+        rows: u16,
+        subscriber: StreamSubscriber,
+    ) -> Result<Arc<TerminalStream>> {
+        debug!("Creating new PTY session for: {}", session_name);
+
+        let pty_system = native_pty_system();
+        let pair = pty_system.openpty(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+
+        let mut cmd = CommandBuilder::new("tmux");
+        cmd.args(["attach-session", "-t", session_name]);
+        cmd.env("TERM", "xterm-256color");
+        cmd.env("COLORTERM", "truecolor");
+
+        // Clear SSH-related environment variables that might confuse starship
+        cmd.env_remove("SSH_CLIENT");
+        cmd.env_remove("SSH_CONNECTION");
+        cmd.env_remove("SSH_TTY");
+        cmd.env_remove("SSH_AUTH_SOCK");
+
+        // Set up proper environment for local terminal
+        cmd.env("WEBMUX", "1");
+
+        // Get reader before we move master
+        let reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+
+        // First check if session exists, if not create it
+        let check_output = tokio::process::Command::new("tmux")
+            .args(["has-session", "-t", session_name])
+            .output()
+            .await?;
+
+        if !check_output.status.success() {
+            info!("Session {} doesn't exist, creating it", session_name);
+            tmux::create_session(session_name).await?;
+        }
+
+        let child = pair.slave.spawn_command(cmd)?;
+
+        let resume_token = Uuid::new_v4().to_string();
+        let output = Arc::new(std::sync::Mutex::new(StreamOutput {
+            replay: TerminalRingBuffer::with_capacity(self.replay_bytes),
+            subscriber: Some(subscriber),
+        }));
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader_task = spawn_reader(reader, output.clone(), closed.clone(), session_name.to_string());
+
+        let stream = Arc::new(TerminalStream {
+            resume_token: resume_token.clone(),
+            session_name: session_name.to_string(),
+            writer: Mutex::new(writer),
+            master: Mutex::new(pair.master),
+            child: Mutex::new(child),
+            reader_task,
+            output,
+            closed,
+            attach_generation: AtomicU64::new(0),
+        });
+
+        self.streams.write().await.insert(resume_token, stream.clone());
+        Ok(stream)
+    }
+
+    /// Hand a stream to a reconnecting client, replaying output after `last_sequence`.
+    /// `on_resumed` runs before the replay is queued so its reply arrives first.
+    pub async fn resume(
+        &self,
+        resume_token: &str,
+        last_sequence: u64,
+        mut subscriber: StreamSubscriber,
+        on_resumed: impl FnOnce(&ResumeInfo),
+    ) -> Result<Arc<TerminalStream>> {
+        let stream = self
+            .streams
+            .read()
+            .await
+            .get(resume_token)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown or expired resume token"))?;
+
+        if stream.closed.load(Ordering::Acquire) {
+            self.remove(&stream).await;
+            anyhow::bail!("Terminal session has ended");
+        }
+
+        stream.attach_generation.fetch_add(1, Ordering::AcqRel);
+        {
+            let mut output = stream.lock_output();
+            let replay = output.replay.since(last_sequence);
+            let info = ResumeInfo {
+                sequence: output.replay.last_sequence(),
+                replayed: replay.chunks.len(),
+                gap: replay.gap,
+            };
+            on_resumed(&info);
+
+            for chunk in &replay.chunks {
+                subscriber.send_chunk(chunk);
+            }
+            // A still-connected previous client simply stops receiving output
+            output.subscriber = Some(subscriber);
+        }
+
+        info!("Resumed terminal stream for session {}", stream.session_name);
+        Ok(stream)
+    }
+
+    /// The client's connection dropped: stop streaming to it but keep the PTY
+    /// around for `resume_grace` in case it reconnects
+    pub async fn detach(self: &Arc<Self>, stream: Arc<TerminalStream>, client_id: &str) {
+        // Another connection already resumed this stream
+        if stream.is_subscribed_by_other(client_id) {
+            return;
+        }
+        if stream.closed.load(Ordering::Acquire) {
+            self.close(&stream).await;
+            return;
+        }
+
+        stream.lock_output().subscriber = None;
+        let generation = stream.attach_generation.fetch_add(1, Ordering::AcqRel) + 1;
+        info!(
+            "Detached terminal stream for session {}, keeping it for {:?}",
+            stream.session_name, self.resume_grace
+        );
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(registry.resume_grace).await;
+            if stream.attach_generation.load(Ordering::Acquire) == generation {
+                info!("Resume window expired for session {}", stream.session_name);
+                registry.close(&stream).await;
+            }
+        });
+    }
+
+    /// Release a stream the client no longer wants, killing it unless
+    /// another connection has taken it over
+    pub async fn release(&self, stream: &TerminalStream, client_id: &str) {
+        if !stream.is_subscribed_by_other(client_id) || stream.closed.load(Ordering::Acquire) {
+            self.close(stream).await;
+        }
+    }
+
+    async fn close(&self, stream: &TerminalStream) {
+        self.remove(stream).await;
+        // Nobody should see the Disconnected caused by our own kill
+        stream.lock_output().subscriber = None;
+        stream.kill().await;
+    }
+
+    async fn remove(&self, stream: &TerminalStream) {
+        self.streams.write().await.remove(&stream.resume_token);
+    }
+}
+
+fn spawn_reader(
+    reader: Box<dyn Read + Send>,
+    output: Arc<std::sync::Mutex<StreamOutput>>,
+    closed: Arc<AtomicBool>,
+    session_name: String,
+) -> JoinHandle<()> {
+    tokio::task::spawn_blocking(move || {
+        let mut reader = reader;
+        let mut buffer = vec![0u8; 8192]; // Smaller buffer to prevent overwhelming
+        let mut consecutive_errors = 0;
+        let mut pending_output: Vec<u8> = Vec::with_capacity(16384);
+        let mut last_send = std::time::Instant::now();
+        let mut bytes_since_pause = 0usize;
+
+        // Record pending bytes in the replay window and forward them to the subscriber
+        let flush = |pending: &mut Vec<u8>| {
+            let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+            let chunk = output.replay.write(pending);
+            pending.clear();
+            if let Some(subscriber) = output.subscriber.as_mut() {
+                if !subscriber.send_chunk(&chunk) {
+                    debug!("Subscriber for session {} went away", session_name);
+                    output.subscriber = None;
+                }
+            }
+        };
+
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => {
+                    info!("PTY EOF for session {}", session_name);
+                    // Send any pending output
+                    if !pending_output.is_empty() {
+                        flush(&mut pending_output);
+                    }
+                    break;
+                }
+                Ok(n) => {
+                    consecutive_errors = 0;
+
+                    pending_output.extend_from_slice(&buffer[..n]);
+                    bytes_since_pause += n;
+
+                    // More aggressive sending for better responsiveness
+                    let should_send = pending_output.len() > 1024
+                        || last_send.elapsed() > std::time::Duration::from_millis(10)
+                        || pending_output.contains(&b'\n'); // Send on newlines
+
+                    if should_send {
+                        flush(&mut pending_output);
+                        last_send = std::time::Instant::now();
+
+                        // Flow control: pause if we're sending too much data
+                        if bytes_since_pause > 65536 {
+                            // 64KB threshold
+                            std::thread::sleep(std::time::Duration::from_millis(5));
+                            bytes_since_pause = 0;
+                        }
+                    }
+                }
+                Err(e) => {
+                    consecutive_errors += 1;
+                    if consecutive_errors > 5 {
+                        error!("Too many consecutive PTY read errors for session {}: {}", session_name, e);
+                        break;
+                    }
+                    error!("PTY read error for session {} (attempt {}): {}", session_name, consecutive_errors, e);
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+            }
+        }
+
+        closed.store(true, Ordering::Release);
+        let output = output.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(subscriber) = output.subscriber.as_ref() {
+            subscriber.send(&ServerMessage::Disconnected {
+                channel: subscriber.channel.name.clone(),
+            });
+        }
+    })
+}
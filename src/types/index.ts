@@ -1,6 +1,8 @@
 // WebSocket message types
 export interface WsMessage {
   type: string;
+  // Echoed back by the server on every reply caused by this message
+  requestId?: string;
   [key: string]: string | number | boolean | object | undefined;
 }
 
@@ -9,17 +11,20 @@ export interface AttachSessionMessage extends WsMessage {
   sessionName: string;
   cols: number;
   rows: number;
+  channel?: string;
 }
 
 export interface InputMessage extends WsMessage {
   type: 'input';
   data: string;
+  channel?: string;
 }
 
 export interface ResizeMessage extends WsMessage {
   type: 'resize';
   cols: number;
   rows: number;
+  channel?: string;
 }
 
 export interface ListWindowsMessage extends WsMessage {
@@ -37,15 +42,58 @@ export interface SelectWindowMessage extends WsMessage {
 export interface OutputMessage extends WsMessage {
   type: 'output';
   data: string;
+  seq?: number;
+  channel?: string;
 }
 
 export interface AttachedMessage extends WsMessage {
   type: 'attached';
   sessionName: string;
+  resumeToken: string;
+  sequence: number;
+  streamId: number;
+  channel?: string;
+}
+
+export interface DetachSessionMessage extends WsMessage {
+  type: 'detach-session';
+  channel?: string;
+}
+
+export interface DetachedMessage extends WsMessage {
+  type: 'detached';
+  channel?: string;
+}
+
+export interface ResumeMessage extends WsMessage {
+  type: 'resume';
+  resumeToken: string;
+  lastSequence: number;
+  cols?: number;
+  rows?: number;
+  channel?: string;
+}
+
+export interface ResumedMessage extends WsMessage {
+  type: 'resumed';
+  resumeToken: string;
+  sequence: number;
+  replayed: number;
+  gap: boolean;
+  streamId: number;
+  channel?: string;
+}
+
+export interface ResumeFailedMessage extends WsMessage {
+  type: 'resume-failed';
+  resumeToken: string;
+  reason: string;
+  channel?: string;
 }
 
 export interface DisconnectedMessage extends WsMessage {
   type: 'disconnected';
+  channel?: string;
 }
 
 export interface WindowsListMessage extends WsMessage {
@@ -70,6 +118,124 @@ export interface TmuxWindow {
   panes: number;
 }
 
+export interface TmuxPane {
+  id: string;
+  index: number;
+  windowIndex: number;
+  width: number;
+  height: number;
+  left: number;
+  top: number;
+  active: boolean;
+  zoomed: boolean;
+  currentCommand: string;
+  currentPath: string;
+  pid: number;
+  title: string;
+  dead: boolean;
+  deadStatus: number | null;
+}
+
+export type SplitDirection = 'horizontal' | 'vertical';
+export type PaneDirection = 'up' | 'down' | 'left' | 'right';
+export type PaneAction = 'split' | 'kill' | 'select' | 'resize' | 'swap' | 'zoom' | 'break' | 'join';
+
+export interface ListPanesMessage extends WsMessage {
+  type: 'list-panes';
+  sessionName: string;
+  windowIndex?: number;
+}
+
+export interface SplitWindowMessage extends WsMessage {
+  type: 'split-window';
+  paneId: string;
+  direction: SplitDirection;
+  percent?: number;
+  startDirectory?: string;
+}
+
+export interface KillPaneMessage extends WsMessage {
+  type: 'kill-pane';
+  paneId: string;
+}
+
+export interface SelectPaneMessage extends WsMessage {
+  type: 'select-pane';
+  paneId: string;
+}
+
+export interface ResizePaneMessage extends WsMessage {
+  type: 'resize-pane';
+  paneId: string;
+  width?: number;
+  height?: number;
+  direction?: PaneDirection;
+  amount?: number;
+}
+
+export interface SwapPaneMessage extends WsMessage {
+  type: 'swap-pane';
+  sourcePaneId: string;
+  targetPaneId: string;
+}
+
+export interface ZoomPaneMessage extends WsMessage {
+  type: 'zoom-pane';
+  paneId: string;
+}
+
+export interface BreakPaneMessage extends WsMessage {
+  type: 'break-pane';
+  paneId: string;
+  windowName?: string;
+}
+
+export interface JoinPaneMessage extends WsMessage {
+  type: 'join-pane';
+  sourcePaneId: string;
+  targetPaneId: string;
+  direction: SplitDirection;
+  percent?: number;
+}
+
+export interface PanesListMessage extends WsMessage {
+  type: 'panes-list';
+  sessionName: string;
+  windowIndex?: number;
+  panes: TmuxPane[];
+}
+
+export interface PaneUpdatedMessage extends WsMessage {
+  type: 'pane-updated';
+  action: PaneAction;
+  success: boolean;
+  paneId?: string;
+  error?: string;
+}
+
+export interface ReloadTmuxConfigMessage extends WsMessage {
+  type: 'reload-tmux-config';
+  // ~/.tmux.conf by default
+  path?: string;
+}
+
+export interface TmuxConfigReload {
+  path: string;
+  success: boolean;
+  errors: TmuxConfigError[];
+}
+
+export interface TmuxConfigError {
+  // The config file or one it sources
+  file?: string;
+  line?: number;
+  message: string;
+}
+
+export interface TmuxConfigReloadedMessage extends WsMessage, TmuxConfigReload {
+  type: 'tmux-config-reloaded';
+}
+
 // API response types
 export interface ApiResponse<T = unknown> {
   success: boolean;
@@ -131,11 +297,41 @@ export interface StatsMessage extends WsMessage {
   };
 }
 
+export interface NegotiateMessage extends WsMessage {
+  type: 'negotiate';
+  binaryFrames: boolean;
+  sequencedFrames?: boolean;
+}
+
+export interface NegotiatedMessage extends WsMessage {
+  type: 'negotiated';
+  protocolVersion: number;
+  binaryFrames: boolean;
+  sequencedFrames: boolean;
+}
+
+// Binary frame layout: [type: u8][payload length: u32 LE][payload]
+export const BINARY_FRAME_OUTPUT = 0x01;
+export const BINARY_FRAME_INPUT = 0x02;
+// Output payload prefixed with its u64 LE sequence number
+export const BINARY_FRAME_SEQUENCED_OUTPUT = 0x03;
+// Named channel frames, payload prefixed with the u32 LE stream id
+export const BINARY_FRAME_CHANNEL_OUTPUT = 0x04;
+export const BINARY_FRAME_CHANNEL_INPUT = 0x05;
+
 export interface ErrorMessage extends WsMessage {
   type: 'error';
   message: string;
 }
 
+export type ProtocolErrorCode = 'invalid-json' | 'invalid-envelope' | 'unknown-type' | 'invalid-message' | 'invalid-frame';
+
+export interface ProtocolErrorMessage extends WsMessage {
+  type: 'protocol-error';
+  code: ProtocolErrorCode;
+  message: string;
+}
+
 export interface WindowSelectedMessage extends WsMessage {
   type: 'window-selected';
   sessionName: string;
@@ -228,19 +424,26 @@ export interface CronJob {
   nextRun?: string;
   createdAt: string;
   updatedAt: string;
-  environment?: Record<string, string>;
+  environment?: Record<string, string>;  // names match [A-Za-z_][A-Za-z0-9_]*
   logOutput?: boolean;
   emailTo?: string;
-  tmuxSession?: string;
+  tmuxSession?: string;  // run in a window of this session (created when missing)
+  tmuxWindow?: string;  // reuse this window instead of opening one per run
+  keepWindowOnFailure?: boolean;
 }
 
 export interface JobExecution {
+  id: string;
   jobId: string;
   startedAt: string;
-  finishedAt?: string;
+  finishedAt?: string | null;
+  // null when the command was killed by a signal
+  exitCode: number | null;
   success: boolean;
-  output?: string;
-  error?: string;
+  // Captured stdout / stderr; omitted from cron-executions-list
+  output?: string | null;
+  error?: string | null;
+  truncated: boolean;
 }
 
 // Cron WebSocket messages
@@ -275,6 +478,31 @@ export interface TestCronCommandMessage extends WsMessage {
   command: string;
 }
 
+export interface PreviewCronScheduleMessage extends WsMessage {
+  type: 'preview-cron-schedule';
+  schedule: string;
+  count?: number;
+}
+
+export interface ListCronExecutionsMessage extends WsMessage {
+  type: 'list-cron-executions';
+  jobId: string;
+  limit?: number;
+}
+
+export interface GetCronExecutionMessage extends WsMessage {
+  type: 'get-cron-execution';
+  jobId: string;
+  executionId: string;
+}
+
+export interface PruneCronExecutionsMessage extends WsMessage {
+  type: 'prune-cron-executions';
+  jobId?: string;
+  keepLast?: number;
+  olderThanDays?: number;
+}
+
 // Cron server responses
 export interface CronJobsListMessage extends WsMessage {
   type: 'cron-jobs-list';
@@ -302,6 +530,40 @@ export interface CronCommandOutputMessage extends WsMessage {
   error?: string;
 }
 
+export type CronField = 'minute' | 'hour' | 'dayOfMonth' | 'month' | 'dayOfWeek';
+
+export interface CronScheduleError {
+  field: CronField | null;
+  value: string | null;
+  message: string;
+}
+
+export interface CronSchedulePreviewMessage extends WsMessage {
+  type: 'cron-schedule-preview';
+  schedule: string;
+  timezone: string;
+  daemonTimezone: string;  // server local time, which cron fires jobs in
+  nextRuns: string[];
+  error?: CronScheduleError;
+}
+
+export interface CronExecutionsListMessage extends WsMessage {
+  type: 'cron-executions-list';
+  jobId: string;
+  executions: JobExecution[];
+}
+
+export interface CronExecutionMessage extends WsMessage {
+  type: 'cron-execution';
+  execution: JobExecution;
+}
+
+export interface CronExecutionsPrunedMessage extends WsMessage {
+  type: 'cron-executions-pruned';
+  jobId?: string;
+  removed: number;
+}
+
 // Dotfile management types
 export type DotFileType = 'Shell' | 'Git' | 'Vim' | 'Tmux' | 'SSH' | 'Other';
 
@@ -314,20 +576,89 @@ export interface DotFile {
   readable: boolean;
   writable: boolean;
   fileType: DotFileType;
+  secrets: SecretFinding[];
+}
+
+export type SecretKind = 'private-key' | 'api-token' | 'env-variable' | 'credential' | 'url-password';
+
+// A secret found in a dotfile; the value itself is never sent
+export interface SecretFinding {
+  line: number;
+  kind: SecretKind;
+  // e.g. the variable name or the kind of token
+  label: string;
 }
 
+// 'external': found on disk without webmux having written it (edited elsewhere, or first seen)
+export type VersionSource = 'webmux' | 'restore' | 'external';
+
 export interface FileVersion {
   timestamp: string;
   content: string;
   size: number;
+  // SHA-256 of the content
   hash: string;
+  source: VersionSource;
+  // Git mode: the commit and its subject
+  revision?: string;
+  message?: string;
 }
 
 export interface DotFileTemplate {
   name: string;
   fileType: DotFileType;
   description: string;
+  // With {{variable}} placeholders
   content: string;
+  // Path the template is meant for, relative to $HOME
+  target?: string;
+  variables: TemplateVariable[];
+  // Shipped with webmux rather than loaded from the templates directory
+  builtin: boolean;
+}
+
+export type TemplateVariableType =
+  | { type: 'string' }
+  | { type: 'email' }
+  | { type: 'integer'; min?: number; max?: number }
+  | { type: 'boolean' }
+  | { type: 'choice'; options: string[] }
+  // A tmux key such as 'C-a'
+  | { type: 'key' };
+
+export type TemplateVariable = TemplateVariableType & {
+  name: string;
+  description?: string;
+  // A value is required when there is no default
+  default?: string;
+};
+
+// Dotfile bundles (HTTP: /api/dotfiles/export and /api/dotfiles/import)
+export type DotfileImportStatus = 'new' | 'identical' | 'update' | 'older' | 'conflict';
+
+export interface DotfileImportFile {
+  // Relative to $HOME
+  path: string;
+  status: DotfileImportStatus;
+  size: number;
+  hash: string;
+  currentHash?: string;
+  // From the file on disk to the incoming one
+  diff?: FileDiff;
+}
+
+export interface DotfileImportPreview {
+  importId: string;
+  exportedAt: string;
+  hostname: string;
+  files: DotfileImportFile[];
+}
+
+export interface DotfileImportResult {
+  path: string;
+  success: boolean;
+  backup?: string;
+  error?: string;
 }
 
 // Dotfile client messages
@@ -338,29 +669,124 @@ export interface ListDotfilesMessage extends WsMessage {
 export interface ReadDotfileMessage extends WsMessage {
   type: 'read-dotfile';
   path: string;
+  // Send secret values even when dotfiles.mask_secrets is on
+  reveal?: boolean;
 }
 
 export interface WriteDotfileMessage extends WsMessage {
   type: 'write-dotfile';
   path: string;
   content: string;
+  // Commit message in git mode
+  message?: string;
+  // Write even if the syntax check fails
+  force?: boolean;
+  // From dotfile-content; omitted when creating the file
+  etag?: string;
+}
+
+export interface ValidateDotfileMessage extends WsMessage {
+  type: 'validate-dotfile';
+  path: string;
+  content: string;
 }
 
 export interface GetDotfileHistoryMessage extends WsMessage {
   type: 'get-dotfile-history';
   path: string;
+  // Send secret values even when dotfiles.mask_secrets is on
+  reveal?: boolean;
 }
 
 export interface RestoreDotfileVersionMessage extends WsMessage {
   type: 'restore-dotfile-version';
   path: string;
-  timestamp: string;
+  // One of the two; revision only in git mode
+  timestamp?: string;
+  revision?: string;
+  // Restore even if the syntax check fails
+  force?: boolean;
+  // From dotfile-content; the restore is refused if the file changed since
+  etag?: string;
 }
 
 export interface GetDotfileTemplatesMessage extends WsMessage {
   type: 'get-dotfile-templates';
 }
 
+export interface ApplyDotfileTemplateMessage extends WsMessage {
+  type: 'apply-dotfile-template';
+  name: string;
+  // The template's target by default
+  path?: string;
+  values?: Record<string, string | number | boolean>;
+  // Only render and diff
+  dryRun?: boolean;
+  // Write even if the syntax check fails
+  force?: boolean;
+}
+
+export type DiffSide =
+  | { kind: 'current' }
+  // timestamp from get-dotfile-history, or revision in git mode
+  | { kind: 'version'; timestamp?: string; revision?: string }
+  // Rendered with values, defaults for the rest
+  | { kind: 'template'; name: string; values?: Record<string, string | number | boolean> }
+  | { kind: 'content'; content: string };
+
+export interface DiffDotfileMessage extends WsMessage {
+  type: 'diff-dotfile';
+  path: string;
+  from: DiffSide;
+  to: DiffSide;
+  // Unchanged lines around each change, 3 by default
+  context?: number;
+  // Send secret values even when dotfiles.mask_secrets is on
+  reveal?: boolean;
+}
+
+export interface ListTrackedDotfilesMessage extends WsMessage {
+  type: 'list-tracked-dotfiles';
+}
+
+export interface TrackDotfileMessage extends WsMessage {
+  type: 'track-dotfile';
+  // Path relative to $HOME, or a glob under ~/.config
+  pattern: string;
+}
+
+export interface UntrackDotfileMessage extends WsMessage {
+  type: 'untrack-dotfile';
+  pattern: string;
+}
+
+export interface BrowseDotfileDirMessage extends WsMessage {
+  type: 'browse-dotfile-dir';
+  // $HOME by default
+  path?: string;
+  // Levels to descend, 1 by default, at most 5
+  depth?: number;
+}
+
+// Git mode only
+export interface GetDotfilesStatusMessage extends WsMessage {
+  type: 'get-dotfiles-status';
+}
+
+export interface GetDotfilesGitDiffMessage extends WsMessage {
+  type: 'get-dotfiles-git-diff';
+  // Every tracked file when omitted
+  path?: string;
+}
+
+export interface PullDotfilesMessage extends WsMessage {
+  type: 'pull-dotfiles';
+}
+
+export interface PushDotfilesMessage extends WsMessage {
+  type: 'push-dotfiles';
+}
+
 // Dotfile server responses
 export interface DotfilesListMessage extends WsMessage {
   type: 'dotfiles-list';
@@ -371,6 +797,11 @@ export interface DotfileContentMessage extends WsMessage {
   type: 'dotfile-content';
   path: string;
   content: string;
+  secrets: SecretFinding[];
+  // Secret values in content were replaced by '<redacted>'
+  masked: boolean;
+  // Hash of the content on disk, for write-dotfile
+  etag?: string;
   error?: string;
 }
 
@@ -379,6 +810,44 @@ export interface DotfileWrittenMessage extends WsMessage {
   path: string;
   success: boolean;
   error?: string;
+  // Why the syntax check refused the write
+  diagnostics?: Diagnostic[];
+  // Set when the written tmux config was reloaded (dotfiles.reload_tmux_on_write)
+  tmuxReload?: TmuxConfigReload;
+  // Of the written content, for the next write
+  etag?: string;
+  // The file changed since it was read; nothing was written
+  conflict?: DotfileWriteConflict;
+}
+
+export interface DotfileMerge {
+  // Regions both sides changed differently are wrapped in <<<<<<< / ======= / >>>>>>> markers
+  content: string;
+  conflicts: number;
+}
+
+export interface DotfileWriteConflict {
+  // The file as it is on disk; empty when it was deleted
+  content: string;
+  // Of the content on disk; null when the file doesn't exist
+  etag: string | null;
+  // The edit and the disk content merged against the version that was read
+  merged?: DotfileMerge;
+}
+
+export interface Diagnostic {
+  // 1-based, when the checker reported one
+  line?: number;
+  message: string;
+}
+
+export interface DotfileValidationMessage extends WsMessage {
+  type: 'dotfile-validation';
+  path: string;
+  // Null when the file type has no checker or it isn't installed
+  validator: string | null;
+  valid: boolean;
+  diagnostics: Diagnostic[];
 }
 
 export interface DotfileHistoryMessage extends WsMessage {
@@ -392,6 +861,25 @@ export interface DotfileRestoredMessage extends WsMessage {
   path: string;
   success: boolean;
   error?: string;
+  // Why the syntax check refused the restore
+  diagnostics?: Diagnostic[];
+  // The file changed since it was read; nothing was written
+  conflict?: DotfileWriteConflict;
+}
+
+export interface DotfileTemplateAppliedMessage extends WsMessage {
+  type: 'dotfile-template-applied';
+  name: string;
+  success: boolean;
+  // Set on success
+  path?: string;
+  content?: string;
+  diff?: FileDiff;
+  // Copy of the previous content, when it was overwritten
+  backup?: string;
+  written?: boolean;
+  error?: string;
+  diagnostics?: Diagnostic[];
 }
 
 export interface DotfileTemplatesMessage extends WsMessage {
@@ -399,10 +887,210 @@ export interface DotfileTemplatesMessage extends WsMessage {
   templates: DotFileTemplate[];
 }
 
+export interface DiffLine {
+  kind: 'context' | 'added' | 'removed';
+  content: string;
+  // 1-based; absent on the side the line doesn't exist on
+  oldLine?: number;
+  newLine?: number;
+}
+
+export interface DiffHunk {
+  oldStart: number;
+  oldLines: number;
+  newStart: number;
+  newLines: number;
+  lines: DiffLine[];
+}
+
+export interface FileDiff {
+  added: number;
+  removed: number;
+  hunks: DiffHunk[];
+}
+
+export interface DotfileDiffMessage extends WsMessage {
+  type: 'dotfile-diff';
+  path: string;
+  diff?: FileDiff;
+  error?: string;
+}
+
+export interface GitFileStatus {
+  // Relative to the home directory
+  path: string;
+  // `git status --porcelain` code, e.g. ' M'
+  status: string;
+}
+
+export interface DotfilesGitStatus {
+  branch: string;
+  head: string | null;
+  remote: string | null;
+  // Relative to the remote branch as of the last pull/push
+  ahead: number;
+  behind: number;
+  files: GitFileStatus[];
+}
+
+export interface TrackedPattern {
+  pattern: string;
+  // Files it currently matches, relative to $HOME
+  matches: string[];
+}
+
+// Broadcast when a tracked dotfile is edited, created or deleted outside webmux
+export interface DotfileChangedMessage extends WsMessage {
+  type: 'dotfile-changed';
+  // Relative to $HOME, as in DotFile.name
+  name: string;
+  path: string;
+  change: 'created' | 'modified' | 'deleted';
+  // Of the new content; absent when the file was deleted
+  etag?: string;
+}
+
+export interface TrackedDotfilesMessage extends WsMessage {
+  type: 'tracked-dotfiles';
+  patterns: TrackedPattern[];
+}
+
+export interface DirEntry {
+  name: string;
+  path: string;
+  kind: 'file' | 'dir' | 'symlink' | 'other';
+  size: number;
+  modified: string | null;
+  // Octal, e.g. '0644'
+  mode: string;
+  // ls -l style, e.g. '-rw-r--r--'
+  permissions: string;
+  tracked: boolean;
+  children?: DirEntry[];
+}
+
+export interface DotfileDirListingMessage extends WsMessage {
+  type: 'dotfile-dir-listing';
+  path: string;
+  entries: DirEntry[];
+  truncated: boolean;
+}
+
+export interface SshOption {
+  keyword: string;
+  value: string;
+}
+
+// A `Host` block of ~/.ssh/config; also what add-ssh-host and update-ssh-host send
+export interface SshHost {
+  patterns: string[];
+  // The comment lines directly above the Host line, without '#'
+  comment?: string;
+  hostName?: string;
+  user?: string;
+  port?: number;
+  identityFiles?: string[];
+  proxyJump?: string;
+  // Every other option, in file order
+  options?: SshOption[];
+}
+
+export interface SshConfig {
+  path: string;
+  // Options before the first block
+  global: SshOption[];
+  // Addressed by index in add/update/remove-ssh-host
+  hosts: SshHost[];
+  // Absent when the file doesn't exist yet
+  etag?: string;
+}
+
+export interface SshConfigMessage extends WsMessage, SshConfig {
+  type: 'ssh-config';
+}
+
+export interface SshConfigUpdatedMessage extends WsMessage {
+  type: 'ssh-config-updated';
+  action: 'add' | 'update' | 'remove';
+  success: boolean;
+  config?: SshConfig;
+  error?: string;
+  // Why `ssh -G` refused the result
+  diagnostics?: Diagnostic[];
+  conflict?: DotfileWriteConflict;
+}
+
+export interface AuthorizedKeyOption {
+  name: string;
+  value?: string;
+}
+
+export interface AuthorizedKey {
+  // 1-based
+  line: number;
+  options: AuthorizedKeyOption[];
+  keyType: string;
+  // Base64 key blob
+  key: string;
+  comment?: string;
+  // 'SHA256:...', as ssh-keygen -l prints it
+  fingerprint: string;
+  // Absent for certificates
+  bits?: number;
+  // The key the backend's own SSH session logged in with; it can't be removed
+  current: boolean;
+}
+
+export interface AuthorizedKeys {
+  path: string;
+  // Addressed by index in update/remove-authorized-key
+  keys: AuthorizedKey[];
+  // Lines that aren't valid keys, kept as they are
+  invalid: { line: number; message: string }[];
+  // Absent when the file doesn't exist yet
+  etag?: string;
+}
+
+export interface AuthorizedKeysMessage extends WsMessage, AuthorizedKeys {
+  type: 'authorized-keys';
+}
+
+export interface AuthorizedKeysUpdatedMessage extends WsMessage {
+  type: 'authorized-keys-updated';
+  action: 'add' | 'update' | 'remove';
+  success: boolean;
+  keys?: AuthorizedKeys;
+  error?: string;
+  conflict?: DotfileWriteConflict;
+}
+
+export interface DotfilesStatusMessage extends WsMessage {
+  type: 'dotfiles-status';
+  status: DotfilesGitStatus;
+}
+
+export interface DotfilesGitDiffMessage extends WsMessage {
+  type: 'dotfiles-git-diff';
+  path?: string;
+  // Unified diff against HEAD
+  diff: string;
+}
+
+export interface DotfilesSyncedMessage extends WsMessage {
+  type: 'dotfiles-synced';
+  action: 'pull' | 'push';
+  success: boolean;
+  output: string;
+  error?: string;
+}
+
 // Union type for all server messages
 export type ServerMessage = 
   | SessionsListMessage
   | AttachedMessage
+  | ResumedMessage
+  | ResumeFailedMessage
+  | DetachedMessage
   | OutputMessage
   | DisconnectedMessage
   | WindowsListMessage
@@ -413,16 +1101,38 @@ export type ServerMessage =
   | WindowCreatedMessage
   | WindowKilledMessage
   | WindowRenamedMessage
+  | PanesListMessage
+  | PaneUpdatedMessage
+  | TmuxConfigReloadedMessage
   | StatsMessage
   | ErrorMessage
+  | ProtocolErrorMessage
+  | NegotiatedMessage
   | CronJobsListMessage
   | CronJobCreatedMessage
   | CronJobUpdatedMessage
   | CronJobDeletedMessage
   | CronCommandOutputMessage
+  | CronSchedulePreviewMessage
+  | CronExecutionsListMessage
+  | CronExecutionMessage
+  | CronExecutionsPrunedMessage
   | DotfilesListMessage
   | DotfileContentMessage
   | DotfileWrittenMessage
+  | DotfileValidationMessage
   | DotfileHistoryMessage
   | DotfileRestoredMessage
-  | DotfileTemplatesMessage;
\ No newline at end of file
+  | DotfileTemplatesMessage
+  | DotfileTemplateAppliedMessage
+  | DotfileDiffMessage
+  | DotfileChangedMessage
+  | TrackedDotfilesMessage
+  | DotfileDirListingMessage
+  | SshConfigMessage
+  | SshConfigUpdatedMessage
+  | AuthorizedKeysMessage
+  | AuthorizedKeysUpdatedMessage
+  | DotfilesStatusMessage
+  | DotfilesGitDiffMessage
+  | DotfilesSyncedMessage;
\ No newline at end of file